@@ -0,0 +1,450 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Assume an IAM role with STS
+
+use crate::sts_util::{default_session_name, into_credentials};
+use aws_auth::provider::AsyncAdapter;
+use aws_auth::{
+    AsyncCredentialsProvider, AsyncProvideCredentials, BoxFuture, Credentials, CredentialsError,
+    ProvideCredentials,
+};
+use aws_hyper::{DynConnector, SdkError, StandardClient};
+use aws_types::region::Region;
+use std::convert::TryFrom;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+
+const PROVIDER_NAME: &str = "AssumeRoleProvider";
+
+/// Credentials provider that assumes a role with STS
+///
+/// Credentials from a source provider are used to call
+/// [`AssumeRole`](https://docs.aws.amazon.com/STS/latest/APIReference/API_AssumeRole.html); the
+/// temporary credentials returned by STS are returned by this provider.
+///
+/// ```rust,no_run
+/// use aws_auth::Credentials;
+/// use aws_auth::provider::{BlockingAdapter, LazyCachingCredentialsProvider};
+/// use aws_auth_providers::assume_role::AssumeRoleProvider;
+/// use aws_types::region::Region;
+///
+/// let provider = AssumeRoleProvider::builder("arn:aws:iam::123456789012:role/demo")
+///     .session_name("demo-session")
+///     .region(Region::new("us-east-1"))
+///     .build(Credentials::from_keys("AKID", "SECRET", None));
+/// let provider = LazyCachingCredentialsProvider::new(BlockingAdapter::new(provider));
+/// ```
+pub struct AssumeRoleProvider {
+    role: AssumeRoleParams,
+    source: AsyncCredentialsProvider,
+    client: StandardClient,
+    conf: sts::Config,
+}
+
+impl Debug for AssumeRoleProvider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AssumeRoleProvider")
+            .field("role", &self.role)
+            .field("client", &self.client)
+            .field("conf", &self.conf)
+            .finish()
+    }
+}
+
+impl AssumeRoleProvider {
+    /// Create a builder for a provider that assumes `role_arn`
+    pub fn builder(role_arn: impl Into<String>) -> Builder {
+        Builder {
+            role: AssumeRoleParams::new(role_arn),
+            region: None,
+            connector: None,
+        }
+    }
+}
+
+impl AsyncProvideCredentials for AssumeRoleProvider {
+    fn provide_credentials<'a>(&'a self) -> BoxFuture<'a, Result<Credentials, CredentialsError>> {
+        Box::pin(
+            self.role
+                .assume_role(&self.client, &self.conf, self.source.clone()),
+        )
+    }
+}
+
+/// Builder for [`AssumeRoleProvider`]
+#[derive(Debug)]
+pub struct Builder {
+    role: AssumeRoleParams,
+    region: Option<Region>,
+    connector: Option<DynConnector>,
+}
+
+impl Builder {
+    /// Set the session name
+    ///
+    /// The session name appears in CloudTrail logs. If it is not set, a name based on the
+    /// current time is generated.
+    pub fn session_name(mut self, session_name: impl Into<String>) -> Self {
+        self.role.session_name = Some(session_name.into());
+        self
+    }
+
+    /// Set the external ID required by the role's trust policy
+    pub fn external_id(mut self, external_id: impl Into<String>) -> Self {
+        self.role.external_id = Some(external_id.into());
+        self
+    }
+
+    /// Set the duration of the role session
+    ///
+    /// If this is not set, STS uses the default duration of one hour.
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.role.duration = Some(duration);
+        self
+    }
+
+    /// Authenticate with an MFA device
+    ///
+    /// `serial_number` identifies the MFA device. `token_code` is called every time the role is
+    /// assumed, eg. to prompt for the current code.
+    pub fn mfa(
+        mut self,
+        serial_number: impl Into<String>,
+        token_code: impl Fn() -> Result<String, CredentialsError> + Send + Sync + 'static,
+    ) -> Self {
+        self.role.mfa = Some(Mfa {
+            serial_number: serial_number.into(),
+            token_code: Arc::new(token_code),
+        });
+        self
+    }
+
+    /// Set the region used to call STS
+    ///
+    /// If this is not set, the region is loaded from the default region provider.
+    pub fn region(mut self, region: Region) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Override the connector used to call STS
+    ///
+    /// By default, an HTTPS connector is used.
+    pub fn connector(mut self, connector: DynConnector) -> Self {
+        self.connector = Some(connector);
+        self
+    }
+
+    /// Build the provider, using credentials from `source` to assume the role
+    pub fn build(self, source: impl ProvideCredentials + 'static) -> AssumeRoleProvider {
+        self.build_async(AsyncAdapter::new(source))
+    }
+
+    /// Build the provider, using credentials from the async provider `source` to assume the role
+    pub fn build_async(self, source: impl AsyncProvideCredentials + 'static) -> AssumeRoleProvider {
+        AssumeRoleProvider {
+            role: self.role,
+            source: Arc::new(source),
+            client: sts_client(self.connector),
+            conf: sts_config(self.region),
+        }
+    }
+}
+
+/// Construct the client used to call STS
+pub(crate) fn sts_client(connector: Option<DynConnector>) -> StandardClient {
+    match connector {
+        Some(connector) => aws_hyper::Client::new(connector),
+        None => aws_hyper::Client::https(),
+    }
+}
+
+pub(crate) fn sts_config(region: Option<Region>) -> sts::Config {
+    let mut conf = sts::Config::builder();
+    if let Some(region) = region {
+        conf = conf.region(region);
+    }
+    conf.build()
+}
+
+/// The parameters of an `AssumeRole` call
+#[derive(Debug)]
+pub(crate) struct AssumeRoleParams {
+    pub(crate) role_arn: String,
+    pub(crate) session_name: Option<String>,
+    pub(crate) external_id: Option<String>,
+    pub(crate) duration: Option<Duration>,
+    pub(crate) mfa: Option<Mfa>,
+}
+
+pub(crate) struct Mfa {
+    serial_number: String,
+    token_code: Arc<dyn Fn() -> Result<String, CredentialsError> + Send + Sync>,
+}
+
+impl Debug for Mfa {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mfa")
+            .field("serial_number", &self.serial_number)
+            .finish()
+    }
+}
+
+impl AssumeRoleParams {
+    pub(crate) fn new(role_arn: impl Into<String>) -> Self {
+        AssumeRoleParams {
+            role_arn: role_arn.into(),
+            session_name: None,
+            external_id: None,
+            duration: None,
+            mfa: None,
+        }
+    }
+
+    /// Call `AssumeRole`, signing the request with credentials from `source`
+    pub(crate) async fn assume_role(
+        &self,
+        client: &StandardClient,
+        conf: &sts::Config,
+        source: AsyncCredentialsProvider,
+    ) -> Result<Credentials, CredentialsError> {
+        let session_name = self
+            .session_name
+            .clone()
+            .unwrap_or_else(|| default_session_name("assume-role-provider"));
+        let mut input = sts::input::AssumeRoleInput::builder()
+            .role_arn(&self.role_arn)
+            .role_session_name(session_name);
+        if let Some(external_id) = &self.external_id {
+            input = input.external_id(external_id);
+        }
+        if let Some(duration) = self.duration {
+            let seconds = i32::try_from(duration.as_secs()).map_err(|_| {
+                CredentialsError::Unhandled(
+                    format!(
+                        "role session duration of {} seconds is too long",
+                        duration.as_secs()
+                    )
+                    .into(),
+                )
+            })?;
+            input = input.duration_seconds(seconds);
+        }
+        if let Some(mfa) = &self.mfa {
+            input = input
+                .serial_number(&mfa.serial_number)
+                .token_code((mfa.token_code)()?);
+        }
+        let mut operation = input
+            .build()
+            .and_then(|input| input.make_operation(conf))
+            .map_err(|err| CredentialsError::Unhandled(err.into()))?;
+        // The source provider takes precedence over the credentials provider of the STS config
+        aws_auth::set_async_provider(&mut operation.config_mut(), source);
+        tracing::debug!(role_arn = %self.role_arn, "assuming role");
+        match client.call(operation).await {
+            Ok(output) => into_credentials(output.credentials, PROVIDER_NAME),
+            Err(SdkError::ServiceError { err, .. }) => Err(CredentialsError::Unhandled(err.into())),
+            Err(other) => Err(CredentialsError::Unhandled(other.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::assume_role::AssumeRoleProvider;
+    use aws_auth::{AsyncProvideCredentials, Credentials, CredentialsError};
+    use aws_hyper::test_connection::TestConnection;
+    use aws_hyper::DynConnector;
+    use aws_types::region::Region;
+    use smithy_http::body::SdkBody;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const ROLE_ARN: &str = "arn:aws:iam::123456789012:role/demo";
+
+    const RESPONSE: &str = r#"<AssumeRoleResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <AssumeRoleResult>
+    <Credentials>
+      <AccessKeyId>ASIARTEST</AccessKeyId>
+      <SecretAccessKey>secret</SecretAccessKey>
+      <SessionToken>token</SessionToken>
+      <Expiration>2021-09-21T04:16:53Z</Expiration>
+    </Credentials>
+  </AssumeRoleResult>
+</AssumeRoleResponse>"#;
+
+    /// A connection that answers `count` AssumeRole calls
+    fn sts(count: usize) -> TestConnection<&'static str> {
+        TestConnection::new(
+            (0..count)
+                .map(|_| {
+                    (
+                        http::Request::builder()
+                            .uri("https://sts.us-east-1.amazonaws.com/")
+                            .body(SdkBody::from(""))
+                            .unwrap(),
+                        http::Response::builder()
+                            .status(200)
+                            .body(RESPONSE)
+                            .unwrap(),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    fn provider(
+        connection: &TestConnection<&'static str>,
+        configure: impl FnOnce(crate::assume_role::Builder) -> crate::assume_role::Builder,
+    ) -> AssumeRoleProvider {
+        configure(
+            AssumeRoleProvider::builder(ROLE_ARN)
+                .region(Region::new("us-east-1"))
+                .connector(DynConnector::new(connection.clone())),
+        )
+        .build(Credentials::from_keys("AKID", "SECRET", None))
+    }
+
+    /// The form encoded bodies of the AssumeRole requests
+    fn request_bodies(connection: &TestConnection<&'static str>) -> Vec<String> {
+        connection
+            .requests()
+            .iter()
+            .map(|request| {
+                std::str::from_utf8(request.actual.body().bytes().expect("in-memory body"))
+                    .expect("utf-8 body")
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn session_name() {
+        let connection = sts(2);
+        let creds = provider(&connection, |builder| builder.session_name("demo-session"))
+            .provide_credentials()
+            .await
+            .expect("valid credentials");
+        assert_eq!(creds.access_key_id(), "ASIARTEST");
+        assert_eq!(creds.session_token(), Some("token"));
+        assert_eq!(creds.provider_name(), "AssumeRoleProvider");
+
+        provider(&connection, |builder| builder)
+            .provide_credentials()
+            .await
+            .expect("valid credentials");
+
+        let bodies = request_bodies(&connection);
+        assert!(bodies[0].contains("Action=AssumeRole"), "{}", bodies[0]);
+        assert!(
+            bodies[0].contains("RoleSessionName=demo-session"),
+            "{}",
+            bodies[0]
+        );
+        // without a session name, one is generated
+        assert!(
+            bodies[1].contains("RoleSessionName=assume-role-provider-"),
+            "{}",
+            bodies[1]
+        );
+        let auth = connection.requests()[0]
+            .actual
+            .headers()
+            .get("authorization")
+            .expect("signed request")
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(
+            auth.contains("Credential=AKID/"),
+            "the request should be signed with the source credentials: {}",
+            auth
+        );
+    }
+
+    #[tokio::test]
+    async fn external_id() {
+        let connection = sts(2);
+        provider(&connection, |builder| builder.external_id("my-external-id"))
+            .provide_credentials()
+            .await
+            .expect("valid credentials");
+        provider(&connection, |builder| builder)
+            .provide_credentials()
+            .await
+            .expect("valid credentials");
+
+        let bodies = request_bodies(&connection);
+        assert!(
+            bodies[0].contains("ExternalId=my-external-id"),
+            "{}",
+            bodies[0]
+        );
+        assert!(!bodies[1].contains("ExternalId"), "{}", bodies[1]);
+    }
+
+    #[tokio::test]
+    async fn mfa() {
+        let connection = sts(2);
+        let token_requests = Arc::new(AtomicUsize::new(0));
+        let counter = token_requests.clone();
+        let provider = provider(&connection, move |builder| {
+            builder.mfa("GAHT12345678", move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok("123456".to_string())
+            })
+        });
+        for _ in 0..2 {
+            provider
+                .provide_credentials()
+                .await
+                .expect("valid credentials");
+        }
+        // the token code is requested every time the role is assumed
+        assert_eq!(token_requests.load(Ordering::SeqCst), 2);
+        for body in request_bodies(&connection) {
+            assert!(body.contains("SerialNumber=GAHT12345678"), "{}", body);
+            assert!(body.contains("TokenCode=123456"), "{}", body);
+        }
+    }
+
+    #[tokio::test]
+    async fn mfa_token_code_error() {
+        let connection = sts(0);
+        let provider = provider(&connection, |builder| {
+            builder.mfa("GAHT12345678", || {
+                Err(CredentialsError::Unhandled("no MFA device".into()))
+            })
+        });
+        match provider.provide_credentials().await {
+            Err(CredentialsError::Unhandled(err)) => {
+                assert_eq!(format!("{}", err), "no MFA device")
+            }
+            other => panic!("expected unhandled error, got {:?}", other),
+        }
+        assert!(connection.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn duration_too_long() {
+        let connection = TestConnection::<&'static str>::new(vec![]);
+        let provider = AssumeRoleProvider::builder("arn:aws:iam::123456789012:role/demo")
+            .duration(Duration::from_secs(i32::MAX as u64 + 1))
+            .region(Region::new("us-east-1"))
+            .connector(DynConnector::new(connection.clone()))
+            .build(Credentials::from_keys("AKID", "SECRET", None));
+        match provider.provide_credentials().await {
+            Err(CredentialsError::Unhandled(err)) => {
+                assert!(format!("{}", err).contains("too long"), "{}", err)
+            }
+            other => panic!("expected unhandled error, got {:?}", other),
+        }
+        assert!(connection.requests().is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Credentials providers backed by AWS STS
+//!
+//! These providers live outside of `aws-auth` because they call STS, and the generated STS client
+//! itself depends on `aws-auth`.
+//!
+//! - [`AssumeRoleProvider`](assume_role::AssumeRoleProvider): assume a role using credentials
+//!   from another provider
+//! - [`WebIdentityTokenCredentialsProvider`](web_identity_token::WebIdentityTokenCredentialsProvider):
+//!   exchange an OIDC token (eg. an EKS service account token) for role credentials
+//! - [`ProfileFileCredentialsProvider`](profile::ProfileFileCredentialsProvider): the shared
+//!   config file provider, with support for `role_arn` & `source_profile` chaining
+//!
+//! Each provider calls STS every time credentials are requested. To cache the credentials until
+//! they expire, wrap the provider in a
+//! [`LazyCachingCredentialsProvider`](aws_auth::provider::LazyCachingCredentialsProvider).
+//!
+//! [`default_provider`] combines these providers with those of `aws-auth` into a chain that
+//! supersedes [`aws_auth::default_provider`]. Generated clients use `aws_auth::default_provider`
+//! unless configured otherwise, so the chain must be set explicitly, eg. with the
+//! `credentials_provider` method of a service config builder.
+
+use aws_auth::provider::{
+    BlockingAdapter, CredentialsProviderChain, EcsCredentialsProvider,
+    EnvironmentVariableCredentialsProvider, ImdsCredentialsProvider,
+    LazyCachingCredentialsProvider,
+};
+use aws_auth::ProvideCredentials;
+use aws_hyper::DynConnector;
+use aws_types::os_shim::{Env, Fs};
+use aws_types::region::Region;
+
+pub mod assume_role;
+pub mod profile;
+mod sts_util;
+pub mod web_identity_token;
+
+/// The default credentials provider chain, including providers that call STS
+///
+/// Credentials are loaded from, in order:
+/// 1. Environment variables: [`EnvironmentVariableCredentialsProvider`](aws_auth::provider::EnvironmentVariableCredentialsProvider)
+/// 2. The shared config & credentials files, assuming roles where configured: [`ProfileFileCredentialsProvider`](profile::ProfileFileCredentialsProvider)
+/// 3. A web identity token: [`WebIdentityTokenCredentialsProvider`](web_identity_token::WebIdentityTokenCredentialsProvider)
+/// 4. The ECS container credentials endpoint: [`EcsCredentialsProvider`](aws_auth::provider::EcsCredentialsProvider)
+/// 5. The EC2 Instance Metadata Service: [`ImdsCredentialsProvider`](aws_auth::provider::ImdsCredentialsProvider)
+///
+/// Loaded credentials are cached with [`LazyCachingCredentialsProvider`](aws_auth::provider::LazyCachingCredentialsProvider).
+pub fn default_provider() -> impl ProvideCredentials {
+    default_chain(Env::real(), Fs::real(), None, None)
+}
+
+fn default_chain(
+    env: Env,
+    fs: Fs,
+    sts_region: Option<Region>,
+    sts_connector: Option<DynConnector>,
+) -> LazyCachingCredentialsProvider<CredentialsProviderChain> {
+    let mut profile = profile::ProfileFileCredentialsProvider::builder()
+        .env(env.clone())
+        .fs(fs.clone());
+    let mut web_identity = web_identity_token::WebIdentityTokenCredentialsProvider::builder()
+        .env(env.clone())
+        .fs(fs);
+    if let Some(region) = sts_region {
+        profile = profile.region(region.clone());
+        web_identity = web_identity.region(region);
+    }
+    if let Some(connector) = sts_connector {
+        profile = profile.connector(connector.clone());
+        web_identity = web_identity.connector(connector);
+    }
+    let chain = CredentialsProviderChain::first_try(
+        "Environment",
+        EnvironmentVariableCredentialsProvider::for_env(env.clone()),
+    )
+    .or_else("Profile", BlockingAdapter::new(profile.build()))
+    .or_else(
+        "WebIdentityToken",
+        BlockingAdapter::new(web_identity.build()),
+    )
+    .or_else(
+        "EcsContainer",
+        BlockingAdapter::new(EcsCredentialsProvider::builder().env(env.clone()).build()),
+    )
+    .or_else(
+        "Ec2InstanceMetadata",
+        BlockingAdapter::new(ImdsCredentialsProvider::builder().env(env).build()),
+    );
+    LazyCachingCredentialsProvider::new(chain)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::default_chain;
+    use aws_auth::ProvideCredentials;
+    use aws_hyper::test_connection::TestConnection;
+    use aws_hyper::DynConnector;
+    use aws_types::os_shim::{Env, Fs};
+    use aws_types::region::Region;
+    use smithy_http::body::SdkBody;
+    use std::collections::HashMap;
+
+    const RESPONSE: &str = r#"<AssumeRoleResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <AssumeRoleResult>
+    <Credentials>
+      <AccessKeyId>ASIARTEST</AccessKeyId>
+      <SecretAccessKey>secret</SecretAccessKey>
+      <SessionToken>token</SessionToken>
+      <Expiration>2021-09-21T04:16:53Z</Expiration>
+    </Credentials>
+  </AssumeRoleResult>
+</AssumeRoleResponse>"#;
+
+    #[test]
+    fn default_chain_assumes_profile_roles() {
+        let mut fs = HashMap::new();
+        fs.insert(
+            "/home/.aws/config".to_string(),
+            br#"[default]
+role_arn = arn:aws:iam::123456789012:role/demo
+source_profile = base
+
+[profile base]
+aws_access_key_id = AKID
+aws_secret_access_key = secret
+"#
+            .to_vec(),
+        );
+        let connection = TestConnection::new(vec![(
+            http::Request::builder()
+                .uri("https://sts.us-east-1.amazonaws.com/")
+                .body(SdkBody::from(""))
+                .unwrap(),
+            http::Response::builder()
+                .status(200)
+                .body(RESPONSE)
+                .unwrap(),
+        )]);
+        let provider = default_chain(
+            Env::from_slice(&[("HOME", "/home")]),
+            Fs::from_map(fs),
+            Some(Region::new("us-east-1")),
+            Some(DynConnector::new(connection.clone())),
+        );
+        let creds = provider.provide_credentials().expect("valid credentials");
+        assert_eq!(creds.access_key_id(), "ASIARTEST");
+        assert_eq!(creds.provider_name(), "AssumeRoleProvider");
+
+        let requests = connection.requests();
+        assert_eq!(requests.len(), 1);
+        let request = &requests[0].actual;
+        let body = std::str::from_utf8(request.body().bytes().unwrap()).unwrap();
+        assert!(body.contains("Action=AssumeRole"), "{}", body);
+        assert!(
+            request
+                .headers()
+                .get("authorization")
+                .and_then(|auth| auth.to_str().ok())
+                .map(|auth| auth.contains("Credential=AKID/"))
+                .unwrap_or(false),
+            "the request should be signed with the source profile credentials"
+        );
+    }
+}
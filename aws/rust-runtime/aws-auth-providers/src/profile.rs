@@ -0,0 +1,657 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Load credentials from the shared config files, assuming roles where configured
+
+use crate::assume_role::{sts_client, sts_config, AssumeRoleParams};
+use aws_auth::provider::{
+    AsyncAdapter, CredentialProcessProvider, EcsCredentialsProvider,
+    EnvironmentVariableCredentialsProvider, ImdsCredentialsProvider,
+    ProfileFileCredentialsProvider as AwsAuthProfileProvider,
+};
+use aws_auth::{
+    AsyncCredentialsProvider, AsyncProvideCredentials, BoxFuture, Credentials, CredentialsError,
+    ProvideCredentials,
+};
+use aws_hyper::{DynConnector, StandardClient};
+use aws_types::os_shim::{Env, Fs};
+use aws_types::profile::{self, Profile, ProfileSet};
+use aws_types::region::Region;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Load credentials from the shared config & credentials files
+///
+/// Like [`aws_auth::provider::ProfileFileCredentialsProvider`], the active profile is selected with
+/// `AWS_PROFILE`. In addition to static credentials, profiles may assume a role:
+/// ```ini
+/// [profile dev]
+/// role_arn = arn:aws:iam::123456789012:role/dev
+/// source_profile = base
+/// external_id = my-external-id
+/// role_session_name = dev-session
+/// duration_seconds = 3600
+///
+/// [profile base]
+/// aws_access_key_id = AKID
+/// aws_secret_access_key = SECRET
+/// ```
+///
+/// A profile without `role_arn` must contain static credentials or a `credential_process`
+/// command. The credentials used to assume the role are loaded from either:
+/// - `source_profile`: another profile, which may itself assume a role. Static credentials in a
+///   source profile take precedence over its `role_arn`. A profile may name itself as its source
+///   profile to assume a role using its own static credentials.
+/// - `credential_source`: one of `Environment`, `EcsContainer` or `Ec2InstanceMetadata`
+///
+/// The profile files are re-read each time credentials are requested.
+#[derive(Debug)]
+pub struct ProfileFileCredentialsProvider {
+    fs: Fs,
+    env: Env,
+    client: StandardClient,
+    conf: sts::Config,
+}
+
+impl Default for ProfileFileCredentialsProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProfileFileCredentialsProvider {
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    async fn credentials(&self) -> Result<Credentials, CredentialsError> {
+        let profiles = profile::load(&self.fs, &self.env)
+            .map_err(|err| CredentialsError::Unhandled(err.into()))?;
+        let chain = resolve_chain(&profiles)?;
+        let mut credentials = self.base_credentials(&chain.base).await?;
+        for role in chain.roles {
+            tracing::info!(role_arn = %role.role_arn, "assuming role from profile");
+            let source: AsyncCredentialsProvider = Arc::new(AsyncAdapter::new(credentials));
+            credentials = role.assume_role(&self.client, &self.conf, source).await?;
+        }
+        Ok(credentials)
+    }
+
+    async fn base_credentials(&self, base: &BaseProvider) -> Result<Credentials, CredentialsError> {
+        match base {
+            BaseProvider::AccessKey(credentials) => Ok(credentials.clone()),
+            BaseProvider::CredentialProcess(command) => {
+                // Credential processes may run for a while (eg. to prompt for MFA): don't block
+                // the runtime
+                let provider = CredentialProcessProvider::new(command);
+                tokio::task::spawn_blocking(move || provider.provide_credentials())
+                    .await
+                    .map_err(|err| CredentialsError::Unhandled(err.into()))?
+            }
+            BaseProvider::CredentialSource(NamedSource::Environment) => {
+                AsyncAdapter::new(EnvironmentVariableCredentialsProvider::for_env(
+                    self.env.clone(),
+                ))
+                .provide_credentials()
+                .await
+            }
+            BaseProvider::CredentialSource(NamedSource::EcsContainer) => {
+                EcsCredentialsProvider::builder()
+                    .env(self.env.clone())
+                    .build()
+                    .provide_credentials()
+                    .await
+            }
+            BaseProvider::CredentialSource(NamedSource::Ec2InstanceMetadata) => {
+                ImdsCredentialsProvider::builder()
+                    .env(self.env.clone())
+                    .build()
+                    .provide_credentials()
+                    .await
+            }
+        }
+    }
+}
+
+impl AsyncProvideCredentials for ProfileFileCredentialsProvider {
+    fn provide_credentials<'a>(&'a self) -> BoxFuture<'a, Result<Credentials, CredentialsError>> {
+        Box::pin(self.credentials())
+    }
+}
+
+/// Builder for [`ProfileFileCredentialsProvider`]
+#[derive(Debug, Default)]
+pub struct Builder {
+    fs: Option<Fs>,
+    env: Option<Env>,
+    region: Option<Region>,
+    connector: Option<DynConnector>,
+}
+
+impl Builder {
+    /// Override the file system used to load the profile files
+    pub fn fs(mut self, fs: Fs) -> Self {
+        self.fs = Some(fs);
+        self
+    }
+
+    /// Override the environment used to select the profile & locate the profile files
+    pub fn env(mut self, env: Env) -> Self {
+        self.env = Some(env);
+        self
+    }
+
+    /// Set the region used to call STS
+    ///
+    /// If this is not set, the region is loaded from the default region provider.
+    pub fn region(mut self, region: Region) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Override the connector used to call STS
+    ///
+    /// By default, an HTTPS connector is used.
+    pub fn connector(mut self, connector: DynConnector) -> Self {
+        self.connector = Some(connector);
+        self
+    }
+
+    pub fn build(self) -> ProfileFileCredentialsProvider {
+        ProfileFileCredentialsProvider {
+            fs: self.fs.unwrap_or_default(),
+            env: self.env.unwrap_or_default(),
+            client: sts_client(self.connector),
+            conf: sts_config(self.region),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum ProfileChainError {
+    /// A profile set `role_arn` without `source_profile` or `credential_source`
+    MissingSource { profile: String },
+    /// A profile set both `source_profile` and `credential_source`
+    MultipleSources { profile: String },
+    /// A profile referenced a source profile that does not exist
+    MissingProfile { profile: String },
+    /// Source profiles formed a cycle
+    CredentialLoop { profiles: Vec<String> },
+    /// A profile assumes a role with its own credentials, but has no static credentials
+    MissingStaticCredentials { profile: String },
+    /// A profile field was invalid
+    InvalidField {
+        profile: String,
+        field: &'static str,
+        message: String,
+    },
+}
+
+impl Display for ProfileChainError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileChainError::MissingSource { profile } => write!(
+                f,
+                "profile `{}` set role_arn but neither source_profile nor credential_source",
+                profile
+            ),
+            ProfileChainError::MultipleSources { profile } => write!(
+                f,
+                "profile `{}` set both source_profile and credential_source",
+                profile
+            ),
+            ProfileChainError::MissingProfile { profile } => {
+                write!(f, "source profile `{}` does not exist", profile)
+            }
+            ProfileChainError::CredentialLoop { profiles } => {
+                write!(f, "source profiles form a cycle: {}", profiles.join(" -> "))
+            }
+            ProfileChainError::MissingStaticCredentials { profile } => write!(
+                f,
+                "profile `{}` is its own source profile but does not contain static credentials",
+                profile
+            ),
+            ProfileChainError::InvalidField {
+                profile,
+                field,
+                message,
+            } => write!(
+                f,
+                "invalid value for `{}` in profile `{}`: {}",
+                field, profile, message
+            ),
+        }
+    }
+}
+
+impl Error for ProfileChainError {}
+
+impl From<ProfileChainError> for CredentialsError {
+    fn from(err: ProfileChainError) -> Self {
+        CredentialsError::Unhandled(err.into())
+    }
+}
+
+/// The named credential sources that may be used with `credential_source`
+#[derive(Debug, PartialEq, Eq)]
+enum NamedSource {
+    Environment,
+    EcsContainer,
+    Ec2InstanceMetadata,
+}
+
+/// The credentials at the root of a profile chain
+#[derive(Debug)]
+enum BaseProvider {
+    AccessKey(Credentials),
+    CredentialProcess(String),
+    CredentialSource(NamedSource),
+}
+
+/// A resolved profile chain: load base credentials, then assume each role in turn
+#[derive(Debug)]
+struct ProfileChain {
+    base: BaseProvider,
+    roles: Vec<AssumeRoleParams>,
+}
+
+/// Walk the source profiles starting from the selected profile
+fn resolve_chain(profiles: &ProfileSet) -> Result<ProfileChain, CredentialsError> {
+    let mut visited: Vec<&str> = vec![];
+    let mut roles = vec![];
+    let mut current = profiles.selected_profile();
+    let base = loop {
+        let profile = match profiles.get_profile(current) {
+            Some(profile) => profile,
+            // The selected profile does not exist: no credentials are configured
+            None if visited.is_empty() => return Err(CredentialsError::CredentialsNotLoaded),
+            None => {
+                return Err(ProfileChainError::MissingProfile {
+                    profile: current.to_string(),
+                }
+                .into())
+            }
+        };
+        if visited.contains(&current) {
+            let mut profiles: Vec<String> = visited.iter().map(|p| p.to_string()).collect();
+            profiles.push(current.to_string());
+            return Err(ProfileChainError::CredentialLoop { profiles }.into());
+        }
+        visited.push(current);
+        // Static credentials of a source profile take precedence over its role_arn
+        if visited.len() > 1 {
+            if let Some(credentials) = AwsAuthProfileProvider::static_credentials(profile)? {
+                break BaseProvider::AccessKey(credentials);
+            }
+        }
+        let role_arn = match profile.get("role_arn") {
+            Some(role_arn) => role_arn,
+            None => match AwsAuthProfileProvider::static_credentials(profile)? {
+                Some(credentials) => break BaseProvider::AccessKey(credentials),
+                None => match profile.get("credential_process") {
+                    Some(command) => break BaseProvider::CredentialProcess(command.to_string()),
+                    None => return Err(CredentialsError::CredentialsNotLoaded),
+                },
+            },
+        };
+        roles.push(role_params(profile, role_arn)?);
+        match (
+            profile.get("source_profile"),
+            profile.get("credential_source"),
+        ) {
+            (Some(_), Some(_)) => {
+                return Err(ProfileChainError::MultipleSources {
+                    profile: current.to_string(),
+                }
+                .into())
+            }
+            (None, None) => {
+                return Err(ProfileChainError::MissingSource {
+                    profile: current.to_string(),
+                }
+                .into())
+            }
+            (None, Some(source)) => {
+                break BaseProvider::CredentialSource(named_source(profile, source)?)
+            }
+            // A profile may assume a role using its own static credentials
+            (Some(source_profile), None) if source_profile == current => {
+                match AwsAuthProfileProvider::static_credentials(profile)? {
+                    Some(credentials) => break BaseProvider::AccessKey(credentials),
+                    None => {
+                        return Err(ProfileChainError::MissingStaticCredentials {
+                            profile: current.to_string(),
+                        }
+                        .into())
+                    }
+                }
+            }
+            (Some(source_profile), None) => current = source_profile,
+        }
+    };
+    // Roles were collected starting from the selected profile, but are assumed starting from the base
+    roles.reverse();
+    Ok(ProfileChain { base, roles })
+}
+
+fn role_params(profile: &Profile, role_arn: &str) -> Result<AssumeRoleParams, ProfileChainError> {
+    let mut role = AssumeRoleParams::new(role_arn);
+    role.session_name = profile.get("role_session_name").map(|s| s.to_string());
+    role.external_id = profile.get("external_id").map(|s| s.to_string());
+    if let Some(duration) = profile.get("duration_seconds") {
+        let seconds = duration
+            .parse::<u64>()
+            .map_err(|err| ProfileChainError::InvalidField {
+                profile: profile.name().to_string(),
+                field: "duration_seconds",
+                message: err.to_string(),
+            })?;
+        role.duration = Some(Duration::from_secs(seconds));
+    }
+    Ok(role)
+}
+
+fn named_source(profile: &Profile, source: &str) -> Result<NamedSource, ProfileChainError> {
+    match source {
+        "Environment" => Ok(NamedSource::Environment),
+        "EcsContainer" => Ok(NamedSource::EcsContainer),
+        "Ec2InstanceMetadata" => Ok(NamedSource::Ec2InstanceMetadata),
+        other => Err(ProfileChainError::InvalidField {
+            profile: profile.name().to_string(),
+            field: "credential_source",
+            message: format!(
+                "unsupported credential source `{}`. Expected one of Environment, EcsContainer or Ec2InstanceMetadata",
+                other
+            ),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::profile::{
+        resolve_chain, BaseProvider, NamedSource, ProfileChain, ProfileFileCredentialsProvider,
+    };
+    use aws_auth::{AsyncProvideCredentials, CredentialsError};
+    use aws_hyper::test_connection::TestConnection;
+    use aws_hyper::DynConnector;
+    use aws_types::os_shim::{Env, Fs};
+    use aws_types::profile;
+    use aws_types::region::Region;
+    use smithy_http::body::SdkBody;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    fn chain(config: &str, env: &[(&str, &str)]) -> Result<ProfileChain, CredentialsError> {
+        let mut fs = HashMap::new();
+        fs.insert("/home/.aws/config".to_string(), config.as_bytes().to_vec());
+        let mut env = env.to_vec();
+        env.push(("HOME", "/home"));
+        let profiles =
+            profile::load(&Fs::from_map(fs), &Env::from_slice(&env)).expect("valid profiles");
+        resolve_chain(&profiles)
+    }
+
+    fn role_arns(chain: &ProfileChain) -> Vec<&str> {
+        chain
+            .roles
+            .iter()
+            .map(|role| role.role_arn.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn static_credentials() {
+        let chain = chain(
+            "[default]\naws_access_key_id = AKID\naws_secret_access_key = secret",
+            &[],
+        )
+        .expect("valid chain");
+        match chain.base {
+            BaseProvider::AccessKey(creds) => assert_eq!(creds.access_key_id(), "AKID"),
+            other => panic!("expected static credentials, got {:?}", other),
+        }
+        assert!(chain.roles.is_empty());
+    }
+
+    #[test]
+    fn chained_roles() {
+        let chain = chain(
+            r#"[default]
+role_arn = arn:aws:iam::123456789012:role/outer
+source_profile = middle
+external_id = external
+role_session_name = session
+duration_seconds = 900
+
+[profile middle]
+role_arn = arn:aws:iam::123456789012:role/inner
+source_profile = base
+
+[profile base]
+aws_access_key_id = AKID
+aws_secret_access_key = secret
+"#,
+            &[],
+        )
+        .expect("valid chain");
+        match &chain.base {
+            BaseProvider::AccessKey(creds) => assert_eq!(creds.access_key_id(), "AKID"),
+            other => panic!("expected static credentials, got {:?}", other),
+        }
+        assert_eq!(
+            role_arns(&chain),
+            vec![
+                "arn:aws:iam::123456789012:role/inner",
+                "arn:aws:iam::123456789012:role/outer"
+            ]
+        );
+        let outer = &chain.roles[1];
+        assert_eq!(outer.external_id.as_deref(), Some("external"));
+        assert_eq!(outer.session_name.as_deref(), Some("session"));
+        assert_eq!(outer.duration, Some(Duration::from_secs(900)));
+    }
+
+    #[test]
+    fn source_profile_static_credentials_take_precedence() {
+        let chain = chain(
+            r#"[default]
+role_arn = arn:aws:iam::123456789012:role/outer
+source_profile = base
+
+[profile base]
+role_arn = arn:aws:iam::123456789012:role/ignored
+source_profile = other
+aws_access_key_id = AKID
+aws_secret_access_key = secret
+"#,
+            &[],
+        )
+        .expect("valid chain");
+        assert_eq!(
+            role_arns(&chain),
+            vec!["arn:aws:iam::123456789012:role/outer"]
+        );
+    }
+
+    #[test]
+    fn self_referential_profile() {
+        let chain = chain(
+            r#"[default]
+role_arn = arn:aws:iam::123456789012:role/demo
+source_profile = default
+aws_access_key_id = AKID
+aws_secret_access_key = secret
+"#,
+            &[],
+        )
+        .expect("valid chain");
+        assert!(matches!(chain.base, BaseProvider::AccessKey(_)));
+        assert_eq!(
+            role_arns(&chain),
+            vec!["arn:aws:iam::123456789012:role/demo"]
+        );
+    }
+
+    #[test]
+    fn source_profile_credential_process() {
+        let chain = chain(
+            r#"[default]
+role_arn = arn:aws:iam::123456789012:role/demo
+source_profile = sso
+
+[profile sso]
+credential_process = sso-helper --account 123456789012
+"#,
+            &[],
+        )
+        .expect("valid chain");
+        match &chain.base {
+            BaseProvider::CredentialProcess(command) => {
+                assert_eq!(command, "sso-helper --account 123456789012")
+            }
+            other => panic!("expected a credential process, got {:?}", other),
+        }
+        assert_eq!(
+            role_arns(&chain),
+            vec!["arn:aws:iam::123456789012:role/demo"]
+        );
+    }
+
+    #[test]
+    fn credential_source() {
+        let chain = chain(
+            "[profile dev]\nrole_arn = arn:aws:iam::123456789012:role/dev\ncredential_source = Ec2InstanceMetadata",
+            &[("AWS_PROFILE", "dev")],
+        )
+        .expect("valid chain");
+        assert!(matches!(
+            chain.base,
+            BaseProvider::CredentialSource(NamedSource::Ec2InstanceMetadata)
+        ));
+    }
+
+    #[test]
+    fn invalid_chains() {
+        let invalid = [
+            "[default]\nrole_arn = arn:aws:iam::123456789012:role/demo",
+            "[default]\nrole_arn = arn:aws:iam::123456789012:role/demo\nsource_profile = missing",
+            "[default]\nrole_arn = arn:aws:iam::123456789012:role/demo\nsource_profile = default",
+            "[default]\nrole_arn = arn:aws:iam::123456789012:role/demo\ncredential_source = Unknown",
+            "[default]\nrole_arn = arn:aws:iam::123456789012:role/demo\nsource_profile = a\ncredential_source = Environment\n[profile a]\naws_access_key_id = AKID\naws_secret_access_key = secret",
+            "[default]\nrole_arn = arn:aws:iam::123456789012:role/a\nsource_profile = a\n[profile a]\nrole_arn = arn:aws:iam::123456789012:role/b\nsource_profile = default",
+        ];
+        for config in &invalid {
+            match chain(config, &[]) {
+                Err(CredentialsError::Unhandled(_)) => {}
+                other => panic!("expected an error for {}, got {:?}", config, other),
+            }
+        }
+    }
+
+    #[test]
+    fn no_credentials() {
+        assert!(matches!(
+            chain("[default]\nregion = us-east-1", &[]),
+            Err(CredentialsError::CredentialsNotLoaded)
+        ));
+        assert!(matches!(
+            chain("[profile other]\nregion = us-east-1", &[]),
+            Err(CredentialsError::CredentialsNotLoaded)
+        ));
+    }
+
+    fn assume_role_response(access_key_id: &str) -> http::Response<String> {
+        http::Response::builder()
+            .status(200)
+            .body(format!(
+                r#"<AssumeRoleResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <AssumeRoleResult>
+    <Credentials>
+      <AccessKeyId>{}</AccessKeyId>
+      <SecretAccessKey>secret</SecretAccessKey>
+      <SessionToken>token</SessionToken>
+      <Expiration>2021-09-21T04:16:53Z</Expiration>
+    </Credentials>
+  </AssumeRoleResult>
+</AssumeRoleResponse>"#,
+                access_key_id
+            ))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn source_profile_chaining() {
+        let mut fs = HashMap::new();
+        fs.insert(
+            "/home/.aws/config".to_string(),
+            br#"[default]
+role_arn = arn:aws:iam::123456789012:role/second
+role_session_name = second-session
+source_profile = first
+
+[profile first]
+role_arn = arn:aws:iam::123456789012:role/first
+role_session_name = first-session
+source_profile = base
+
+[profile base]
+aws_access_key_id = AKID
+aws_secret_access_key = secret
+"#
+            .to_vec(),
+        );
+        let request = || {
+            http::Request::builder()
+                .uri("https://sts.us-east-1.amazonaws.com/")
+                .body(SdkBody::from(""))
+                .unwrap()
+        };
+        let connection = TestConnection::new(vec![
+            (request(), assume_role_response("ASIAFIRST")),
+            (request(), assume_role_response("ASIASECOND")),
+        ]);
+        let provider = ProfileFileCredentialsProvider::builder()
+            .fs(Fs::from_map(fs))
+            .env(Env::from_slice(&[("HOME", "/home")]))
+            .region(Region::new("us-east-1"))
+            .connector(DynConnector::new(connection.clone()))
+            .build();
+        let creds = provider
+            .provide_credentials()
+            .await
+            .expect("valid credentials");
+        assert_eq!(creds.access_key_id(), "ASIASECOND");
+
+        // each role is assumed with the credentials of its source profile
+        let requests = connection.requests();
+        let expected = [
+            ("first-session", "Credential=AKID/", false),
+            ("second-session", "Credential=ASIAFIRST/", true),
+        ];
+        assert_eq!(requests.len(), expected.len());
+        for (request, (session_name, credential, session_token)) in requests.iter().zip(&expected) {
+            let request = &request.actual;
+            let body = std::str::from_utf8(request.body().bytes().unwrap()).unwrap();
+            assert!(
+                body.contains(&format!("RoleSessionName={}", session_name)),
+                "{}",
+                body
+            );
+            let auth = request.headers()["authorization"].to_str().unwrap();
+            assert!(auth.contains(credential), "{}", auth);
+            assert_eq!(
+                request.headers().contains_key("x-amz-security-token"),
+                *session_token
+            );
+        }
+    }
+}
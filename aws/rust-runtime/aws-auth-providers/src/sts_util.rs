@@ -0,0 +1,92 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Helpers shared by the STS-backed credentials providers
+
+use aws_auth::{Credentials, CredentialsError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Convert the credentials returned by STS into [`Credentials`]
+pub(crate) fn into_credentials(
+    sts_credentials: Option<sts::model::Credentials>,
+    provider_name: &'static str,
+) -> Result<Credentials, CredentialsError> {
+    let sts_credentials = sts_credentials.ok_or_else(|| {
+        CredentialsError::Unhandled("STS response did not contain credentials".into())
+    })?;
+    let expiration = sts_credentials
+        .expiration
+        .and_then(|expiration| expiration.to_system_time())
+        .ok_or_else(|| {
+            CredentialsError::Unhandled("STS credentials did not have a valid expiration".into())
+        })?;
+    Ok(Credentials::new(
+        sts_credentials.access_key_id.ok_or_else(|| {
+            CredentialsError::Unhandled("STS credentials did not contain an access key".into())
+        })?,
+        sts_credentials.secret_access_key.ok_or_else(|| {
+            CredentialsError::Unhandled("STS credentials did not contain a secret key".into())
+        })?,
+        sts_credentials.session_token,
+        Some(expiration),
+        provider_name,
+    ))
+}
+
+/// Create a session name for a role session when one was not configured
+pub(crate) fn default_session_name(base: &str) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("post epoch");
+    format!("{}-{}", base, now.as_millis())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sts_util::{default_session_name, into_credentials};
+    use aws_auth::CredentialsError;
+    use smithy_types::Instant;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn convert_credentials() {
+        let sts_credentials = sts::model::Credentials::builder()
+            .access_key_id("ASIARTEST")
+            .secret_access_key("secret")
+            .session_token("token")
+            .expiration(Instant::from_epoch_seconds(1632197813))
+            .build();
+        let creds = into_credentials(Some(sts_credentials), "test").expect("valid credentials");
+        assert_eq!(creds.access_key_id(), "ASIARTEST");
+        assert_eq!(creds.secret_access_key(), "secret");
+        assert_eq!(creds.session_token(), Some("token"));
+        assert_eq!(
+            creds.expiry(),
+            Some(UNIX_EPOCH + Duration::from_secs(1632197813))
+        );
+        assert_eq!(creds.provider_name(), "test");
+    }
+
+    #[test]
+    fn missing_credentials() {
+        assert!(matches!(
+            into_credentials(None, "test"),
+            Err(CredentialsError::Unhandled(_))
+        ));
+        let no_expiration = sts::model::Credentials::builder()
+            .access_key_id("ASIARTEST")
+            .secret_access_key("secret")
+            .build();
+        assert!(matches!(
+            into_credentials(Some(no_expiration), "test"),
+            Err(CredentialsError::Unhandled(_))
+        ));
+    }
+
+    #[test]
+    fn session_names_are_prefixed() {
+        assert!(default_session_name("assume-role-provider").starts_with("assume-role-provider-"));
+    }
+}
@@ -0,0 +1,295 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Exchange a web identity token for role credentials
+
+use crate::assume_role::sts_config;
+use crate::sts_util::{default_session_name, into_credentials};
+use aws_auth::{AsyncProvideCredentials, BoxFuture, Credentials, CredentialsError};
+use aws_endpoint::AwsEndpointStage;
+use aws_http::user_agent::UserAgentStage;
+use aws_hyper::{DynConnector, SdkError};
+use aws_types::os_shim::{Env, Fs};
+use aws_types::region::Region;
+use smithy_http_tower::map_request::MapRequestLayer;
+use tower::layer::util::Stack;
+use tower::ServiceBuilder;
+
+const ENV_VAR_TOKEN_FILE: &str = "AWS_WEB_IDENTITY_TOKEN_FILE";
+const ENV_VAR_ROLE_ARN: &str = "AWS_ROLE_ARN";
+const ENV_VAR_SESSION_NAME: &str = "AWS_ROLE_SESSION_NAME";
+
+const PROVIDER_NAME: &str = "WebIdentityToken";
+
+/// Credentials provider for a web identity token
+///
+/// The token (eg. the service account token of an EKS pod) is read from a file and exchanged for
+/// role credentials with
+/// [`AssumeRoleWithWebIdentity`](https://docs.aws.amazon.com/STS/latest/APIReference/API_AssumeRoleWithWebIdentity.html).
+/// The provider is configured with environment variables:
+/// - `AWS_WEB_IDENTITY_TOKEN_FILE`: the path of the token file. The file is re-read every time
+///   credentials are loaded since the token may be rotated.
+/// - `AWS_ROLE_ARN`: the ARN of the role to assume
+/// - `AWS_ROLE_SESSION_NAME` (optional): the session name. If it is not set, a name based on the
+///   current time is generated.
+///
+/// If `AWS_WEB_IDENTITY_TOKEN_FILE` is not set, [`CredentialsError::CredentialsNotLoaded`] is
+/// returned.
+///
+/// `AssumeRoleWithWebIdentity` is authenticated by the token itself, so the request is not signed.
+#[derive(Debug)]
+pub struct WebIdentityTokenCredentialsProvider {
+    env: Env,
+    fs: Fs,
+    client: smithy_client::Client<DynConnector, UnsignedMiddleware>,
+    conf: sts::Config,
+}
+
+impl Default for WebIdentityTokenCredentialsProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebIdentityTokenCredentialsProvider {
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    async fn credentials(&self) -> Result<Credentials, CredentialsError> {
+        let token_file = match self.env.get(ENV_VAR_TOKEN_FILE) {
+            Ok(token_file) => token_file,
+            Err(_) => {
+                tracing::debug!("web identity token file is not configured");
+                return Err(CredentialsError::CredentialsNotLoaded);
+            }
+        };
+        let role_arn = self.env.get(ENV_VAR_ROLE_ARN).map_err(|_| {
+            CredentialsError::Unhandled(
+                format!(
+                    "{} was set but {} was not",
+                    ENV_VAR_TOKEN_FILE, ENV_VAR_ROLE_ARN
+                )
+                .into(),
+            )
+        })?;
+        let session_name = self
+            .env
+            .get(ENV_VAR_SESSION_NAME)
+            .unwrap_or_else(|_| default_session_name("web-identity-token"));
+        let token = self
+            .fs
+            .read_to_end(&token_file)
+            .map_err(|err| CredentialsError::Unhandled(err.into()))?;
+        let token =
+            String::from_utf8(token).map_err(|err| CredentialsError::Unhandled(err.into()))?;
+        let operation = sts::input::AssumeRoleWithWebIdentityInput::builder()
+            .role_arn(role_arn)
+            .role_session_name(session_name)
+            .web_identity_token(token)
+            .build()
+            .and_then(|input| input.make_operation(&self.conf))
+            .map_err(|err| CredentialsError::Unhandled(err.into()))?;
+        match self.client.call(operation).await {
+            Ok(output) => into_credentials(output.credentials, PROVIDER_NAME),
+            Err(SdkError::ServiceError { err, .. }) => Err(CredentialsError::Unhandled(err.into())),
+            Err(other) => Err(CredentialsError::Unhandled(other.into())),
+        }
+    }
+}
+
+impl AsyncProvideCredentials for WebIdentityTokenCredentialsProvider {
+    fn provide_credentials<'a>(&'a self) -> BoxFuture<'a, Result<Credentials, CredentialsError>> {
+        Box::pin(self.credentials())
+    }
+}
+
+/// Builder for [`WebIdentityTokenCredentialsProvider`]
+#[derive(Debug, Default)]
+pub struct Builder {
+    env: Option<Env>,
+    fs: Option<Fs>,
+    region: Option<Region>,
+    connector: Option<DynConnector>,
+}
+
+impl Builder {
+    /// Override the environment used to configure the provider
+    pub fn env(mut self, env: Env) -> Self {
+        self.env = Some(env);
+        self
+    }
+
+    /// Override the file system used to read the token file
+    pub fn fs(mut self, fs: Fs) -> Self {
+        self.fs = Some(fs);
+        self
+    }
+
+    /// Set the region used to call STS
+    ///
+    /// If this is not set, the region is loaded from the default region provider.
+    pub fn region(mut self, region: Region) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Override the connector used to call STS
+    ///
+    /// By default, an HTTPS connector is used.
+    pub fn connector(mut self, connector: DynConnector) -> Self {
+        self.connector = Some(connector);
+        self
+    }
+
+    pub fn build(self) -> WebIdentityTokenCredentialsProvider {
+        let client = match self.connector {
+            Some(connector) => smithy_client::Client::new(connector),
+            None => smithy_client::Client::https(),
+        };
+        WebIdentityTokenCredentialsProvider {
+            env: self.env.unwrap_or_default(),
+            fs: self.fs.unwrap_or_default(),
+            client,
+            conf: sts_config(self.region),
+        }
+    }
+}
+
+type UnsignedMiddlewareStack =
+    Stack<MapRequestLayer<UserAgentStage>, MapRequestLayer<AwsEndpointStage>>;
+
+/// The standard AWS middleware without the SigV4 signer
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub(crate) struct UnsignedMiddleware;
+
+impl<S> tower::Layer<S> for UnsignedMiddleware {
+    type Service = <UnsignedMiddlewareStack as tower::Layer<S>>::Service;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ServiceBuilder::new()
+            .layer(MapRequestLayer::for_mapper(AwsEndpointStage))
+            .layer(MapRequestLayer::for_mapper(UserAgentStage::new()))
+            .service(inner)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::web_identity_token::WebIdentityTokenCredentialsProvider;
+    use aws_auth::{AsyncProvideCredentials, CredentialsError};
+    use aws_hyper::test_connection::TestConnection;
+    use aws_hyper::DynConnector;
+    use aws_types::os_shim::{Env, Fs};
+    use aws_types::region::Region;
+    use smithy_http::body::SdkBody;
+    use std::collections::HashMap;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    const RESPONSE: &str = r#"<AssumeRoleWithWebIdentityResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <AssumeRoleWithWebIdentityResult>
+    <Credentials>
+      <AccessKeyId>ASIARTEST</AccessKeyId>
+      <SecretAccessKey>secret</SecretAccessKey>
+      <SessionToken>token</SessionToken>
+      <Expiration>2021-09-21T04:16:53Z</Expiration>
+    </Credentials>
+  </AssumeRoleWithWebIdentityResult>
+</AssumeRoleWithWebIdentityResponse>"#;
+
+    fn provider(
+        env: &[(&str, &str)],
+        connection: &TestConnection<&'static str>,
+    ) -> WebIdentityTokenCredentialsProvider {
+        let mut fs = HashMap::new();
+        fs.insert("/token.jwt".to_string(), b"web-identity-token".to_vec());
+        WebIdentityTokenCredentialsProvider::builder()
+            .env(Env::from_slice(env))
+            .fs(Fs::from_map(fs))
+            .region(Region::new("us-east-1"))
+            .connector(DynConnector::new(connection.clone()))
+            .build()
+    }
+
+    #[tokio::test]
+    async fn load_credentials() {
+        let connection = TestConnection::new(vec![(
+            http::Request::builder()
+                .uri("https://sts.us-east-1.amazonaws.com/")
+                .body(SdkBody::from(""))
+                .unwrap(),
+            http::Response::builder()
+                .status(200)
+                .body(RESPONSE)
+                .unwrap(),
+        )]);
+        let provider = provider(
+            &[
+                ("AWS_WEB_IDENTITY_TOKEN_FILE", "/token.jwt"),
+                ("AWS_ROLE_ARN", "arn:aws:iam::123456789012:role/test"),
+                ("AWS_ROLE_SESSION_NAME", "test-session"),
+            ],
+            &connection,
+        );
+        let creds = provider
+            .provide_credentials()
+            .await
+            .expect("valid credentials");
+        assert_eq!(creds.access_key_id(), "ASIARTEST");
+        assert_eq!(creds.secret_access_key(), "secret");
+        assert_eq!(creds.session_token(), Some("token"));
+        assert_eq!(
+            creds.expiry(),
+            Some(UNIX_EPOCH + Duration::from_secs(1632197813))
+        );
+        assert_eq!(creds.provider_name(), "WebIdentityToken");
+
+        let requests = connection.requests();
+        let request = &requests[0].actual;
+        assert!(request.headers().get("authorization").is_none());
+        let body = std::str::from_utf8(request.body().bytes().unwrap()).unwrap();
+        assert!(
+            body.contains("Action=AssumeRoleWithWebIdentity"),
+            "{}",
+            body
+        );
+        assert!(
+            body.contains("WebIdentityToken=web-identity-token"),
+            "{}",
+            body
+        );
+        assert!(body.contains("RoleSessionName=test-session"), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn not_configured() {
+        let connection = TestConnection::new(vec![]);
+        match provider(&[], &connection).provide_credentials().await {
+            Err(CredentialsError::CredentialsNotLoaded) => {}
+            other => panic!("expected credentials not loaded, got {:?}", other),
+        }
+        assert!(connection.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn missing_role_arn() {
+        let connection = TestConnection::new(vec![]);
+        let provider = provider(
+            &[("AWS_WEB_IDENTITY_TOKEN_FILE", "/token.jwt")],
+            &connection,
+        );
+        match provider.provide_credentials().await {
+            Err(CredentialsError::Unhandled(err)) => {
+                assert!(format!("{}", err).contains("AWS_ROLE_ARN"), "{}", err)
+            }
+            other => panic!("expected unhandled error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,823 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Raw IMDSv2 Client
+//!
+//! Client for direct access to the [EC2 Instance Metadata Service](https://docs.aws.amazon.com/AWSEC2/latest/UserGuide/configuring-instance-metadata-service.html)
+//! (IMDS). The client implements the IMDSv2 session token handshake: a token is acquired with a
+//! `PUT` request, cached for its TTL, and sent along with every metadata request.
+//!
+//! Most users will not need to use this client directly. See [`ImdsRegionProvider`] and
+//! [`ImdsCredentialsProvider`](crate::provider::ImdsCredentialsProvider).
+
+mod region;
+
+pub use region::ImdsRegionProvider;
+
+use aws_types::os_shim::Env;
+use bytes::Bytes;
+use http::uri::{InvalidUri, PathAndQuery};
+use http::{HeaderValue, Method, Response, StatusCode, Uri};
+use hyper::client::HttpConnector;
+use smithy_client::erase::DynConnector;
+use smithy_client::{retry, SdkError, SdkSuccess};
+use smithy_http::body::SdkBody;
+use smithy_http::operation::{self, Metadata, Operation};
+use smithy_http::response::ParseStrictResponse;
+use smithy_http::retry::ClassifyResponse;
+use smithy_types::retry::{ErrorKind, ProvideErrorKind, RetryKind};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tower::layer::util::Identity;
+use tower::timeout::Timeout;
+
+const TOKEN_PATH: &str = "/latest/api/token";
+const X_AWS_EC2_METADATA_TOKEN_TTL_SECONDS: &str = "x-aws-ec2-metadata-token-ttl-seconds";
+const X_AWS_EC2_METADATA_TOKEN: &str = "x-aws-ec2-metadata-token";
+
+const ENV_ENDPOINT: &str = "AWS_EC2_METADATA_SERVICE_ENDPOINT";
+const ENV_ENDPOINT_MODE: &str = "AWS_EC2_METADATA_SERVICE_ENDPOINT_MODE";
+const ENV_DISABLED: &str = "AWS_EC2_METADATA_DISABLED";
+
+const DEFAULT_TOKEN_TTL: Duration = Duration::from_secs(21_600);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Tokens are refreshed this long before they actually expire
+const TOKEN_REFRESH_BUFFER: Duration = Duration::from_secs(120);
+
+/// IMDSv2 Client
+///
+/// Clients are cheap to clone: clones share a connection & a cached session token.
+///
+/// # Examples
+/// ```rust,no_run
+/// # async fn docs() {
+/// use aws_auth::imds::Client;
+/// let client = Client::builder().build().expect("valid IMDS configuration");
+/// let ami_id = client
+///     .get("/latest/meta-data/ami-id")
+///     .await
+///     .expect("failure communicating with IMDS");
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Client {
+    inner: Arc<ClientInner>,
+}
+
+#[derive(Debug)]
+struct ClientInner {
+    endpoint: Uri,
+    token_ttl: Duration,
+    token: Mutex<Option<Token>>,
+    smithy_client: smithy_client::Client<DynConnector, Identity>,
+}
+
+#[derive(Clone)]
+struct Token {
+    value: HeaderValue,
+    refresh_at: Instant,
+}
+
+impl Debug for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Token")
+            .field("value", &"** redacted **")
+            .field("refresh_at", &self.refresh_at)
+            .finish()
+    }
+}
+
+impl Client {
+    /// IMDS client builder
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// Retrieve information from IMDS
+    ///
+    /// `path` is the absolute path of the metadata to load, eg. `/latest/meta-data/ami-id`. A
+    /// session token is loaded (or reused from the cache) before the request is sent. If IMDS
+    /// rejects the session token, a new token is loaded and the request is retried once.
+    pub async fn get(&self, path: &str) -> Result<String, ImdsError> {
+        let token = self.token(false).await?;
+        match self.get_with_token(path, &token).await {
+            Err(ImdsError::ErrorResponse { raw }) if raw.status() == StatusCode::UNAUTHORIZED => {
+                tracing::debug!("IMDS rejected the session token, loading a new token");
+                let token = self.token(true).await?;
+                self.get_with_token(path, &token).await
+            }
+            result => result,
+        }
+    }
+
+    async fn get_with_token(&self, path: &str, token: &HeaderValue) -> Result<String, ImdsError> {
+        let request = http::Request::builder()
+            .method(Method::GET)
+            .uri(self.uri(path).map_err(ImdsError::Unexpected)?)
+            .header(X_AWS_EC2_METADATA_TOKEN, token.clone())
+            // unlike `SdkBody::empty()`, an in-memory body can be retried
+            .body(SdkBody::from(""))
+            .map_err(|err| ImdsError::Unexpected(err.into()))?;
+        let operation = Operation::new(operation::Request::new(request), ImdsGetResponseHandler)
+            .with_metadata(Metadata::new("get", "imds"))
+            .with_retry_policy(ImdsErrorPolicy);
+        match self.inner.smithy_client.call(operation).await {
+            Ok(response) => Ok(response),
+            Err(SdkError::ServiceError {
+                err: InnerImdsError::BadStatus,
+                raw,
+            }) => Err(ImdsError::ErrorResponse { raw }),
+            Err(SdkError::ServiceError { err, .. }) => Err(ImdsError::Unexpected(err.into())),
+            Err(SdkError::DispatchFailure(err)) | Err(SdkError::ResponseError { err, .. }) => {
+                Err(ImdsError::IoError(err))
+            }
+            Err(SdkError::ConstructionFailure(err)) => Err(ImdsError::Unexpected(err)),
+        }
+    }
+
+    /// Load a session token, reusing the cached token if it is still valid
+    async fn token(&self, force_refresh: bool) -> Result<HeaderValue, ImdsError> {
+        // Holding the lock while the token loads ensures that concurrent requests share a token
+        let mut cached = self.inner.token.lock().await;
+        match &*cached {
+            Some(token) if !force_refresh && Instant::now() < token.refresh_at => {
+                Ok(token.value.clone())
+            }
+            _ => {
+                let token = self.load_token().await?;
+                let value = token.value.clone();
+                *cached = Some(token);
+                Ok(value)
+            }
+        }
+    }
+
+    async fn load_token(&self) -> Result<Token, ImdsError> {
+        let request = http::Request::builder()
+            .method(Method::PUT)
+            .uri(self.uri(TOKEN_PATH).map_err(ImdsError::Unexpected)?)
+            .header(
+                X_AWS_EC2_METADATA_TOKEN_TTL_SECONDS,
+                self.inner.token_ttl.as_secs(),
+            )
+            .body(SdkBody::from(""))
+            .map_err(|err| ImdsError::Unexpected(err.into()))?;
+        let handler = GetTokenResponseHandler {
+            token_ttl: self.inner.token_ttl,
+        };
+        let operation = Operation::new(operation::Request::new(request), handler)
+            .with_metadata(Metadata::new("get-token", "imds"))
+            .with_retry_policy(ImdsErrorPolicy);
+        self.inner
+            .smithy_client
+            .call(operation)
+            .await
+            .map_err(ImdsError::FailedToLoadToken)
+    }
+
+    fn uri(&self, path: &str) -> Result<Uri, Box<dyn Error + Send + Sync + 'static>> {
+        let mut parts = self.inner.endpoint.clone().into_parts();
+        parts.path_and_query = Some(PathAndQuery::try_from(path)?);
+        Ok(Uri::from_parts(parts)?)
+    }
+}
+
+/// An error retrieving metadata from IMDS
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ImdsError {
+    /// An IMDSv2 session token could not be loaded
+    FailedToLoadToken(SdkError<InnerImdsError>),
+
+    /// The metadata request returned a non-200 status code
+    ErrorResponse { raw: http::Response<SdkBody> },
+
+    /// The request could not be dispatched or no response was received, eg. because it timed out
+    IoError(Box<dyn Error + Send + Sync + 'static>),
+
+    /// An unexpected error occurred, eg. the response was not valid UTF-8
+    Unexpected(Box<dyn Error + Send + Sync + 'static>),
+}
+
+impl Display for ImdsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ImdsError::FailedToLoadToken(inner) => {
+                write!(f, "Failed to load session token: {}", inner)
+            }
+            ImdsError::ErrorResponse { raw } => write!(
+                f,
+                "Error response from IMDS (code: {}). {:?}",
+                raw.status().as_u16(),
+                raw
+            ),
+            ImdsError::IoError(err) => {
+                write!(f, "An IO error occurred communicating with IMDS: {}", err)
+            }
+            ImdsError::Unexpected(err) => write!(
+                f,
+                "An unexpected error occurred communicating with IMDS: {}",
+                err
+            ),
+        }
+    }
+}
+
+impl Error for ImdsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ImdsError::FailedToLoadToken(inner) => Some(inner as _),
+            ImdsError::IoError(err) | ImdsError::Unexpected(err) => Some(err.as_ref() as _),
+            ImdsError::ErrorResponse { .. } => None,
+        }
+    }
+}
+
+/// Error parsing an IMDS response
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum InnerImdsError {
+    /// IMDS returned a non-200 status code
+    BadStatus,
+
+    /// The response body was not valid UTF-8
+    InvalidUtf8,
+
+    /// The session token returned by IMDS was not a valid header value
+    InvalidToken,
+}
+
+impl Display for InnerImdsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            InnerImdsError::BadStatus => write!(f, "failing status code returned from IMDS"),
+            InnerImdsError::InvalidUtf8 => write!(f, "IMDS did not return valid UTF-8"),
+            InnerImdsError::InvalidToken => write!(f, "IMDS returned an invalid session token"),
+        }
+    }
+}
+
+impl Error for InnerImdsError {}
+
+impl ProvideErrorKind for InnerImdsError {
+    fn retryable_error_kind(&self) -> Option<ErrorKind> {
+        None
+    }
+
+    fn code(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Addressing mode of the IMDS endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EndpointMode {
+    /// IPv4 mode: `http://169.254.169.254`
+    ///
+    /// This is the default unless otherwise specified.
+    IpV4,
+
+    /// IPv6 mode: `http://[fd00:ec2::254]`
+    IpV6,
+}
+
+impl EndpointMode {
+    fn endpoint(&self) -> Uri {
+        match self {
+            EndpointMode::IpV4 => Uri::from_static("http://169.254.169.254"),
+            EndpointMode::IpV6 => Uri::from_static("http://[fd00:ec2::254]"),
+        }
+    }
+}
+
+impl FromStr for EndpointMode {
+    type Err = BuildError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            _ if value.eq_ignore_ascii_case("ipv4") => Ok(EndpointMode::IpV4),
+            _ if value.eq_ignore_ascii_case("ipv6") => Ok(EndpointMode::IpV6),
+            other => Err(BuildError::InvalidEndpointMode(other.to_string())),
+        }
+    }
+}
+
+/// An IMDS client could not be constructed
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BuildError {
+    /// The endpoint mode was not `IPv4` or `IPv6`
+    InvalidEndpointMode(String),
+
+    /// The endpoint was not a valid URI
+    InvalidEndpointUri(InvalidUri),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::InvalidEndpointMode(mode) => write!(
+                f,
+                "`{}` is not a valid endpoint mode. Valid values are [`IPv4`, `IPv6`]",
+                mode
+            ),
+            BuildError::InvalidEndpointUri(err) => write!(f, "invalid IMDS endpoint: {}", err),
+        }
+    }
+}
+
+impl Error for BuildError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BuildError::InvalidEndpointUri(err) => Some(err as _),
+            BuildError::InvalidEndpointMode(_) => None,
+        }
+    }
+}
+
+/// Builder for an IMDSv2 [`Client`]
+///
+/// Unless set explicitly, the endpoint is loaded from the `AWS_EC2_METADATA_SERVICE_ENDPOINT` &
+/// `AWS_EC2_METADATA_SERVICE_ENDPOINT_MODE` environment variables.
+#[derive(Debug, Default)]
+pub struct Builder {
+    endpoint: Option<Uri>,
+    mode: Option<EndpointMode>,
+    token_ttl: Option<Duration>,
+    timeout: Option<Duration>,
+    retry_config: Option<retry::Config>,
+    connector: Option<DynConnector>,
+    env: Env,
+}
+
+impl Builder {
+    /// Override the endpoint of the client
+    ///
+    /// This takes precedence over the endpoint mode & the endpoint environment variables.
+    pub fn endpoint(mut self, endpoint: Uri) -> Self {
+        self.endpoint = Some(endpoint);
+        self
+    }
+
+    /// Use the default IMDS endpoint for an [`EndpointMode`]
+    pub fn endpoint_mode(mut self, mode: EndpointMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Override the TTL requested for session tokens (default: 6 hours)
+    pub fn token_ttl(mut self, ttl: Duration) -> Self {
+        self.token_ttl = Some(ttl);
+        self
+    }
+
+    /// Override the timeout for each individual request attempt (default: 1 second)
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Override the retry configuration
+    ///
+    /// Failed attempts are retried when the request times out, no response is received, or when
+    /// IMDS returns a 5xx status code.
+    pub fn retry_config(mut self, config: retry::Config) -> Self {
+        self.retry_config = Some(config);
+        self
+    }
+
+    /// Override the connector used to dispatch requests (default: a plain-HTTP Hyper connector)
+    ///
+    /// This is mostly useful for testing, eg. with a [`TestConnection`](smithy_client::test_connection::TestConnection).
+    pub fn connector(mut self, connector: DynConnector) -> Self {
+        self.connector = Some(connector);
+        self
+    }
+
+    /// Override the environment used to load endpoint configuration
+    pub fn env(mut self, env: Env) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Build an IMDSv2 Client
+    pub fn build(self) -> Result<Client, BuildError> {
+        let endpoint = self.resolve_endpoint()?;
+        let timeout = self.timeout.unwrap_or(DEFAULT_TIMEOUT);
+        let builder = smithy_client::Builder::new().middleware(Identity::new());
+        let smithy_client = match self.connector {
+            Some(connector) => builder
+                .connector(connector)
+                .map_connector(|conn| Timeout::new(conn, timeout))
+                .build()
+                .into_dyn_connector(),
+            None => builder
+                .hyper(hyper::Client::builder().build(HttpConnector::new()))
+                .map_connector(|conn| Timeout::new(conn, timeout))
+                .build()
+                .into_dyn_connector(),
+        };
+        let smithy_client = smithy_client.with_retry_config(self.retry_config.unwrap_or_default());
+        Ok(Client {
+            inner: Arc::new(ClientInner {
+                endpoint,
+                token_ttl: self.token_ttl.unwrap_or(DEFAULT_TOKEN_TTL),
+                token: Mutex::new(None),
+                smithy_client,
+            }),
+        })
+    }
+
+    fn resolve_endpoint(&self) -> Result<Uri, BuildError> {
+        if let Some(endpoint) = &self.endpoint {
+            return Ok(endpoint.clone());
+        }
+        if let Ok(endpoint) = self.env.get(ENV_ENDPOINT) {
+            return Uri::from_str(&endpoint).map_err(BuildError::InvalidEndpointUri);
+        }
+        let mode = match (self.mode, self.env.get(ENV_ENDPOINT_MODE)) {
+            (Some(mode), _) => mode,
+            (None, Ok(mode)) => mode.parse()?,
+            (None, Err(_)) => EndpointMode::IpV4,
+        };
+        Ok(mode.endpoint())
+    }
+}
+
+/// Returns true when IMDS has been disabled with `AWS_EC2_METADATA_DISABLED=true`
+pub(crate) fn imds_disabled(env: &Env) -> bool {
+    env.get(ENV_DISABLED)
+        .map(|disabled| disabled.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+#[derive(Clone)]
+struct ImdsGetResponseHandler;
+
+impl ParseStrictResponse for ImdsGetResponseHandler {
+    type Output = Result<String, InnerImdsError>;
+
+    fn parse(&self, response: &Response<Bytes>) -> Self::Output {
+        if response.status().is_success() {
+            std::str::from_utf8(response.body().as_ref())
+                .map(|data| data.to_string())
+                .map_err(|_| InnerImdsError::InvalidUtf8)
+        } else {
+            Err(InnerImdsError::BadStatus)
+        }
+    }
+}
+
+#[derive(Clone)]
+struct GetTokenResponseHandler {
+    token_ttl: Duration,
+}
+
+impl ParseStrictResponse for GetTokenResponseHandler {
+    type Output = Result<Token, InnerImdsError>;
+
+    fn parse(&self, response: &Response<Bytes>) -> Self::Output {
+        if !response.status().is_success() {
+            return Err(InnerImdsError::BadStatus);
+        }
+        let mut value = HeaderValue::from_maybe_shared(response.body().clone())
+            .map_err(|_| InnerImdsError::InvalidToken)?;
+        value.set_sensitive(true);
+        // IMDS echoes the TTL of the token. If it is missing, assume we got the TTL we asked for.
+        let ttl = response
+            .headers()
+            .get(X_AWS_EC2_METADATA_TOKEN_TTL_SECONDS)
+            .and_then(|ttl| ttl.to_str().ok())
+            .and_then(|ttl| ttl.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(self.token_ttl);
+        Ok(Token {
+            value,
+            refresh_at: Instant::now() + ttl.saturating_sub(TOKEN_REFRESH_BUFFER),
+        })
+    }
+}
+
+/// IMDS retry policy
+///
+/// Requests are retried when they fail to dispatch (including timeouts) or when IMDS returns a
+/// 5xx status code.
+#[derive(Clone)]
+struct ImdsErrorPolicy;
+
+impl ImdsErrorPolicy {
+    fn classify(response: &http::Response<SdkBody>) -> RetryKind {
+        if response.status().is_server_error() {
+            RetryKind::Error(ErrorKind::ServerError)
+        } else {
+            RetryKind::NotRetryable
+        }
+    }
+}
+
+impl<T, E> ClassifyResponse<SdkSuccess<T>, SdkError<E>> for ImdsErrorPolicy {
+    fn classify(&self, response: Result<&SdkSuccess<T>, &SdkError<E>>) -> RetryKind {
+        match response {
+            Ok(_) => RetryKind::NotRetryable,
+            Err(SdkError::ResponseError { raw, .. }) | Err(SdkError::ServiceError { raw, .. }) => {
+                Self::classify(raw)
+            }
+            Err(SdkError::DispatchFailure(_)) => RetryKind::Error(ErrorKind::TransientError),
+            Err(SdkError::ConstructionFailure(_)) => RetryKind::NotRetryable,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::imds::{BuildError, Client, EndpointMode, ImdsError};
+    use aws_types::os_shim::Env;
+    use http::Uri;
+    use smithy_client::erase::DynConnector;
+    use smithy_client::test_connection::TestConnection;
+    use smithy_http::body::SdkBody;
+    use std::time::Duration;
+    use tower::BoxError;
+
+    const TOKEN_A: &str = "AQAEAFTNrA4eEGx0AQgJ1arIq_Cc-t4tWt3fB0Hd8RKhXlKc5ccvhg==";
+    const TOKEN_B: &str = "alternatetoken==";
+
+    pub(crate) fn token_request(base: &str, ttl: u32) -> http::Request<SdkBody> {
+        http::Request::builder()
+            .uri(format!("{}/latest/api/token", base))
+            .header("x-aws-ec2-metadata-token-ttl-seconds", ttl)
+            .method("PUT")
+            .body(SdkBody::empty())
+            .unwrap()
+    }
+
+    pub(crate) fn token_response(ttl: u32, token: &'static str) -> http::Response<&'static str> {
+        http::Response::builder()
+            .status(200)
+            .header("X-aws-ec2-metadata-token-ttl-seconds", ttl)
+            .body(token)
+            .unwrap()
+    }
+
+    pub(crate) fn imds_request(path: &'static str, token: &str) -> http::Request<SdkBody> {
+        http::Request::builder()
+            .uri(Uri::from_static(path))
+            .method("GET")
+            .header("x-aws-ec2-metadata-token", token)
+            .body(SdkBody::empty())
+            .unwrap()
+    }
+
+    pub(crate) fn imds_response(body: &'static str) -> http::Response<&'static str> {
+        http::Response::builder().status(200).body(body).unwrap()
+    }
+
+    fn response(status: u16) -> http::Response<&'static str> {
+        http::Response::builder().status(status).body("").unwrap()
+    }
+
+    pub(crate) fn make_client(conn: &TestConnection<&'static str>) -> Client {
+        Client::builder()
+            .env(Env::from_slice(&[]))
+            .connector(DynConnector::new(conn.clone()))
+            .build()
+            .expect("valid client")
+    }
+
+    fn assert_requests_match(conn: &TestConnection<&'static str>) {
+        for request in conn.requests().iter() {
+            request.assert_matches(vec![]);
+        }
+    }
+
+    #[tokio::test]
+    async fn client_caches_token() {
+        let connection = TestConnection::new(vec![
+            (
+                token_request("http://169.254.169.254", 21600),
+                token_response(21600, TOKEN_A),
+            ),
+            (
+                imds_request("http://169.254.169.254/latest/metadata", TOKEN_A),
+                imds_response(r#"test-imds-output"#),
+            ),
+            (
+                imds_request("http://169.254.169.254/latest/metadata2", TOKEN_A),
+                imds_response("output2"),
+            ),
+        ]);
+        let client = make_client(&connection);
+        let metadata = client.get("/latest/metadata").await.expect("failed");
+        assert_eq!(metadata, "test-imds-output");
+        let metadata = client.get("/latest/metadata2").await.expect("failed");
+        assert_eq!(metadata, "output2");
+        assert_eq!(connection.requests().len(), 3);
+        assert_requests_match(&connection);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn token_refreshed_before_expiry() {
+        let connection = TestConnection::new(vec![
+            (
+                token_request("http://[fd00:ec2::254]", 600),
+                token_response(600, TOKEN_A),
+            ),
+            (
+                imds_request("http://[fd00:ec2::254]/latest/metadata", TOKEN_A),
+                imds_response("output1"),
+            ),
+            (
+                imds_request("http://[fd00:ec2::254]/latest/metadata", TOKEN_A),
+                imds_response("output2"),
+            ),
+            (
+                token_request("http://[fd00:ec2::254]", 600),
+                token_response(600, TOKEN_B),
+            ),
+            (
+                imds_request("http://[fd00:ec2::254]/latest/metadata", TOKEN_B),
+                imds_response("output3"),
+            ),
+        ]);
+        let client = Client::builder()
+            .env(Env::from_slice(&[]))
+            .endpoint_mode(EndpointMode::IpV6)
+            .token_ttl(Duration::from_secs(600))
+            .connector(DynConnector::new(connection.clone()))
+            .build()
+            .expect("valid client");
+
+        assert_eq!(client.get("/latest/metadata").await.unwrap(), "output1");
+        tokio::time::advance(Duration::from_secs(400)).await;
+        assert_eq!(client.get("/latest/metadata").await.unwrap(), "output2");
+        // the token expires in 600 seconds but is refreshed 120 seconds early
+        tokio::time::advance(Duration::from_secs(100)).await;
+        assert_eq!(client.get("/latest/metadata").await.unwrap(), "output3");
+        assert_eq!(connection.requests().len(), 5);
+        assert_requests_match(&connection);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_500() {
+        let connection = TestConnection::new(vec![
+            (
+                token_request("http://169.254.169.254", 21600),
+                token_response(21600, TOKEN_A),
+            ),
+            (
+                imds_request("http://169.254.169.254/latest/metadata", TOKEN_A),
+                response(500),
+            ),
+            (
+                imds_request("http://169.254.169.254/latest/metadata", TOKEN_A),
+                imds_response("ok"),
+            ),
+        ]);
+        let client = make_client(&connection);
+        assert_eq!(client.get("/latest/metadata").await.expect("success"), "ok");
+        assert_requests_match(&connection);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_token_failure() {
+        let connection = TestConnection::new(vec![
+            (
+                token_request("http://169.254.169.254", 21600),
+                response(500),
+            ),
+            (
+                token_request("http://169.254.169.254", 21600),
+                token_response(21600, TOKEN_A),
+            ),
+            (
+                imds_request("http://169.254.169.254/latest/metadata", TOKEN_A),
+                imds_response("ok"),
+            ),
+        ]);
+        let client = make_client(&connection);
+        assert_eq!(client.get("/latest/metadata").await.expect("success"), "ok");
+        assert_requests_match(&connection);
+    }
+
+    #[tokio::test]
+    async fn retry_401_with_new_token() {
+        let connection = TestConnection::new(vec![
+            (
+                token_request("http://169.254.169.254", 21600),
+                token_response(21600, TOKEN_A),
+            ),
+            (
+                imds_request("http://169.254.169.254/latest/metadata", TOKEN_A),
+                response(401),
+            ),
+            (
+                token_request("http://169.254.169.254", 21600),
+                token_response(21600, TOKEN_B),
+            ),
+            (
+                imds_request("http://169.254.169.254/latest/metadata", TOKEN_B),
+                imds_response("ok"),
+            ),
+        ]);
+        let client = make_client(&connection);
+        assert_eq!(client.get("/latest/metadata").await.expect("success"), "ok");
+        assert_requests_match(&connection);
+    }
+
+    #[tokio::test]
+    async fn no_retry_on_404() {
+        let connection = TestConnection::new(vec![
+            (
+                token_request("http://169.254.169.254", 21600),
+                token_response(21600, TOKEN_A),
+            ),
+            (
+                imds_request("http://169.254.169.254/latest/metadata", TOKEN_A),
+                response(404),
+            ),
+        ]);
+        let client = make_client(&connection);
+        match client.get("/latest/metadata").await {
+            Err(ImdsError::ErrorResponse { raw }) => assert_eq!(raw.status(), 404),
+            other => panic!("expected 404 error response, got {:?}", other),
+        }
+        assert_eq!(connection.requests().len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn request_timeout() {
+        let connector = tower::service_fn(|_request: http::Request<SdkBody>| async {
+            std::future::pending::<Result<http::Response<SdkBody>, BoxError>>().await
+        });
+        let client = Client::builder()
+            .env(Env::from_slice(&[]))
+            .timeout(Duration::from_secs(1))
+            .connector(DynConnector::new(connector))
+            .build()
+            .expect("valid client");
+        let start = tokio::time::Instant::now();
+        match client.get("/latest/metadata").await {
+            Err(ImdsError::FailedToLoadToken(smithy_client::SdkError::DispatchFailure(_))) => {}
+            other => panic!("expected a dispatch failure, got {:?}", other),
+        }
+        // 3 attempts, each of which times out after 1 second
+        assert!(start.elapsed() >= Duration::from_secs(3));
+    }
+
+    #[test]
+    fn endpoint_config() {
+        let endpoint = |env: &[(&str, &str)]| {
+            Client::builder()
+                .env(Env::from_slice(env))
+                .build()
+                .map(|client| client.inner.endpoint.clone())
+        };
+        assert_eq!(
+            endpoint(&[]).unwrap(),
+            Uri::from_static("http://169.254.169.254")
+        );
+        assert_eq!(
+            endpoint(&[("AWS_EC2_METADATA_SERVICE_ENDPOINT_MODE", "IPv6")]).unwrap(),
+            Uri::from_static("http://[fd00:ec2::254]")
+        );
+        assert_eq!(
+            endpoint(&[
+                ("AWS_EC2_METADATA_SERVICE_ENDPOINT_MODE", "IPv6"),
+                ("AWS_EC2_METADATA_SERVICE_ENDPOINT", "http://localhost:8080")
+            ])
+            .unwrap(),
+            Uri::from_static("http://localhost:8080")
+        );
+        match endpoint(&[("AWS_EC2_METADATA_SERVICE_ENDPOINT_MODE", "IPv7")]) {
+            Err(BuildError::InvalidEndpointMode(mode)) => assert_eq!(mode, "IPv7"),
+            other => panic!("expected invalid endpoint mode, got {:?}", other),
+        }
+        assert!(matches!(
+            endpoint(&[("AWS_EC2_METADATA_SERVICE_ENDPOINT", "not a uri")]),
+            Err(BuildError::InvalidEndpointUri(_))
+        ));
+
+        let explicit = Client::builder()
+            .env(Env::from_slice(&[(
+                "AWS_EC2_METADATA_SERVICE_ENDPOINT",
+                "http://localhost:8080",
+            )]))
+            .endpoint(Uri::from_static("http://localhost:1234"))
+            .build()
+            .unwrap();
+        assert_eq!(
+            explicit.inner.endpoint,
+            Uri::from_static("http://localhost:1234")
+        );
+    }
+}
@@ -0,0 +1,199 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+use crate::imds::{self, imds_disabled, BuildError, Client};
+use aws_types::os_shim::Env;
+use aws_types::region::{ProvideRegion, Region};
+
+const REGION_PATH: &str = "/latest/meta-data/placement/region";
+
+/// IMDSv2 Region Provider
+///
+/// Loads the region of the EC2 instance from the Instance Metadata Service. Setting
+/// `AWS_EC2_METADATA_DISABLED=true` disables this provider.
+///
+/// [`ProvideRegion`] is synchronous: `region()` blocks the current thread while IMDS is queried.
+/// From async code, prefer [`ImdsRegionProvider::load_region`].
+///
+/// IMDS is not part of the default region provider chain. To fall back to the region of the
+/// instance when no region is configured, add it to the chain:
+/// ```rust
+/// use aws_auth::imds::ImdsRegionProvider;
+/// use aws_types::region::RegionProviderChain;
+/// let provider = RegionProviderChain::default_provider().or_else(ImdsRegionProvider::new());
+/// ```
+#[derive(Debug)]
+pub struct ImdsRegionProvider {
+    client: Result<Client, BuildError>,
+    env: Env,
+}
+
+impl Default for ImdsRegionProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ImdsRegionProvider {
+    /// Create a region provider with the default IMDS client configuration
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// Load the region from IMDS
+    ///
+    /// If IMDS is disabled, unreachable, or returns an error, `None` is returned.
+    pub async fn load_region(&self) -> Option<Region> {
+        let client = self.client()?;
+        load_region(client).await
+    }
+
+    fn client(&self) -> Option<&Client> {
+        if imds_disabled(&self.env) {
+            tracing::debug!("not using IMDS to load region, IMDS is disabled");
+            return None;
+        }
+        match &self.client {
+            Ok(client) => Some(client),
+            Err(err) => {
+                tracing::warn!(err = %err, "invalid IMDS client configuration");
+                None
+            }
+        }
+    }
+}
+
+async fn load_region(client: &Client) -> Option<Region> {
+    match client.get(REGION_PATH).await {
+        Ok(region) => {
+            tracing::info!(region = %region, "loaded region from IMDS");
+            Some(Region::new(region))
+        }
+        Err(err) => {
+            tracing::warn!(err = %err, "unable to load region from IMDS");
+            None
+        }
+    }
+}
+
+impl ProvideRegion for ImdsRegionProvider {
+    fn region(&self) -> Option<Region> {
+        let client = self.client()?.clone();
+        crate::provider::block_on_thread(async move { load_region(&client).await })
+            .ok()
+            .flatten()
+    }
+}
+
+/// Builder for [`ImdsRegionProvider`]
+#[derive(Debug, Default)]
+pub struct Builder {
+    imds_client: Option<Client>,
+    env: Option<Env>,
+}
+
+impl Builder {
+    /// Override the IMDS client used to load the region
+    pub fn imds_client(mut self, client: Client) -> Self {
+        self.imds_client = Some(client);
+        self
+    }
+
+    /// Override the environment used to configure the provider
+    pub fn env(mut self, env: Env) -> Self {
+        self.env = Some(env);
+        self
+    }
+
+    pub fn build(self) -> ImdsRegionProvider {
+        let env = self.env.unwrap_or_default();
+        let client = match self.imds_client {
+            Some(client) => Ok(client),
+            None => imds::Client::builder().env(env.clone()).build(),
+        };
+        ImdsRegionProvider { client, env }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::imds::region::ImdsRegionProvider;
+    use crate::imds::test::{
+        imds_request, imds_response, make_client, token_request, token_response,
+    };
+    use aws_types::os_shim::Env;
+    use aws_types::region::{ProvideRegion, Region};
+    use smithy_client::test_connection::TestConnection;
+
+    fn region_events() -> Vec<(
+        http::Request<smithy_http::body::SdkBody>,
+        http::Response<&'static str>,
+    )> {
+        vec![
+            (
+                token_request("http://169.254.169.254", 21600),
+                token_response(21600, "token"),
+            ),
+            (
+                imds_request(
+                    "http://169.254.169.254/latest/meta-data/placement/region",
+                    "token",
+                ),
+                imds_response("eu-west-1"),
+            ),
+        ]
+    }
+
+    #[tokio::test]
+    async fn load_region() {
+        let connection = TestConnection::new(region_events());
+        let provider = ImdsRegionProvider::builder()
+            .imds_client(make_client(&connection))
+            .env(Env::from_slice(&[]))
+            .build();
+        assert_eq!(provider.load_region().await, Some(Region::new("eu-west-1")));
+        for request in connection.requests().iter() {
+            request.assert_matches(vec![]);
+        }
+    }
+
+    #[test]
+    fn blocking_region() {
+        let connection = TestConnection::new(region_events());
+        let provider = ImdsRegionProvider::builder()
+            .imds_client(make_client(&connection))
+            .env(Env::from_slice(&[]))
+            .build();
+        assert_eq!(provider.region(), Some(Region::new("eu-west-1")));
+    }
+
+    #[tokio::test]
+    async fn imds_disabled() {
+        let connection = TestConnection::new(region_events());
+        let provider = ImdsRegionProvider::builder()
+            .imds_client(make_client(&connection))
+            .env(Env::from_slice(&[("AWS_EC2_METADATA_DISABLED", "true")]))
+            .build();
+        assert_eq!(provider.load_region().await, None);
+        assert!(connection.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn imds_error() {
+        let connection = TestConnection::new(vec![(
+            token_request("http://169.254.169.254", 21600),
+            http::Response::builder().status(403).body("").unwrap(),
+        )]);
+        let provider = ImdsRegionProvider::builder()
+            .imds_client(make_client(&connection))
+            .env(Env::from_slice(&[]))
+            .build();
+        assert_eq!(provider.load_region().await, None);
+    }
+}
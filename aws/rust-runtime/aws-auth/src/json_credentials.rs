@@ -0,0 +1,295 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Parser for the JSON credentials document returned by IMDS & the ECS container metadata endpoint
+
+use smithy_json::deserialize::token::{
+    expect_start_object, expect_unescaped_string_or_null, skip_value,
+};
+use smithy_json::deserialize::{json_token_iter, Token};
+use smithy_types::instant::Format;
+use smithy_types::Instant;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::time::SystemTime;
+
+#[derive(Debug)]
+pub(crate) enum InvalidJsonCredentials {
+    /// The response did not contain valid JSON
+    JsonError(smithy_json::deserialize::Error),
+    /// The response was missing a required field
+    MissingField(&'static str),
+    /// A field was invalid
+    InvalidField {
+        field: &'static str,
+        err: Box<dyn Error + Send + Sync>,
+    },
+}
+
+impl From<smithy_json::deserialize::Error> for InvalidJsonCredentials {
+    fn from(err: smithy_json::deserialize::Error) -> Self {
+        InvalidJsonCredentials::JsonError(err)
+    }
+}
+
+impl Display for InvalidJsonCredentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidJsonCredentials::JsonError(json) => {
+                write!(f, "invalid JSON in response: {}", json)
+            }
+            InvalidJsonCredentials::MissingField(field) => write!(
+                f,
+                "Expected field `{}` in response but it was missing",
+                field
+            ),
+            InvalidJsonCredentials::InvalidField { field, err } => {
+                write!(f, "Invalid field in response: `{}`. {}", field, err)
+            }
+        }
+    }
+}
+
+impl Error for InvalidJsonCredentials {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            InvalidJsonCredentials::JsonError(err) => Some(err as _),
+            InvalidJsonCredentials::InvalidField { err, .. } => Some(err.as_ref() as _),
+            InvalidJsonCredentials::MissingField(_) => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq)]
+pub(crate) enum JsonCredentials {
+    RefreshableCredentials {
+        access_key_id: String,
+        secret_access_key: String,
+        session_token: String,
+        expiration: SystemTime,
+    },
+    Error {
+        code: String,
+        message: String,
+    },
+}
+
+impl fmt::Debug for JsonCredentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonCredentials::RefreshableCredentials {
+                access_key_id,
+                expiration,
+                ..
+            } => f
+                .debug_struct("RefreshableCredentials")
+                .field("access_key_id", access_key_id)
+                .field("expiration", expiration)
+                .finish(),
+            JsonCredentials::Error { code, message } => f
+                .debug_struct("Error")
+                .field("code", code)
+                .field("message", message)
+                .finish(),
+        }
+    }
+}
+
+/// Deserialize a credentials document
+///
+/// Example:
+/// ```json
+/// {
+///   "Code" : "Success",
+///   "LastUpdated" : "2021-09-17T20:57:08Z",
+///   "Type" : "AWS-HMAC",
+///   "AccessKeyId" : "ASIARTEST",
+///   "SecretAccessKey" : "xjtest",
+///   "Token" : "IQote///test",
+///   "Expiration" : "2021-09-18T03:31:56Z"
+/// }
+/// ```
+///
+/// `Code` is optional (the ECS endpoint does not return it). When it is present and is not
+/// `Success`, the document is returned as a [`JsonCredentials::Error`].
+pub(crate) fn parse_json_credentials(
+    credentials_response: &str,
+) -> Result<JsonCredentials, InvalidJsonCredentials> {
+    let mut tokens = json_token_iter(credentials_response.as_bytes());
+    let mut code = None;
+    let mut access_key_id = None;
+    let mut secret_access_key = None;
+    let mut session_token = None;
+    let mut expiration = None;
+    let mut message = None;
+    expect_start_object(tokens.next())?;
+    loop {
+        match tokens.next().transpose()? {
+            Some(Token::EndObject { .. }) => break,
+            Some(Token::ObjectKey { key, .. }) => {
+                let key = key
+                    .to_unescaped()
+                    .map_err(smithy_json::deserialize::Error::from)?;
+                match key.as_ref() {
+                    "Code" => code = expect_unescaped_string_or_null(tokens.next())?,
+                    "AccessKeyId" => {
+                        access_key_id = expect_unescaped_string_or_null(tokens.next())?
+                    }
+                    "SecretAccessKey" => {
+                        secret_access_key = expect_unescaped_string_or_null(tokens.next())?
+                    }
+                    "Token" => session_token = expect_unescaped_string_or_null(tokens.next())?,
+                    "Expiration" => expiration = expect_unescaped_string_or_null(tokens.next())?,
+                    "Message" => message = expect_unescaped_string_or_null(tokens.next())?,
+                    _ => skip_value(&mut tokens)?,
+                }
+            }
+            other => {
+                return Err(InvalidJsonCredentials::JsonError(
+                    smithy_json::deserialize::Error::custom(match other {
+                        Some(_) => "expected object key or end of object",
+                        None => "unexpected end of stream",
+                    }),
+                ))
+            }
+        }
+    }
+    if tokens.next().is_some() {
+        return Err(InvalidJsonCredentials::JsonError(
+            smithy_json::deserialize::Error::custom(
+                "found more JSON tokens after completing parsing",
+            ),
+        ));
+    }
+    match code {
+        Some(code) if code != "Success" => Ok(JsonCredentials::Error {
+            code,
+            message: message.unwrap_or_else(|| "no message".to_string()),
+        }),
+        _ => {
+            let access_key_id =
+                access_key_id.ok_or(InvalidJsonCredentials::MissingField("AccessKeyId"))?;
+            let secret_access_key =
+                secret_access_key.ok_or(InvalidJsonCredentials::MissingField("SecretAccessKey"))?;
+            let session_token =
+                session_token.ok_or(InvalidJsonCredentials::MissingField("Token"))?;
+            let expiration =
+                expiration.ok_or(InvalidJsonCredentials::MissingField("Expiration"))?;
+            let expiration = Instant::from_str(&expiration, Format::DateTime)
+                .map_err(|err| InvalidJsonCredentials::InvalidField {
+                    field: "Expiration",
+                    err: err.into(),
+                })?
+                .to_system_time()
+                .ok_or_else(|| InvalidJsonCredentials::InvalidField {
+                    field: "Expiration",
+                    err: "credential expiration time cannot be represented by a SystemTime".into(),
+                })?;
+            Ok(JsonCredentials::RefreshableCredentials {
+                access_key_id,
+                secret_access_key,
+                session_token,
+                expiration,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::json_credentials::{
+        parse_json_credentials, InvalidJsonCredentials, JsonCredentials,
+    };
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn json_credentials_success_response() {
+        let response = r#"
+        {
+          "Code" : "Success",
+          "LastUpdated" : "2021-09-17T20:57:08Z",
+          "Type" : "AWS-HMAC",
+          "AccessKeyId" : "ASIARTEST",
+          "SecretAccessKey" : "xjtest",
+          "Token" : "IQote///test",
+          "Expiration" : "2021-09-18T03:31:56Z"
+        }"#;
+        let parsed = parse_json_credentials(response).expect("valid JSON");
+        assert_eq!(
+            parsed,
+            JsonCredentials::RefreshableCredentials {
+                access_key_id: "ASIARTEST".to_string(),
+                secret_access_key: "xjtest".to_string(),
+                session_token: "IQote///test".to_string(),
+                expiration: UNIX_EPOCH + Duration::from_secs(1631935916),
+            }
+        )
+    }
+
+    #[test]
+    fn json_credentials_without_code() {
+        let response = r#"{
+            "AccessKeyId" : "MUA...",
+            "Expiration" : "2016-02-25T06:03:31Z",
+            "RoleArn" : "arn:aws:iam::123456789012:role/test",
+            "SecretAccessKey" : "wJa...",
+            "Token" : "AQoDY...."
+        }"#;
+        match parse_json_credentials(response).expect("valid JSON") {
+            JsonCredentials::RefreshableCredentials { access_key_id, .. } => {
+                assert_eq!(access_key_id, "MUA...")
+            }
+            other => panic!("expected credentials, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn json_credentials_error_response() {
+        let response = r#"{
+          "Code" : "AssumeRoleUnauthorizedAccess",
+          "Message" : "EC2 cannot assume the role integration-test.",
+          "LastUpdated" : "2021-09-17T20:46:56Z"
+        }"#;
+        assert_eq!(
+            parse_json_credentials(response).expect("valid JSON"),
+            JsonCredentials::Error {
+                code: "AssumeRoleUnauthorizedAccess".to_string(),
+                message: "EC2 cannot assume the role integration-test.".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn json_credentials_missing_field() {
+        let response = r#"{
+          "Code" : "Success",
+          "AccessKeyId" : "ASIARTEST",
+          "SecretAccessKey" : "xjtest",
+          "Expiration" : "2021-09-18T03:31:56Z"
+        }"#;
+        match parse_json_credentials(response) {
+            Err(InvalidJsonCredentials::MissingField("Token")) => {}
+            other => panic!("expected missing token, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn json_credentials_invalid_json() {
+        let error = parse_json_credentials("404: not found").expect_err("no json");
+        match error {
+            InvalidJsonCredentials::JsonError(_) => {}
+            err => panic!("incorrect error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn json_credentials_not_json_object() {
+        let error = parse_json_credentials("[1,2,3]").expect_err("no json");
+        match error {
+            InvalidJsonCredentials::JsonError(_) => {}
+            _ => panic!("incorrect error"),
+        }
+    }
+}
@@ -3,12 +3,16 @@
  * SPDX-License-Identifier: Apache-2.0.
  */
 
+pub mod imds;
+mod json_credentials;
 pub mod provider;
 
 use smithy_http::property_bag::PropertyBag;
 use std::error::Error;
 use std::fmt;
 use std::fmt::{Debug, Display, Formatter};
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
 use std::time::SystemTime;
 use zeroize::Zeroizing;
@@ -95,6 +99,11 @@ impl Credentials {
     pub fn session_token(&self) -> Option<&str> {
         self.0.session_token.as_deref()
     }
+
+    /// The name of the provider that loaded these credentials, eg. `EnvironmentVariable`
+    pub fn provider_name(&self) -> &'static str {
+        self.0.provider_name
+    }
 }
 
 #[derive(Debug)]
@@ -129,14 +138,76 @@ pub type CredentialsProvider = Arc<dyn ProvideCredentials>;
 /// This interface is intentionally NOT async. Credential providers should provide a separate
 /// async method to drive refresh (eg. in a background task).
 ///
-/// Pending future design iteration, an async credentials provider may be introduced.
+/// Providers that must perform I/O to load credentials should implement
+/// [`AsyncProvideCredentials`] instead.
 pub trait ProvideCredentials: Send + Sync {
     fn provide_credentials(&self) -> Result<Credentials, CredentialsError>;
 }
 
+impl<T> ProvideCredentials for Arc<T>
+where
+    T: ProvideCredentials + ?Sized,
+{
+    fn provide_credentials(&self) -> Result<Credentials, CredentialsError> {
+        self.as_ref().provide_credentials()
+    }
+}
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+pub type AsyncCredentialsProvider = Arc<dyn AsyncProvideCredentials>;
+
+/// An asynchronous Credentials Provider
+///
+/// Async providers are able to perform network I/O (eg. calling STS or the instance metadata
+/// service) while loading credentials. To use a synchronous provider where an async provider is
+/// required, wrap it in [`AsyncAdapter`](provider::AsyncAdapter), or in
+/// [`SpawnBlockingAdapter`](provider::SpawnBlockingAdapter) if it may block. To go the other way,
+/// use [`BlockingAdapter`](provider::BlockingAdapter).
+pub trait AsyncProvideCredentials: Send + Sync {
+    fn provide_credentials<'a>(&'a self) -> BoxFuture<'a, Result<Credentials, CredentialsError>>;
+}
+
+impl<T> AsyncProvideCredentials for Arc<T>
+where
+    T: AsyncProvideCredentials + ?Sized,
+{
+    fn provide_credentials<'a>(&'a self) -> BoxFuture<'a, Result<Credentials, CredentialsError>> {
+        self.as_ref().provide_credentials()
+    }
+}
+
+/// The default credentials provider chain
+///
+/// Credentials are loaded from, in order:
+/// 1. Environment variables: [`EnvironmentVariableCredentialsProvider`](provider::EnvironmentVariableCredentialsProvider)
+/// 2. The shared config & credentials files: [`ProfileFileCredentialsProvider`](provider::ProfileFileCredentialsProvider)
+/// 3. The ECS container credentials endpoint: [`EcsCredentialsProvider`](provider::EcsCredentialsProvider)
+/// 4. The EC2 Instance Metadata Service: [`ImdsCredentialsProvider`](provider::ImdsCredentialsProvider)
+///
+/// Loaded credentials are cached with [`LazyCachingCredentialsProvider`](provider::LazyCachingCredentialsProvider).
+/// Failures are not cached, but when IMDS cannot be reached (eg. when not running on EC2), it is
+/// skipped for the following 5 minutes rather than contacted again by every call.
+///
+/// Loading credentials may block while the ECS endpoint or IMDS are contacted. Request signing
+/// invokes synchronous providers on a blocking thread, so this does not stall the async runtime.
+///
+/// Profiles that assume a role are not supported by this chain since it cannot call STS. The
+/// `default_provider` of the `aws-auth-providers` crate supports them.
 pub fn default_provider() -> impl ProvideCredentials {
-    // TODO: this should be a chain based on the CRT
-    provider::EnvironmentVariableCredentialsProvider::new()
+    let chain = provider::CredentialsProviderChain::first_try(
+        "Environment",
+        provider::EnvironmentVariableCredentialsProvider::new(),
+    )
+    .or_else("Profile", provider::ProfileFileCredentialsProvider::new())
+    .or_else(
+        "EcsContainer",
+        provider::BlockingAdapter::new(provider::EcsCredentialsProvider::new()),
+    )
+    .or_else(
+        "Ec2InstanceMetadata",
+        provider::BlockingAdapter::new(provider::ImdsCredentialsProvider::new()),
+    );
+    provider::LazyCachingCredentialsProvider::new(chain)
 }
 
 impl ProvideCredentials for Credentials {
@@ -149,6 +220,14 @@ pub fn set_provider(config: &mut PropertyBag, provider: Arc<dyn ProvideCredentia
     config.insert(provider);
 }
 
+/// Set an async credentials provider in the property bag
+///
+/// When both are present, request signing uses the async provider rather than the provider
+/// set by [`set_provider`].
+pub fn set_async_provider(config: &mut PropertyBag, provider: Arc<dyn AsyncProvideCredentials>) {
+    config.insert(provider);
+}
+
 #[cfg(test)]
 mod test {
     use crate::Credentials;
@@ -3,7 +3,26 @@
  * SPDX-License-Identifier: Apache-2.0.
  */
 
+mod adapters;
+mod cache;
+mod chain;
+mod credential_process;
+mod ecs;
+mod imds;
+mod profile;
+
+pub use adapters::{AsyncAdapter, BlockingAdapter, SpawnBlockingAdapter};
+pub use cache::{BackgroundRefresh, LazyCachingCredentialsProvider};
+pub use chain::CredentialsProviderChain;
+pub use credential_process::CredentialProcessProvider;
+pub use ecs::EcsCredentialsProvider;
+pub use imds::ImdsCredentialsProvider;
+pub use profile::ProfileFileCredentialsProvider;
+
+pub(crate) use adapters::block_on_thread;
+
 use crate::{Credentials, CredentialsError, ProvideCredentials};
+use aws_types::os_shim::Env;
 use std::collections::HashMap;
 use std::env::VarError;
 
@@ -27,6 +46,13 @@ impl EnvironmentVariableCredentialsProvider {
             }),
         }
     }
+
+    /// Create a EnvironmentVariable provider that reads from an [`Env`]
+    pub fn for_env(env: Env) -> Self {
+        EnvironmentVariableCredentialsProvider {
+            env: Box::new(move |key: &str| env.get(key)),
+        }
+    }
 }
 
 impl Default for EnvironmentVariableCredentialsProvider {
@@ -69,6 +95,7 @@ fn to_cred_error(err: VarError) -> CredentialsError {
 mod test {
     use crate::provider::EnvironmentVariableCredentialsProvider;
     use crate::{CredentialsError, ProvideCredentials};
+    use aws_types::os_shim::Env;
     use std::collections::HashMap;
 
     #[test]
@@ -125,6 +152,17 @@ mod test {
         };
     }
 
+    #[test]
+    fn from_env() {
+        let provider = EnvironmentVariableCredentialsProvider::for_env(Env::from_slice(&[
+            ("AWS_ACCESS_KEY_ID", "access"),
+            ("AWS_SECRET_ACCESS_KEY", "secret"),
+        ]));
+        let creds = provider.provide_credentials().expect("valid credentials");
+        assert_eq!(creds.access_key_id(), "access");
+        assert_eq!(creds.secret_access_key(), "secret");
+    }
+
     #[test]
     fn real_environment() {
         let provider = EnvironmentVariableCredentialsProvider::new();
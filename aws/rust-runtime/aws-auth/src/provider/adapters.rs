@@ -0,0 +1,307 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+use crate::{
+    AsyncProvideCredentials, BoxFuture, Credentials, CredentialsError, ProvideCredentials,
+};
+use lazy_static::lazy_static;
+use std::error::Error;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::runtime::Handle;
+
+/// Use a synchronous [`ProvideCredentials`] as an [`AsyncProvideCredentials`]
+///
+/// The inner provider is invoked directly when the future is polled. It should not block for long.
+/// Wrap slow providers in a [`LazyCachingCredentialsProvider`](crate::provider::LazyCachingCredentialsProvider).
+#[derive(Clone, Debug)]
+pub struct AsyncAdapter<P>(P);
+
+impl<P> AsyncAdapter<P> {
+    pub fn new(provider: P) -> Self {
+        AsyncAdapter(provider)
+    }
+}
+
+impl<P> AsyncProvideCredentials for AsyncAdapter<P>
+where
+    P: ProvideCredentials,
+{
+    fn provide_credentials<'a>(&'a self) -> BoxFuture<'a, Result<Credentials, CredentialsError>> {
+        Box::pin(async move { self.0.provide_credentials() })
+    }
+}
+
+/// Use a synchronous [`ProvideCredentials`] that may block as an [`AsyncProvideCredentials`]
+///
+/// Unlike [`AsyncAdapter`], the inner provider is invoked on Tokio's blocking thread pool with
+/// [`spawn_blocking`](tokio::task::spawn_blocking), so a provider that performs I/O (eg. a
+/// [`CredentialsProviderChain`](crate::provider::CredentialsProviderChain) that contacts IMDS) does
+/// not stall the async runtime. This must be used from within a Tokio runtime.
+#[derive(Debug)]
+pub struct SpawnBlockingAdapter<P>(Arc<P>);
+
+impl<P> Clone for SpawnBlockingAdapter<P> {
+    fn clone(&self) -> Self {
+        SpawnBlockingAdapter(self.0.clone())
+    }
+}
+
+impl<P> SpawnBlockingAdapter<P> {
+    pub fn new(provider: P) -> Self {
+        SpawnBlockingAdapter(Arc::new(provider))
+    }
+}
+
+impl<P> AsyncProvideCredentials for SpawnBlockingAdapter<P>
+where
+    P: ProvideCredentials + 'static,
+{
+    fn provide_credentials<'a>(&'a self) -> BoxFuture<'a, Result<Credentials, CredentialsError>> {
+        let provider = self.0.clone();
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || provider.provide_credentials())
+                .await
+                .map_err(|err| CredentialsError::Unhandled(err.into()))?
+        })
+    }
+}
+
+/// Use an [`AsyncProvideCredentials`] as a synchronous [`ProvideCredentials`]
+///
+/// Each call blocks the calling thread while the async provider runs to completion on a dedicated
+/// thread & single-threaded Tokio runtime, shared by the whole process. This makes it safe to call
+/// from within an async context, and HTTP connections pooled by the provider stay usable between
+/// calls, but blocking is expensive: prefer using the async provider directly where possible, or
+/// cache its credentials with a [`LazyCachingCredentialsProvider`](crate::provider::LazyCachingCredentialsProvider).
+#[derive(Debug)]
+pub struct BlockingAdapter<P>(Arc<P>);
+
+impl<P> Clone for BlockingAdapter<P> {
+    fn clone(&self) -> Self {
+        BlockingAdapter(self.0.clone())
+    }
+}
+
+impl<P> BlockingAdapter<P> {
+    pub fn new(provider: P) -> Self {
+        BlockingAdapter(Arc::new(provider))
+    }
+}
+
+impl<P> ProvideCredentials for BlockingAdapter<P>
+where
+    P: AsyncProvideCredentials + 'static,
+{
+    fn provide_credentials(&self) -> Result<Credentials, CredentialsError> {
+        let provider = self.0.clone();
+        block_on_thread(async move { provider.provide_credentials().await })
+            .map_err(CredentialsError::Unhandled)?
+    }
+}
+
+const BLOCKING_RUNTIME_THREAD: &str = "aws-auth-blocking";
+
+lazy_static! {
+    /// Runtime driving the futures of [`block_on_thread`]
+    ///
+    /// A single runtime is kept for the whole process because hyper clients (eg. the IMDS
+    /// connection pool) spawn their connections on the runtime they are first used from: a
+    /// connection created by a runtime that was since dropped can't be used anymore.
+    static ref BLOCKING_RUNTIME: Result<Handle, String> = start_blocking_runtime();
+}
+
+fn start_blocking_runtime() -> Result<Handle, String> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|err| err.to_string())?;
+    let handle = runtime.handle().clone();
+    std::thread::Builder::new()
+        .name(BLOCKING_RUNTIME_THREAD.to_string())
+        .spawn(move || runtime.block_on(std::future::pending::<()>()))
+        .map_err(|err| err.to_string())?;
+    Ok(handle)
+}
+
+/// Drive `future` to completion on a dedicated thread & single-threaded Tokio runtime
+///
+/// This blocks the calling thread, but unlike `Runtime::block_on`, it is safe to call from within
+/// an async context. It fails rather than deadlocks if called from a future it is driving.
+pub(crate) fn block_on_thread<F>(future: F) -> Result<F::Output, Box<dyn Error + Send + Sync>>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    if std::thread::current().name() == Some(BLOCKING_RUNTIME_THREAD) {
+        return Err("cannot block on a future from the blocking runtime itself".into());
+    }
+    let runtime = BLOCKING_RUNTIME
+        .as_ref()
+        .map_err(|err| format!("failed to start the blocking runtime: {}", err))?;
+    let (sender, receiver) = std::sync::mpsc::channel();
+    runtime.spawn(async move {
+        // the receiver is only gone if the calling thread panicked
+        let _ = sender.send(future.await);
+    });
+    receiver
+        .recv()
+        .map_err(|_| "future panicked while blocking".into())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::provider::{AsyncAdapter, BlockingAdapter, SpawnBlockingAdapter};
+    use crate::{
+        AsyncProvideCredentials, BoxFuture, Credentials, CredentialsError, ProvideCredentials,
+    };
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc::{channel, Receiver};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    struct SlowProvider;
+    impl AsyncProvideCredentials for SlowProvider {
+        fn provide_credentials<'a>(
+            &'a self,
+        ) -> BoxFuture<'a, Result<Credentials, CredentialsError>> {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                Ok(Credentials::from_keys("async", "secret", None))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn sync_as_async() {
+        let provider = AsyncAdapter::new(Credentials::from_keys("sync", "secret", None));
+        let creds = AsyncProvideCredentials::provide_credentials(&provider)
+            .await
+            .unwrap();
+        assert_eq!(creds.access_key_id(), "sync");
+    }
+
+    /// Provider that blocks its thread until it is signaled
+    struct WaitForSignal(Mutex<Receiver<()>>);
+    impl ProvideCredentials for WaitForSignal {
+        fn provide_credentials(&self) -> Result<Credentials, CredentialsError> {
+            self.0.lock().unwrap().recv().unwrap();
+            Ok(Credentials::from_keys("blocking", "secret", None))
+        }
+    }
+
+    #[tokio::test]
+    async fn blocking_provider_does_not_block_the_runtime() {
+        let (signal, receiver) = channel();
+        let provider = SpawnBlockingAdapter::new(WaitForSignal(Mutex::new(receiver)));
+        // on this single threaded runtime, the signal can only be sent if the provider runs on
+        // another thread
+        let (creds, _) = tokio::join!(
+            AsyncProvideCredentials::provide_credentials(&provider),
+            async move {
+                tokio::task::yield_now().await;
+                signal.send(()).unwrap();
+            }
+        );
+        assert_eq!(creds.unwrap().access_key_id(), "blocking");
+    }
+
+    #[test]
+    fn async_as_blocking() {
+        let provider = BlockingAdapter::new(SlowProvider);
+        assert_eq!(
+            provider.provide_credentials().unwrap().access_key_id(),
+            "async"
+        );
+    }
+
+    /// Provider that loads credentials with a pooled hyper client, like the IMDS provider
+    struct PooledProvider {
+        client: hyper::Client<hyper::client::HttpConnector>,
+        uri: http::Uri,
+    }
+
+    impl AsyncProvideCredentials for PooledProvider {
+        fn provide_credentials<'a>(
+            &'a self,
+        ) -> BoxFuture<'a, Result<Credentials, CredentialsError>> {
+            Box::pin(async move {
+                let response = self
+                    .client
+                    .get(self.uri.clone())
+                    .await
+                    .map_err(|err| CredentialsError::Unhandled(err.into()))?;
+                let body = hyper::body::to_bytes(response.into_body())
+                    .await
+                    .map_err(|err| CredentialsError::Unhandled(err.into()))?;
+                Ok(Credentials::from_keys(
+                    String::from_utf8_lossy(&body),
+                    "secret",
+                    None,
+                ))
+            })
+        }
+    }
+
+    /// Serve `akid` to every request, keeping connections alive
+    fn serve_keep_alive() -> (http::Uri, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = connections.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let mut stream = stream.unwrap();
+                std::thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut line = String::new();
+                    loop {
+                        line.clear();
+                        match reader.read_line(&mut line) {
+                            Ok(0) | Err(_) => return,
+                            Ok(_) if line == "\r\n" => {
+                                stream
+                                    .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 4\r\n\r\nakid")
+                                    .unwrap();
+                            }
+                            Ok(_) => {}
+                        }
+                    }
+                });
+            }
+        });
+        (uri, connections)
+    }
+
+    #[test]
+    fn blocking_adapter_reuses_pooled_connections() {
+        let (uri, connections) = serve_keep_alive();
+        let provider = BlockingAdapter::new(PooledProvider {
+            client: hyper::Client::new(),
+            uri,
+        });
+        for _ in 0..3 {
+            assert_eq!(
+                provider.provide_credentials().unwrap().access_key_id(),
+                "akid"
+            );
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn blocking_adapter_within_runtime() {
+        let provider = BlockingAdapter::new(SlowProvider);
+        assert_eq!(
+            provider.provide_credentials().unwrap().access_key_id(),
+            "async"
+        );
+    }
+}
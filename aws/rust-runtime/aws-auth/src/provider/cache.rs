@@ -0,0 +1,472 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+use crate::{Credentials, CredentialsError, ProvideCredentials};
+use std::fmt::{self, Debug, Formatter};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_REFRESH_BUFFER: Duration = Duration::from_secs(5 * 60);
+const DEFAULT_EXPIRATION: Duration = Duration::from_secs(15 * 60);
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+const REFRESH_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Caching credentials provider
+///
+/// Wraps another [`ProvideCredentials`] and caches the credentials it returns. Cached credentials
+/// are reused until `refresh_buffer` before their [expiry](Credentials::expiry), at which point the
+/// next call to `provide_credentials` loads new credentials from the inner provider. Credentials
+/// that do not set an expiry are cached for `default_expiration`.
+///
+/// Concurrent callers that find the cache empty or expired wait for a single load of the inner
+/// provider rather than each loading credentials themselves.
+///
+/// Loading is lazy by default. To keep credentials fresh ahead of time instead, drive
+/// [`refresh`](LazyCachingCredentialsProvider::refresh) from a background task or use
+/// [`refresh_in_background`](LazyCachingCredentialsProvider::refresh_in_background).
+///
+/// ## Example
+/// ```rust
+/// use aws_auth::provider::{LazyCachingCredentialsProvider, ProfileFileCredentialsProvider};
+/// use std::time::Duration;
+/// let provider = LazyCachingCredentialsProvider::new(ProfileFileCredentialsProvider::new())
+///     .with_refresh_buffer(Duration::from_secs(60));
+/// ```
+pub struct LazyCachingCredentialsProvider<P> {
+    provider: Arc<P>,
+    cache: Arc<Cache>,
+    refresh_buffer: Duration,
+    default_expiration: Duration,
+    time_source: fn() -> SystemTime,
+}
+
+#[derive(Default)]
+struct Cache {
+    /// Cached credentials and the time at which they expire
+    value: RwLock<Option<(Credentials, SystemTime)>>,
+    /// Held while loading credentials from the inner provider
+    load_lock: Mutex<()>,
+}
+
+impl<P> Clone for LazyCachingCredentialsProvider<P> {
+    fn clone(&self) -> Self {
+        LazyCachingCredentialsProvider {
+            provider: self.provider.clone(),
+            cache: self.cache.clone(),
+            refresh_buffer: self.refresh_buffer,
+            default_expiration: self.default_expiration,
+            time_source: self.time_source,
+        }
+    }
+}
+
+impl<P> Debug for LazyCachingCredentialsProvider<P> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyCachingCredentialsProvider")
+            .field("cached", &self.cache.value.read().unwrap().as_ref())
+            .field("refresh_buffer", &self.refresh_buffer)
+            .field("default_expiration", &self.default_expiration)
+            .finish()
+    }
+}
+
+impl<P> LazyCachingCredentialsProvider<P>
+where
+    P: ProvideCredentials,
+{
+    pub fn new(provider: P) -> Self {
+        LazyCachingCredentialsProvider {
+            provider: Arc::new(provider),
+            cache: Default::default(),
+            refresh_buffer: DEFAULT_REFRESH_BUFFER,
+            default_expiration: DEFAULT_EXPIRATION,
+            time_source: SystemTime::now,
+        }
+    }
+
+    /// Set how long before expiry cached credentials are refreshed (default: 5 minutes)
+    pub fn with_refresh_buffer(mut self, refresh_buffer: Duration) -> Self {
+        self.refresh_buffer = refresh_buffer;
+        self
+    }
+
+    /// Set how long credentials without an expiry are cached (default: 15 minutes)
+    pub fn with_default_expiration(mut self, default_expiration: Duration) -> Self {
+        self.default_expiration = default_expiration;
+        self
+    }
+
+    /// Override the source of the current time
+    ///
+    /// By default, [`SystemTime::now`](SystemTime::now) is used. In tests, it can be helpful
+    /// to control the clock.
+    pub fn with_time_source(mut self, time_source: fn() -> SystemTime) -> Self {
+        self.time_source = time_source;
+        self
+    }
+
+    /// Return cached credentials if they are not within `refresh_buffer` of expiring
+    fn cached(&self) -> Option<Credentials> {
+        let now = (self.time_source)();
+        match self.cache.value.read().unwrap().as_ref() {
+            Some((creds, expires_at)) if now < self.refresh_at(*expires_at) => Some(creds.clone()),
+            _ => None,
+        }
+    }
+
+    /// Load credentials from the inner provider and cache them
+    ///
+    /// The caller must hold `load_lock`.
+    fn load(&self) -> Result<Credentials, CredentialsError> {
+        let creds = self.provider.provide_credentials()?;
+        let expires_at = creds
+            .expiry()
+            .unwrap_or_else(|| (self.time_source)() + self.default_expiration);
+        *self.cache.value.write().unwrap() = Some((creds.clone(), expires_at));
+        Ok(creds)
+    }
+
+    /// When credentials expiring at `expires_at` should be refreshed
+    ///
+    /// A refresh buffer longer than the time since the Unix epoch refreshes immediately rather than
+    /// underflowing.
+    fn refresh_at(&self, expires_at: SystemTime) -> SystemTime {
+        expires_at
+            .checked_sub(self.refresh_buffer)
+            .unwrap_or(UNIX_EPOCH)
+    }
+
+    /// How long until the cached credentials should be refreshed
+    fn time_until_refresh(&self) -> Duration {
+        let now = (self.time_source)();
+        let refresh_at = match self.cache.value.read().unwrap().as_ref() {
+            Some((_, expires_at)) => self.refresh_at(*expires_at),
+            None => now,
+        };
+        refresh_at
+            .duration_since(now)
+            .unwrap_or_default()
+            .max(MIN_REFRESH_INTERVAL)
+    }
+}
+
+impl<P> LazyCachingCredentialsProvider<P>
+where
+    P: ProvideCredentials + 'static,
+{
+    /// Load fresh credentials from the inner provider, regardless of the state of the cache
+    ///
+    /// The inner provider is invoked on a blocking thread so that it does not stall the
+    /// async runtime.
+    pub async fn refresh(&self) -> Result<Credentials, CredentialsError> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let _guard = this.cache.load_lock.lock().unwrap();
+            this.load()
+        })
+        .await
+        .map_err(|err| CredentialsError::Unhandled(err.into()))?
+    }
+
+    /// Spawn a task on the current Tokio runtime that keeps the cache fresh
+    ///
+    /// The task loads credentials immediately and then again `refresh_buffer` before they expire.
+    /// If loading fails, it is retried after a short delay. The task is aborted when the returned
+    /// [`BackgroundRefresh`] is dropped.
+    pub fn refresh_in_background(&self) -> BackgroundRefresh {
+        let this = self.clone();
+        let handle = tokio::spawn(async move {
+            loop {
+                let delay = match this.refresh().await {
+                    Ok(_) => this.time_until_refresh(),
+                    Err(err) => {
+                        tracing::warn!(error = %err, "failed to refresh cached credentials");
+                        REFRESH_RETRY_INTERVAL
+                    }
+                };
+                tokio::time::sleep(delay).await;
+            }
+        });
+        BackgroundRefresh { handle }
+    }
+}
+
+/// Handle to the task spawned by [`refresh_in_background`](LazyCachingCredentialsProvider::refresh_in_background)
+///
+/// Dropping the handle aborts the task. Unlike a [`JoinHandle`](tokio::task::JoinHandle), which
+/// detaches its task when dropped, this ensures the task does not outlive its owner and keep the
+/// inner provider alive.
+#[derive(Debug)]
+#[must_use = "the background refresh is aborted when this handle is dropped"]
+pub struct BackgroundRefresh {
+    handle: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for BackgroundRefresh {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+impl<P> ProvideCredentials for LazyCachingCredentialsProvider<P>
+where
+    P: ProvideCredentials,
+{
+    fn provide_credentials(&self) -> Result<Credentials, CredentialsError> {
+        if let Some(creds) = self.cached() {
+            return Ok(creds);
+        }
+        let _guard = self.cache.load_lock.lock().unwrap();
+        // Another caller may have loaded credentials while we waited for the lock
+        if let Some(creds) = self.cached() {
+            return Ok(creds);
+        }
+        self.load()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::provider::cache::MIN_REFRESH_INTERVAL;
+    use crate::provider::LazyCachingCredentialsProvider;
+    use crate::{Credentials, CredentialsError, ProvideCredentials};
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    /// Provider that returns credentials expiring `ttl` after the (test) current time
+    struct TestProvider {
+        calls: Arc<AtomicUsize>,
+        ttl: Option<Duration>,
+        now: fn() -> SystemTime,
+        /// Block loading until this many callers have started
+        wait_for_callers: Option<(Arc<AtomicUsize>, usize)>,
+    }
+
+    impl TestProvider {
+        fn new(ttl: Option<Duration>, now: fn() -> SystemTime) -> (Arc<AtomicUsize>, Self) {
+            let calls = Arc::new(AtomicUsize::new(0));
+            (
+                calls.clone(),
+                TestProvider {
+                    calls,
+                    ttl,
+                    now,
+                    wait_for_callers: None,
+                },
+            )
+        }
+    }
+
+    impl ProvideCredentials for TestProvider {
+        fn provide_credentials(&self) -> Result<Credentials, CredentialsError> {
+            if let Some((started, callers)) = &self.wait_for_callers {
+                while started.load(Ordering::SeqCst) < *callers {
+                    std::thread::yield_now();
+                }
+            }
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(Credentials::new(
+                format!("akid-{}", call),
+                "secret",
+                None,
+                self.ttl.map(|ttl| (self.now)() + ttl),
+                "test",
+            ))
+        }
+    }
+
+    // Each test that manipulates the clock uses its own static so tests can run in parallel
+    macro_rules! test_clock {
+        ($name:ident) => {
+            static $name: AtomicU64 = AtomicU64::new(1_000_000);
+        };
+    }
+
+    test_clock!(EXPIRY_CLOCK);
+    fn expiry_now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(EXPIRY_CLOCK.load(Ordering::SeqCst))
+    }
+
+    #[test]
+    fn reuse_until_refresh_buffer() {
+        let (calls, inner) = TestProvider::new(Some(Duration::from_secs(3600)), expiry_now);
+        let provider = LazyCachingCredentialsProvider::new(inner)
+            .with_refresh_buffer(Duration::from_secs(600))
+            .with_time_source(expiry_now);
+
+        let creds = provider.provide_credentials().unwrap();
+        assert_eq!(creds.access_key_id(), "akid-0");
+        // 49 minutes later, still outside the 10 minute refresh buffer
+        EXPIRY_CLOCK.fetch_add(49 * 60, Ordering::SeqCst);
+        let creds = provider.provide_credentials().unwrap();
+        assert_eq!(creds.access_key_id(), "akid-0");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // 51 minutes: within the refresh buffer
+        EXPIRY_CLOCK.fetch_add(2 * 60, Ordering::SeqCst);
+        let creds = provider.provide_credentials().unwrap();
+        assert_eq!(creds.access_key_id(), "akid-1");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    test_clock!(DEFAULT_EXPIRY_CLOCK);
+    fn default_expiry_now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(DEFAULT_EXPIRY_CLOCK.load(Ordering::SeqCst))
+    }
+
+    #[test]
+    fn credentials_without_expiry_use_default_expiration() {
+        let (calls, inner) = TestProvider::new(None, default_expiry_now);
+        let provider = LazyCachingCredentialsProvider::new(inner)
+            .with_refresh_buffer(Duration::from_secs(0))
+            .with_default_expiration(Duration::from_secs(60))
+            .with_time_source(default_expiry_now);
+        provider.provide_credentials().unwrap();
+        DEFAULT_EXPIRY_CLOCK.fetch_add(59, Ordering::SeqCst);
+        provider.provide_credentials().unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        DEFAULT_EXPIRY_CLOCK.fetch_add(1, Ordering::SeqCst);
+        provider.provide_credentials().unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn refresh_buffer_before_unix_epoch() {
+        struct Expired;
+        impl ProvideCredentials for Expired {
+            fn provide_credentials(&self) -> Result<Credentials, CredentialsError> {
+                Ok(Credentials::new(
+                    "akid",
+                    "secret",
+                    None,
+                    Some(UNIX_EPOCH),
+                    "test",
+                ))
+            }
+        }
+        let provider = LazyCachingCredentialsProvider::new(Expired)
+            .with_refresh_buffer(Duration::from_secs(u64::MAX / 4));
+        assert!(provider.provide_credentials().is_ok());
+        assert!(provider.cached().is_none());
+        assert_eq!(provider.time_until_refresh(), MIN_REFRESH_INTERVAL);
+    }
+
+    #[test]
+    fn errors_are_not_cached() {
+        struct Failing;
+        impl ProvideCredentials for Failing {
+            fn provide_credentials(&self) -> Result<Credentials, CredentialsError> {
+                Err(CredentialsError::CredentialsNotLoaded)
+            }
+        }
+        let provider = LazyCachingCredentialsProvider::new(Failing);
+        assert!(provider.provide_credentials().is_err());
+        assert!(provider.provide_credentials().is_err());
+    }
+
+    #[test]
+    fn concurrent_loads_are_collapsed() {
+        let (calls, mut inner) =
+            TestProvider::new(Some(Duration::from_secs(3600)), SystemTime::now);
+        let started = Arc::new(AtomicUsize::new(0));
+        inner.wait_for_callers = Some((started.clone(), 10));
+        let provider = LazyCachingCredentialsProvider::new(inner);
+        let threads: Vec<_> = (0..10)
+            .map(|_| {
+                let provider = provider.clone();
+                let started = started.clone();
+                std::thread::spawn(move || {
+                    started.fetch_add(1, Ordering::SeqCst);
+                    provider.provide_credentials().unwrap()
+                })
+            })
+            .collect();
+        for thread in threads {
+            assert_eq!(thread.join().unwrap().access_key_id(), "akid-0");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn explicit_refresh() {
+        let (calls, inner) = TestProvider::new(Some(Duration::from_secs(3600)), SystemTime::now);
+        let provider = LazyCachingCredentialsProvider::new(inner);
+        assert_eq!(
+            provider.provide_credentials().unwrap().access_key_id(),
+            "akid-0"
+        );
+        let refreshed = provider.refresh().await.unwrap();
+        assert_eq!(refreshed.access_key_id(), "akid-1");
+        assert_eq!(
+            provider.provide_credentials().unwrap().access_key_id(),
+            "akid-1"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    test_clock!(BACKGROUND_CLOCK);
+    fn background_now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(BACKGROUND_CLOCK.load(Ordering::SeqCst))
+    }
+
+    /// Advance both the test clock and Tokio's paused clock
+    async fn advance_background_clock(secs: u64) {
+        BACKGROUND_CLOCK.fetch_add(secs, Ordering::SeqCst);
+        tokio::time::advance(Duration::from_secs(secs)).await;
+    }
+
+    /// Yield to the background task until it has cached credentials with `access_key_id`
+    async fn wait_for_cached(provider: &LazyCachingCredentialsProvider<TestProvider>, akid: &str) {
+        while provider
+            .cache
+            .value
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|(creds, _)| creds.access_key_id() != akid)
+            .unwrap_or(true)
+        {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn background_refresh() {
+        tokio::time::pause();
+        // credentials that need refreshing 200 seconds after they are loaded
+        let (calls, inner) = TestProvider::new(Some(Duration::from_secs(1200)), background_now);
+        let provider = LazyCachingCredentialsProvider::new(inner)
+            .with_refresh_buffer(Duration::from_secs(1000))
+            .with_time_source(background_now);
+        let task = provider.refresh_in_background();
+        wait_for_cached(&provider, "akid-0").await;
+
+        advance_background_clock(100).await;
+        assert_eq!(
+            provider.provide_credentials().unwrap().access_key_id(),
+            "akid-0"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        advance_background_clock(150).await;
+        wait_for_cached(&provider, "akid-1").await;
+        assert_eq!(
+            provider.provide_credentials().unwrap().access_key_id(),
+            "akid-1"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // dropping the handle aborts the task, which releases its reference to the provider
+        drop(task);
+        for _ in 0..100 {
+            if Arc::strong_count(&provider.provider) == 1 {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(Arc::strong_count(&provider.provider), 1);
+    }
+}
@@ -0,0 +1,157 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+use crate::{Credentials, CredentialsError, ProvideCredentials};
+use std::borrow::Cow;
+use std::fmt::{self, Debug, Formatter};
+
+/// Credentials provider that checks a series of inner providers
+///
+/// Each provider will be checked in turn. The first provider that returns a successful credential
+/// will be used. If a provider returns [`CredentialsError::CredentialsNotLoaded`], the next provider
+/// is checked. Any other error stops the chain and is returned to the caller.
+///
+/// ## Example
+/// ```rust
+/// use aws_auth::provider::{CredentialsProviderChain, EnvironmentVariableCredentialsProvider, ProfileFileCredentialsProvider};
+/// let provider = CredentialsProviderChain::first_try("Environment", EnvironmentVariableCredentialsProvider::new())
+///     .or_else("Profile", ProfileFileCredentialsProvider::new());
+/// ```
+pub struct CredentialsProviderChain {
+    providers: Vec<(Cow<'static, str>, Box<dyn ProvideCredentials>)>,
+}
+
+impl Debug for CredentialsProviderChain {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CredentialsProviderChain")
+            .field(
+                "providers",
+                &self
+                    .providers
+                    .iter()
+                    .map(|provider| &provider.0)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl CredentialsProviderChain {
+    /// Create a `CredentialsProviderChain` that begins by evaluating this provider
+    pub fn first_try(
+        name: impl Into<Cow<'static, str>>,
+        provider: impl ProvideCredentials + 'static,
+    ) -> Self {
+        CredentialsProviderChain {
+            providers: vec![(name.into(), Box::new(provider))],
+        }
+    }
+
+    /// Add a fallback to the default provider chain
+    pub fn or_else(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
+        provider: impl ProvideCredentials + 'static,
+    ) -> Self {
+        self.providers.push((name.into(), Box::new(provider)));
+        self
+    }
+}
+
+impl ProvideCredentials for CredentialsProviderChain {
+    fn provide_credentials(&self) -> Result<Credentials, CredentialsError> {
+        for (name, provider) in &self.providers {
+            let span = tracing::info_span!("load_credentials", provider = %name);
+            match span.in_scope(|| provider.provide_credentials()) {
+                Ok(credentials) => {
+                    tracing::info!(provider = %name, "loaded credentials");
+                    return Ok(credentials);
+                }
+                Err(CredentialsError::CredentialsNotLoaded) => {
+                    tracing::info!(provider = %name, "provider in chain did not provide credentials");
+                }
+                Err(e) => {
+                    tracing::warn!(provider = %name, error = %e, "provider failed to provide credentials");
+                    return Err(e);
+                }
+            }
+        }
+        Err(CredentialsError::CredentialsNotLoaded)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::provider::CredentialsProviderChain;
+    use crate::{Credentials, CredentialsError, ProvideCredentials};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Test provider that records how often it is called
+    struct Counting<F> {
+        calls: Arc<AtomicUsize>,
+        respond: F,
+    }
+
+    impl<F> ProvideCredentials for Counting<F>
+    where
+        F: Fn() -> Result<Credentials, CredentialsError> + Send + Sync,
+    {
+        fn provide_credentials(&self) -> Result<Credentials, CredentialsError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            (self.respond)()
+        }
+    }
+
+    fn counting(
+        respond: impl Fn() -> Result<Credentials, CredentialsError> + Send + Sync + 'static,
+    ) -> (Arc<AtomicUsize>, impl ProvideCredentials + 'static) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        (calls.clone(), Counting { calls, respond })
+    }
+
+    #[test]
+    fn falls_through_not_loaded() {
+        let (first_calls, first) = counting(|| Err(CredentialsError::CredentialsNotLoaded));
+        let (second_calls, second) = counting(|| Ok(Credentials::from_keys("a", "b", None)));
+        let (third_calls, third) = counting(|| Ok(Credentials::from_keys("c", "d", None)));
+        let chain = CredentialsProviderChain::first_try("first", first)
+            .or_else("second", second)
+            .or_else("third", third);
+        let creds = chain
+            .provide_credentials()
+            .expect("second provider succeeds");
+        assert_eq!(creds.access_key_id(), "a");
+        assert_eq!(first_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(second_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(third_calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn stops_on_unhandled_error() {
+        let (_, first) = counting(|| Err(CredentialsError::Unhandled("boom".into())));
+        let (second_calls, second) = counting(|| Ok(Credentials::from_keys("a", "b", None)));
+        let chain = CredentialsProviderChain::first_try("first", first).or_else("second", second);
+        let err = chain
+            .provide_credentials()
+            .expect_err("first provider fails");
+        assert!(matches!(err, CredentialsError::Unhandled(_)));
+        assert_eq!(second_calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn nothing_loaded() {
+        let (_, first) = counting(|| Err(CredentialsError::CredentialsNotLoaded));
+        let chain = CredentialsProviderChain::first_try("first", first);
+        assert!(matches!(
+            chain.provide_credentials(),
+            Err(CredentialsError::CredentialsNotLoaded)
+        ));
+        assert_eq!(
+            format!("{:?}", chain),
+            "CredentialsProviderChain { providers: [\"first\"] }"
+        );
+    }
+}
@@ -0,0 +1,334 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+use crate::json_credentials::InvalidJsonCredentials;
+use crate::{Credentials, CredentialsError, ProvideCredentials};
+use smithy_json::deserialize::token::{
+    expect_number_or_null, expect_start_object, expect_unescaped_string_or_null, skip_value,
+};
+use smithy_json::deserialize::{json_token_iter, Token};
+use smithy_types::instant::Format;
+use smithy_types::{Instant, Number};
+use std::process::{Command, Output};
+
+const PROVIDER_NAME: &str = "CredentialProcess";
+
+/// Load credentials from an external process
+///
+/// The command is run with the system shell (`sh -c` or `cmd.exe /C` on Windows) every time
+/// credentials are requested. It must print a JSON document to stdout:
+/// ```json
+/// {
+///   "Version": 1,
+///   "AccessKeyId": "ASIARTEST",
+///   "SecretAccessKey": "secret",
+///   "SessionToken": "token",
+///   "Expiration": "2021-09-21T04:16:53Z"
+/// }
+/// ```
+/// `SessionToken` and `Expiration` are optional. If the command fails or prints an invalid
+/// document, [`CredentialsError::Unhandled`] is returned, including anything the command wrote to
+/// stderr.
+///
+/// The command is usually configured with `credential_process` in a profile, see
+/// [`ProfileFileCredentialsProvider`](crate::provider::ProfileFileCredentialsProvider). Running a
+/// process is slow, so wrap this provider in a
+/// [`LazyCachingCredentialsProvider`](crate::provider::LazyCachingCredentialsProvider) to reuse
+/// the credentials until they expire.
+#[derive(Debug, Clone)]
+pub struct CredentialProcessProvider {
+    command: String,
+}
+
+impl CredentialProcessProvider {
+    pub fn new(command: impl Into<String>) -> Self {
+        CredentialProcessProvider {
+            command: command.into(),
+        }
+    }
+}
+
+impl ProvideCredentials for CredentialProcessProvider {
+    fn provide_credentials(&self) -> Result<Credentials, CredentialsError> {
+        tracing::debug!(command = %self.command, "loading credentials from external process");
+        let output = run(&self.command).map_err(|err| {
+            CredentialsError::Unhandled(
+                format!(
+                    "failed to run credential process `{}`: {}",
+                    self.command, err
+                )
+                .into(),
+            )
+        })?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            return Err(CredentialsError::Unhandled(
+                format!(
+                    "credential process `{}` failed ({}). stderr: {}",
+                    self.command,
+                    output.status,
+                    stderr.trim()
+                )
+                .into(),
+            ));
+        }
+        let stdout = std::str::from_utf8(&output.stdout).map_err(|err| {
+            CredentialsError::Unhandled(
+                format!(
+                    "credential process `{}` returned invalid UTF-8: {}. stderr: {}",
+                    self.command,
+                    err,
+                    stderr.trim()
+                )
+                .into(),
+            )
+        })?;
+        parse_credential_process_output(stdout).map_err(|err| {
+            CredentialsError::Unhandled(
+                format!(
+                    "credential process `{}` returned an invalid response: {}. stderr: {}",
+                    self.command,
+                    err,
+                    stderr.trim()
+                )
+                .into(),
+            )
+        })
+    }
+}
+
+#[cfg(windows)]
+fn run(command: &str) -> std::io::Result<Output> {
+    Command::new("cmd.exe").arg("/C").arg(command).output()
+}
+
+#[cfg(not(windows))]
+fn run(command: &str) -> std::io::Result<Output> {
+    Command::new("sh").arg("-c").arg(command).output()
+}
+
+/// Parse the versioned JSON document printed by a credential process
+fn parse_credential_process_output(output: &str) -> Result<Credentials, InvalidJsonCredentials> {
+    let mut tokens = json_token_iter(output.as_bytes());
+    let mut version = None;
+    let mut access_key_id = None;
+    let mut secret_access_key = None;
+    let mut session_token = None;
+    let mut expiration = None;
+    expect_start_object(tokens.next())?;
+    loop {
+        match tokens.next().transpose()? {
+            Some(Token::EndObject { .. }) => break,
+            Some(Token::ObjectKey { key, .. }) => {
+                let key = key
+                    .to_unescaped()
+                    .map_err(smithy_json::deserialize::Error::from)?;
+                match key.as_ref() {
+                    "Version" => version = expect_number_or_null(tokens.next())?,
+                    "AccessKeyId" => {
+                        access_key_id = expect_unescaped_string_or_null(tokens.next())?
+                    }
+                    "SecretAccessKey" => {
+                        secret_access_key = expect_unescaped_string_or_null(tokens.next())?
+                    }
+                    "SessionToken" => {
+                        session_token = expect_unescaped_string_or_null(tokens.next())?
+                    }
+                    "Expiration" => expiration = expect_unescaped_string_or_null(tokens.next())?,
+                    _ => skip_value(&mut tokens)?,
+                }
+            }
+            other => {
+                return Err(InvalidJsonCredentials::JsonError(
+                    smithy_json::deserialize::Error::custom(match other {
+                        Some(_) => "expected object key or end of object",
+                        None => "unexpected end of stream",
+                    }),
+                ))
+            }
+        }
+    }
+    if tokens.next().is_some() {
+        return Err(InvalidJsonCredentials::JsonError(
+            smithy_json::deserialize::Error::custom(
+                "found more JSON tokens after completing parsing",
+            ),
+        ));
+    }
+    match version {
+        Some(Number::PosInt(1)) => {}
+        Some(other) => {
+            return Err(InvalidJsonCredentials::InvalidField {
+                field: "Version",
+                err: format!("unsupported version {:?}, expected 1", other).into(),
+            })
+        }
+        None => return Err(InvalidJsonCredentials::MissingField("Version")),
+    }
+    let access_key_id = access_key_id.ok_or(InvalidJsonCredentials::MissingField("AccessKeyId"))?;
+    let secret_access_key =
+        secret_access_key.ok_or(InvalidJsonCredentials::MissingField("SecretAccessKey"))?;
+    let expiration = expiration
+        .map(|expiration| {
+            Instant::from_str(&expiration, Format::DateTime)
+                .map_err(|err| InvalidJsonCredentials::InvalidField {
+                    field: "Expiration",
+                    err: err.into(),
+                })?
+                .to_system_time()
+                .ok_or_else(|| InvalidJsonCredentials::InvalidField {
+                    field: "Expiration",
+                    err: "credential expiration time cannot be represented by a SystemTime".into(),
+                })
+        })
+        .transpose()?;
+    Ok(Credentials::new(
+        access_key_id,
+        secret_access_key,
+        session_token,
+        expiration,
+        PROVIDER_NAME,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::json_credentials::InvalidJsonCredentials;
+    use crate::provider::credential_process::parse_credential_process_output;
+    use crate::provider::{CredentialProcessProvider, LazyCachingCredentialsProvider};
+    use crate::{CredentialsError, ProvideCredentials};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn parse_full_output() {
+        let creds = parse_credential_process_output(
+            r#"{
+              "Version": 1,
+              "AccessKeyId": "ASIARTEST",
+              "SecretAccessKey": "secret",
+              "SessionToken": "token",
+              "Expiration": "2021-09-21T04:16:53Z"
+            }"#,
+        )
+        .expect("valid output");
+        assert_eq!(creds.access_key_id(), "ASIARTEST");
+        assert_eq!(creds.secret_access_key(), "secret");
+        assert_eq!(creds.session_token(), Some("token"));
+        assert_eq!(
+            creds.expiry(),
+            Some(UNIX_EPOCH + Duration::from_secs(1632197813))
+        );
+        assert_eq!(creds.provider_name(), "CredentialProcess");
+    }
+
+    #[test]
+    fn parse_long_term_credentials() {
+        let creds = parse_credential_process_output(
+            r#"{"Version": 1, "AccessKeyId": "AKIDTEST", "SecretAccessKey": "secret"}"#,
+        )
+        .expect("valid output");
+        assert_eq!(creds.session_token(), None);
+        assert_eq!(creds.expiry(), None);
+    }
+
+    #[test]
+    fn parse_invalid_output() {
+        assert!(matches!(
+            parse_credential_process_output(r#"{"AccessKeyId": "AKID", "SecretAccessKey": "s"}"#),
+            Err(InvalidJsonCredentials::MissingField("Version"))
+        ));
+        assert!(matches!(
+            parse_credential_process_output(
+                r#"{"Version": 2, "AccessKeyId": "AKID", "SecretAccessKey": "s"}"#
+            ),
+            Err(InvalidJsonCredentials::InvalidField {
+                field: "Version",
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse_credential_process_output(r#"{"Version": 1, "AccessKeyId": "AKID"}"#),
+            Err(InvalidJsonCredentials::MissingField("SecretAccessKey"))
+        ));
+        assert!(matches!(
+            parse_credential_process_output(
+                r#"{"Version": 1, "AccessKeyId": "AKID", "SecretAccessKey": "s", "Expiration": "tomorrow"}"#
+            ),
+            Err(InvalidJsonCredentials::InvalidField {
+                field: "Expiration",
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse_credential_process_output("not json"),
+            Err(InvalidJsonCredentials::JsonError(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_process() {
+        let provider = CredentialProcessProvider::new(
+            r#"echo '{"Version": 1, "AccessKeyId": "ASIARTEST", "SecretAccessKey": "secret", "Expiration": "2021-09-21T04:16:53Z"}'"#,
+        );
+        let creds = provider.provide_credentials().expect("valid credentials");
+        assert_eq!(creds.access_key_id(), "ASIARTEST");
+        assert_eq!(
+            creds.expiry(),
+            Some(UNIX_EPOCH + Duration::from_secs(1632197813))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn process_failure_includes_stderr() {
+        let provider = CredentialProcessProvider::new("echo 'token expired' >&2; exit 1");
+        match provider.provide_credentials() {
+            Err(CredentialsError::Unhandled(err)) => {
+                assert!(format!("{}", err).contains("token expired"), "{}", err)
+            }
+            other => panic!("expected unhandled error, got {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn invalid_output_includes_stderr() {
+        let provider = CredentialProcessProvider::new("echo 'not json'; echo 'oops' >&2");
+        match provider.provide_credentials() {
+            Err(CredentialsError::Unhandled(err)) => {
+                let message = format!("{}", err);
+                assert!(message.contains("invalid response"), "{}", message);
+                assert!(message.contains("oops"), "{}", message);
+            }
+            other => panic!("expected unhandled error, got {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cached_process_credentials() {
+        fn now() -> std::time::SystemTime {
+            UNIX_EPOCH + Duration::from_secs(1632190000)
+        }
+        // The command counts its invocations so we can tell whether credentials were cached
+        let dir =
+            std::env::temp_dir().join(format!("credential-process-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let counter = dir.join("count");
+        let provider = LazyCachingCredentialsProvider::new(CredentialProcessProvider::new(format!(
+            r#"echo x >> {}; echo '{{"Version": 1, "AccessKeyId": "ASIARTEST", "SecretAccessKey": "secret", "Expiration": "2021-09-21T04:16:53Z"}}'"#,
+            counter.display()
+        )))
+        .with_time_source(now);
+        for _ in 0..3 {
+            let creds = provider.provide_credentials().expect("valid credentials");
+            assert_eq!(creds.access_key_id(), "ASIARTEST");
+        }
+        let invocations = std::fs::read_to_string(&counter).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(invocations.lines().count(), 1);
+    }
+}
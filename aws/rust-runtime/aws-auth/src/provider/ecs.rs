@@ -0,0 +1,608 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+use crate::json_credentials::{parse_json_credentials, InvalidJsonCredentials, JsonCredentials};
+use crate::{AsyncProvideCredentials, BoxFuture, Credentials, CredentialsError};
+use aws_types::os_shim::Env;
+use bytes::Bytes;
+use http::uri::Scheme;
+use http::{HeaderValue, Response, Uri};
+use smithy_client::erase::DynConnector;
+use smithy_client::{SdkError, SdkSuccess};
+use smithy_http::body::SdkBody;
+use smithy_http::operation::{self, Metadata, Operation};
+use smithy_http::response::ParseStrictResponse;
+use smithy_http::retry::ClassifyResponse;
+use smithy_types::retry::{ErrorKind, ProvideErrorKind, RetryKind};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tower::layer::util::Identity;
+use tower::timeout::Timeout;
+
+const ENV_RELATIVE_URI: &str = "AWS_CONTAINER_CREDENTIALS_RELATIVE_URI";
+const ENV_FULL_URI: &str = "AWS_CONTAINER_CREDENTIALS_FULL_URI";
+const ENV_AUTHORIZATION: &str = "AWS_CONTAINER_AUTHORIZATION_TOKEN";
+
+const BASE_HOST: &str = "http://169.254.170.2";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+const PROVIDER_NAME: &str = "EcsContainer";
+
+/// ECS / Container Credentials Provider
+///
+/// Loads credentials from the container credentials endpoint (eg. the role of an ECS task). The
+/// endpoint is configured with environment variables:
+/// - `AWS_CONTAINER_CREDENTIALS_RELATIVE_URI`: a path relative to `http://169.254.170.2`. This is
+///   set automatically by ECS.
+/// - `AWS_CONTAINER_CREDENTIALS_FULL_URI`: a full URI. This is only used if the relative URI is not
+///   set. To avoid sending the credentials request off-host, the URI must refer to a loopback host
+///   (`localhost`, `127.0.0.0/8` or `::1`), whether it uses `http` or `https`.
+/// - `AWS_CONTAINER_AUTHORIZATION_TOKEN`: when set along with the full URI, its value is sent as the
+///   `Authorization` header.
+///
+/// If neither URI is set, [`CredentialsError::CredentialsNotLoaded`] is returned.
+///
+/// Like [`ImdsCredentialsProvider`](crate::provider::ImdsCredentialsProvider), this provider calls
+/// the endpoint every time credentials are requested. Wrap it in a
+/// [`LazyCachingCredentialsProvider`](crate::provider::LazyCachingCredentialsProvider) to cache the
+/// credentials until they expire.
+#[derive(Debug)]
+pub struct EcsCredentialsProvider {
+    env: Env,
+    connector: Option<DynConnector>,
+    timeout: Duration,
+    client: OnceCell<smithy_client::Client<DynConnector, Identity>>,
+}
+
+impl Default for EcsCredentialsProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EcsCredentialsProvider {
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    async fn credentials(&self) -> Result<Credentials, CredentialsError> {
+        let request = match self.request()? {
+            Some(request) => request,
+            None => {
+                tracing::debug!("container credentials endpoint is not configured");
+                return Err(CredentialsError::CredentialsNotLoaded);
+            }
+        };
+        let operation = Operation::new(operation::Request::new(request), EcsResponseHandler)
+            .with_metadata(Metadata::new("get-credentials", "ecs"))
+            .with_retry_policy(EcsErrorPolicy);
+        match self.client().await.call(operation).await {
+            Ok(credentials) => Ok(credentials),
+            Err(SdkError::ServiceError { err, .. }) => Err(CredentialsError::Unhandled(err.into())),
+            Err(other) => Err(CredentialsError::Unhandled(other.into())),
+        }
+    }
+
+    async fn client(&self) -> &smithy_client::Client<DynConnector, Identity> {
+        self.client
+            .get_or_init(|| async {
+                let timeout = self.timeout;
+                let builder = smithy_client::Builder::new().middleware(Identity::new());
+                // The HTTPS connector is only constructed once the endpoint is actually used
+                match self.connector.clone() {
+                    Some(connector) => builder
+                        .connector(connector)
+                        .map_connector(|conn| Timeout::new(conn, timeout))
+                        .build()
+                        .into_dyn_connector(),
+                    None => builder
+                        .https()
+                        .map_connector(|conn| Timeout::new(conn, timeout))
+                        .build()
+                        .into_dyn_connector(),
+                }
+            })
+            .await
+    }
+
+    /// Construct the credentials request from the environment
+    ///
+    /// Returns `None` if the container credentials endpoint is not configured.
+    fn request(&self) -> Result<Option<http::Request<SdkBody>>, CredentialsError> {
+        let (uri, authorization) = if let Ok(relative_uri) = self.env.get(ENV_RELATIVE_URI) {
+            let uri = Uri::from_str(&format!("{}{}", BASE_HOST, relative_uri))
+                .map_err(|err| InvalidEndpoint::InvalidUri(err.into()))?;
+            (uri, None)
+        } else if let Ok(full_uri) = self.env.get(ENV_FULL_URI) {
+            let uri = validate_full_uri(&full_uri)?;
+            (uri, self.env.get(ENV_AUTHORIZATION).ok())
+        } else {
+            return Ok(None);
+        };
+        let mut request = http::Request::builder().method("GET").uri(uri);
+        if let Some(authorization) = authorization {
+            let mut authorization = HeaderValue::from_str(&authorization)
+                .map_err(|err| InvalidEndpoint::InvalidAuthorizationToken(err.into()))?;
+            authorization.set_sensitive(true);
+            request = request.header(http::header::AUTHORIZATION, authorization);
+        }
+        // unlike `SdkBody::empty()`, an in-memory body can be retried
+        let request = request
+            .body(SdkBody::from(""))
+            .map_err(|err| CredentialsError::Unhandled(err.into()))?;
+        Ok(Some(request))
+    }
+}
+
+impl AsyncProvideCredentials for EcsCredentialsProvider {
+    fn provide_credentials<'a>(&'a self) -> BoxFuture<'a, Result<Credentials, CredentialsError>> {
+        Box::pin(self.credentials())
+    }
+}
+
+/// Validate `AWS_CONTAINER_CREDENTIALS_FULL_URI`
+///
+/// The URI must use the `http` or `https` scheme and, for either scheme, refer to a loopback host.
+fn validate_full_uri(uri: &str) -> Result<Uri, InvalidEndpoint> {
+    let uri = Uri::from_str(uri).map_err(|err| InvalidEndpoint::InvalidUri(err.into()))?;
+    match uri.scheme() {
+        Some(scheme) if scheme == &Scheme::HTTP || scheme == &Scheme::HTTPS => {}
+        _ => {
+            return Err(InvalidEndpoint::InvalidUri(
+                "the full URI must use the `http` or `https` scheme".into(),
+            ))
+        }
+    }
+    match uri.host() {
+        Some(host) if is_loopback(host) => Ok(uri),
+        Some(host) => Err(InvalidEndpoint::DisallowedHost(host.to_string())),
+        None => Err(InvalidEndpoint::InvalidUri(
+            "the full URI has no host".into(),
+        )),
+    }
+}
+
+fn is_loopback(host: &str) -> bool {
+    if host.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+    // IPv6 hosts are bracketed in URIs, eg. `[::1]`
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    IpAddr::from_str(host)
+        .map(|ip| ip.is_loopback())
+        .unwrap_or(false)
+}
+
+/// The container credentials endpoint configured in the environment was invalid
+#[derive(Debug)]
+enum InvalidEndpoint {
+    InvalidUri(Box<dyn Error + Send + Sync>),
+    DisallowedHost(String),
+    InvalidAuthorizationToken(Box<dyn Error + Send + Sync>),
+}
+
+impl Display for InvalidEndpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidEndpoint::InvalidUri(err) => {
+                write!(f, "invalid container credentials URI: {}", err)
+            }
+            InvalidEndpoint::DisallowedHost(host) => write!(
+                f,
+                "`{}` is not an allowed host for {}. Only loopback hosts are allowed.",
+                host, ENV_FULL_URI
+            ),
+            InvalidEndpoint::InvalidAuthorizationToken(err) => {
+                write!(f, "invalid {}: {}", ENV_AUTHORIZATION, err)
+            }
+        }
+    }
+}
+
+impl Error for InvalidEndpoint {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            InvalidEndpoint::InvalidUri(err) | InvalidEndpoint::InvalidAuthorizationToken(err) => {
+                Some(err.as_ref() as _)
+            }
+            InvalidEndpoint::DisallowedHost(_) => None,
+        }
+    }
+}
+
+impl From<InvalidEndpoint> for CredentialsError {
+    fn from(err: InvalidEndpoint) -> Self {
+        CredentialsError::Unhandled(err.into())
+    }
+}
+
+/// The container credentials endpoint returned an error
+#[derive(Debug)]
+enum EcsResponseError {
+    BadStatus(http::StatusCode),
+    InvalidUtf8,
+    InvalidCredentials(InvalidJsonCredentials),
+    ErrorDocument { code: String, message: String },
+}
+
+impl Display for EcsResponseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EcsResponseError::BadStatus(status) => write!(
+                f,
+                "container credentials endpoint returned status code {}",
+                status.as_u16()
+            ),
+            EcsResponseError::InvalidUtf8 => write!(
+                f,
+                "container credentials endpoint did not return valid UTF-8"
+            ),
+            EcsResponseError::InvalidCredentials(err) => write!(f, "{}", err),
+            EcsResponseError::ErrorDocument { code, message } => write!(
+                f,
+                "Error retrieving credentials from the container credentials endpoint: {} {}",
+                code, message
+            ),
+        }
+    }
+}
+
+impl Error for EcsResponseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EcsResponseError::InvalidCredentials(err) => Some(err as _),
+            _ => None,
+        }
+    }
+}
+
+impl ProvideErrorKind for EcsResponseError {
+    fn retryable_error_kind(&self) -> Option<ErrorKind> {
+        None
+    }
+
+    fn code(&self) -> Option<&str> {
+        None
+    }
+}
+
+#[derive(Clone)]
+struct EcsResponseHandler;
+
+impl ParseStrictResponse for EcsResponseHandler {
+    type Output = Result<Credentials, EcsResponseError>;
+
+    fn parse(&self, response: &Response<Bytes>) -> Self::Output {
+        if !response.status().is_success() {
+            return Err(EcsResponseError::BadStatus(response.status()));
+        }
+        let body = std::str::from_utf8(response.body().as_ref())
+            .map_err(|_| EcsResponseError::InvalidUtf8)?;
+        match parse_json_credentials(body).map_err(EcsResponseError::InvalidCredentials)? {
+            JsonCredentials::RefreshableCredentials {
+                access_key_id,
+                secret_access_key,
+                session_token,
+                expiration,
+            } => Ok(Credentials::new(
+                access_key_id,
+                secret_access_key,
+                Some(session_token),
+                Some(expiration),
+                PROVIDER_NAME,
+            )),
+            JsonCredentials::Error { code, message } => {
+                Err(EcsResponseError::ErrorDocument { code, message })
+            }
+        }
+    }
+}
+
+/// Retry requests that fail to dispatch or that return a 5xx status code
+#[derive(Clone)]
+struct EcsErrorPolicy;
+
+impl<T, E> ClassifyResponse<SdkSuccess<T>, SdkError<E>> for EcsErrorPolicy {
+    fn classify(&self, response: Result<&SdkSuccess<T>, &SdkError<E>>) -> RetryKind {
+        match response {
+            Err(SdkError::ResponseError { raw, .. }) | Err(SdkError::ServiceError { raw, .. })
+                if raw.status().is_server_error() =>
+            {
+                RetryKind::Error(ErrorKind::ServerError)
+            }
+            Err(SdkError::DispatchFailure(_)) => RetryKind::Error(ErrorKind::TransientError),
+            _ => RetryKind::NotRetryable,
+        }
+    }
+}
+
+/// Builder for [`EcsCredentialsProvider`]
+#[derive(Debug, Default)]
+pub struct Builder {
+    env: Option<Env>,
+    connector: Option<DynConnector>,
+    timeout: Option<Duration>,
+}
+
+impl Builder {
+    /// Override the environment used to locate the container credentials endpoint
+    pub fn env(mut self, env: Env) -> Self {
+        self.env = Some(env);
+        self
+    }
+
+    /// Override the connector used to call the container credentials endpoint
+    pub fn connector(mut self, connector: DynConnector) -> Self {
+        self.connector = Some(connector);
+        self
+    }
+
+    /// Override the timeout for each request attempt (default: 2 seconds)
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn build(self) -> EcsCredentialsProvider {
+        EcsCredentialsProvider {
+            env: self.env.unwrap_or_default(),
+            connector: self.connector,
+            timeout: self.timeout.unwrap_or(DEFAULT_TIMEOUT),
+            client: OnceCell::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::provider::ecs::{is_loopback, validate_full_uri, InvalidEndpoint};
+    use crate::provider::EcsCredentialsProvider;
+    use crate::{AsyncProvideCredentials, CredentialsError};
+    use aws_types::os_shim::Env;
+    use http::Uri;
+    use smithy_client::erase::DynConnector;
+    use smithy_client::test_connection::TestConnection;
+    use smithy_http::body::SdkBody;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    const CREDENTIALS: &str = r#"{
+        "AccessKeyId" : "AKIDEXAMPLE",
+        "SecretAccessKey" : "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY",
+        "Token" : "IQoJb3JpZ2luX2VjEAMaCXVzLWVhc3QtMSJHMEUCIEXAMPLE",
+        "RoleArn" : "arn:aws:iam::123456789012:role/ecs-task-role",
+        "Expiration" : "2021-09-21T04:16:53Z"
+    }"#;
+
+    fn provider(
+        env: &[(&str, &str)],
+        connection: &TestConnection<&'static str>,
+    ) -> EcsCredentialsProvider {
+        EcsCredentialsProvider::builder()
+            .env(Env::from_slice(env))
+            .connector(DynConnector::new(connection.clone()))
+            .build()
+    }
+
+    fn request(uri: &'static str, auth: Option<&str>) -> http::Request<SdkBody> {
+        let mut builder = http::Request::builder().uri(Uri::from_static(uri));
+        if let Some(auth) = auth {
+            builder = builder.header("authorization", auth);
+        }
+        builder.body(SdkBody::empty()).unwrap()
+    }
+
+    fn ok_response(body: &'static str) -> http::Response<&'static str> {
+        http::Response::builder().status(200).body(body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn relative_uri() {
+        let connection = TestConnection::new(vec![(
+            request("http://169.254.170.2/credentials?id=abc", None),
+            ok_response(CREDENTIALS),
+        )]);
+        let provider = provider(
+            &[(
+                "AWS_CONTAINER_CREDENTIALS_RELATIVE_URI",
+                "/credentials?id=abc",
+            )],
+            &connection,
+        );
+        let creds = provider
+            .provide_credentials()
+            .await
+            .expect("valid credentials");
+        assert_eq!(creds.access_key_id(), "AKIDEXAMPLE");
+        assert_eq!(
+            creds.session_token(),
+            Some("IQoJb3JpZ2luX2VjEAMaCXVzLWVhc3QtMSJHMEUCIEXAMPLE")
+        );
+        assert_eq!(
+            creds.expiry(),
+            Some(UNIX_EPOCH + Duration::from_secs(1632197813))
+        );
+        assert_eq!(creds.provider_name(), "EcsContainer");
+        connection.requests()[0].assert_matches(vec![]);
+    }
+
+    #[tokio::test]
+    async fn full_uri_with_authorization() {
+        let connection = TestConnection::new(vec![(
+            request("http://127.0.0.1:8080/credentials", Some("Basic password")),
+            ok_response(CREDENTIALS),
+        )]);
+        let provider = provider(
+            &[
+                (
+                    "AWS_CONTAINER_CREDENTIALS_FULL_URI",
+                    "http://127.0.0.1:8080/credentials",
+                ),
+                ("AWS_CONTAINER_AUTHORIZATION_TOKEN", "Basic password"),
+            ],
+            &connection,
+        );
+        provider
+            .provide_credentials()
+            .await
+            .expect("valid credentials");
+        connection.requests()[0].assert_matches(vec![]);
+    }
+
+    #[tokio::test]
+    async fn relative_uri_takes_precedence() {
+        let connection = TestConnection::new(vec![(
+            request("http://169.254.170.2/credentials", None),
+            ok_response(CREDENTIALS),
+        )]);
+        let provider = provider(
+            &[
+                ("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI", "/credentials"),
+                (
+                    "AWS_CONTAINER_CREDENTIALS_FULL_URI",
+                    "http://localhost/other",
+                ),
+                ("AWS_CONTAINER_AUTHORIZATION_TOKEN", "Basic password"),
+            ],
+            &connection,
+        );
+        provider
+            .provide_credentials()
+            .await
+            .expect("valid credentials");
+        let requests = connection.requests();
+        requests[0].assert_matches(vec![]);
+        assert!(requests[0].actual.headers().get("authorization").is_none());
+    }
+
+    #[tokio::test]
+    async fn not_configured() {
+        let connection = TestConnection::new(vec![]);
+        match provider(&[], &connection).provide_credentials().await {
+            Err(CredentialsError::CredentialsNotLoaded) => {}
+            other => panic!("expected credentials not loaded, got {:?}", other),
+        }
+        assert!(connection.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn non_loopback_full_uri_rejected() {
+        let connection = TestConnection::new(vec![]);
+        let provider = provider(
+            &[(
+                "AWS_CONTAINER_CREDENTIALS_FULL_URI",
+                "http://169.254.169.254/credentials",
+            )],
+            &connection,
+        );
+        match provider.provide_credentials().await {
+            Err(CredentialsError::Unhandled(err)) => {
+                assert!(
+                    format!("{}", err).contains("not an allowed host"),
+                    "{}",
+                    err
+                )
+            }
+            other => panic!("expected unhandled error, got {:?}", other),
+        }
+        assert!(connection.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn non_loopback_https_full_uri_rejected() {
+        let connection = TestConnection::new(vec![]);
+        let provider = provider(
+            &[(
+                "AWS_CONTAINER_CREDENTIALS_FULL_URI",
+                "https://credentials.example.com/credentials",
+            )],
+            &connection,
+        );
+        match provider.provide_credentials().await {
+            Err(CredentialsError::Unhandled(err)) => {
+                assert!(
+                    format!("{}", err).contains("not an allowed host"),
+                    "{}",
+                    err
+                )
+            }
+            other => panic!("expected unhandled error, got {:?}", other),
+        }
+        assert!(connection.requests().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_server_errors() {
+        let connection = TestConnection::new(vec![
+            (
+                request("http://169.254.170.2/credentials", None),
+                http::Response::builder().status(503).body("").unwrap(),
+            ),
+            (
+                request("http://169.254.170.2/credentials", None),
+                ok_response(CREDENTIALS),
+            ),
+        ]);
+        let provider = provider(
+            &[("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI", "/credentials")],
+            &connection,
+        );
+        provider
+            .provide_credentials()
+            .await
+            .expect("valid credentials");
+        assert_eq!(connection.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn client_error_is_not_retried() {
+        let connection = TestConnection::new(vec![(
+            request("http://169.254.170.2/credentials", None),
+            http::Response::builder().status(400).body("").unwrap(),
+        )]);
+        let provider = provider(
+            &[("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI", "/credentials")],
+            &connection,
+        );
+        match provider.provide_credentials().await {
+            Err(CredentialsError::Unhandled(err)) => {
+                assert!(format!("{}", err).contains("400"), "{}", err)
+            }
+            other => panic!("expected unhandled error, got {:?}", other),
+        }
+        assert_eq!(connection.requests().len(), 1);
+    }
+
+    #[test]
+    fn full_uri_validation() {
+        assert!(validate_full_uri("http://localhost:8080/creds").is_ok());
+        assert!(validate_full_uri("http://127.0.0.2/creds").is_ok());
+        assert!(validate_full_uri("http://[::1]/creds").is_ok());
+        assert!(validate_full_uri("https://localhost/creds").is_ok());
+        assert!(matches!(
+            validate_full_uri("https://credentials.example.com/creds"),
+            Err(InvalidEndpoint::DisallowedHost(_))
+        ));
+        assert!(matches!(
+            validate_full_uri("http://credentials.example.com/creds"),
+            Err(InvalidEndpoint::DisallowedHost(_))
+        ));
+        assert!(matches!(
+            validate_full_uri("ftp://localhost/creds"),
+            Err(InvalidEndpoint::InvalidUri(_))
+        ));
+        assert!(matches!(
+            validate_full_uri("not a uri"),
+            Err(InvalidEndpoint::InvalidUri(_))
+        ));
+        assert!(!is_loopback("169.254.170.2"));
+    }
+}
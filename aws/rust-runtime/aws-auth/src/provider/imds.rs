@@ -0,0 +1,359 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+use crate::imds::{self, imds_disabled, BuildError, ImdsError};
+use crate::json_credentials::{parse_json_credentials, JsonCredentials};
+use crate::{AsyncProvideCredentials, BoxFuture, Credentials, CredentialsError};
+use aws_types::os_shim::Env;
+use http::StatusCode;
+use smithy_client::SdkError;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+const CREDENTIALS_PATH: &str = "/latest/meta-data/iam/security-credentials/";
+const PROVIDER_NAME: &str = "IMDSv2";
+const DEFAULT_UNREACHABLE_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// IMDSv2 Credentials Provider
+///
+/// Loads the credentials of the IAM role attached to the EC2 instance (the instance profile) from
+/// the Instance Metadata Service. Setting `AWS_EC2_METADATA_DISABLED=true` disables this provider.
+///
+/// When IMDS cannot be reached (eg. because the code is not running on EC2) or no role is attached
+/// to the instance, [`CredentialsError::CredentialsNotLoaded`] is returned. Once IMDS could not be
+/// reached, the provider returns `CredentialsNotLoaded` without contacting IMDS again until the
+/// [unreachable backoff](Builder::unreachable_backoff) has elapsed. Otherwise, every call would
+/// wait for all the attempts of the IMDS client to time out.
+///
+/// This provider queries IMDS on every call. To use it with synchronous code & to cache the
+/// credentials until they expire, wrap it:
+/// ```rust
+/// use aws_auth::provider::{BlockingAdapter, ImdsCredentialsProvider, LazyCachingCredentialsProvider};
+/// let provider = LazyCachingCredentialsProvider::new(BlockingAdapter::new(ImdsCredentialsProvider::new()));
+/// ```
+#[derive(Debug)]
+pub struct ImdsCredentialsProvider {
+    client: Result<imds::Client, BuildError>,
+    env: Env,
+    unreachable_backoff: Duration,
+    /// Set when IMDS could not be reached: IMDS is not contacted again until this time
+    unreachable_until: Mutex<Option<Instant>>,
+}
+
+impl Default for ImdsCredentialsProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ImdsCredentialsProvider {
+    /// Create a credentials provider with the default IMDS client configuration
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    async fn credentials(&self) -> Result<Credentials, CredentialsError> {
+        if imds_disabled(&self.env) {
+            tracing::debug!("not using IMDS to load credentials, IMDS is disabled");
+            return Err(CredentialsError::CredentialsNotLoaded);
+        }
+        if let Some(until) = *self.unreachable_until.lock().unwrap() {
+            if Instant::now() < until {
+                tracing::debug!(
+                    "not using IMDS to load credentials, IMDS was recently unreachable"
+                );
+                return Err(CredentialsError::CredentialsNotLoaded);
+            }
+        }
+        let client = self.client.as_ref().map_err(|err| {
+            CredentialsError::Unhandled(
+                format!("invalid IMDS client configuration: {}", err).into(),
+            )
+        })?;
+        let profile = client
+            .get(CREDENTIALS_PATH)
+            .await
+            .map_err(|err| self.imds_error(err))?;
+        let profile = match profile.lines().next().map(str::trim) {
+            Some(profile) if !profile.is_empty() => profile,
+            _ => {
+                tracing::info!("no IAM role is attached to this instance");
+                return Err(CredentialsError::CredentialsNotLoaded);
+            }
+        };
+        let credentials = client
+            .get(&format!("{}{}", CREDENTIALS_PATH, profile))
+            .await
+            .map_err(|err| self.imds_error(err))?;
+        match parse_json_credentials(&credentials) {
+            Ok(JsonCredentials::RefreshableCredentials {
+                access_key_id,
+                secret_access_key,
+                session_token,
+                expiration,
+            }) => Ok(Credentials::new(
+                access_key_id,
+                secret_access_key,
+                Some(session_token),
+                Some(expiration),
+                PROVIDER_NAME,
+            )),
+            Ok(JsonCredentials::Error { code, message }) => Err(CredentialsError::Unhandled(
+                format!(
+                    "Error retrieving credentials from IMDS: {} {}",
+                    code, message
+                )
+                .into(),
+            )),
+            Err(invalid) => Err(CredentialsError::Unhandled(invalid.into())),
+        }
+    }
+
+    fn imds_error(&self, err: ImdsError) -> CredentialsError {
+        match err {
+            // IMDS could not be reached: we are most likely not running on EC2
+            ImdsError::IoError(_) | ImdsError::FailedToLoadToken(SdkError::DispatchFailure(_)) => {
+                tracing::info!(err = %err, backoff = ?self.unreachable_backoff, "IMDS could not be reached");
+                *self.unreachable_until.lock().unwrap() =
+                    Some(Instant::now() + self.unreachable_backoff);
+                CredentialsError::CredentialsNotLoaded
+            }
+            ImdsError::ErrorResponse { raw } if raw.status() == StatusCode::NOT_FOUND => {
+                tracing::info!("no IAM role is attached to this instance");
+                CredentialsError::CredentialsNotLoaded
+            }
+            other => CredentialsError::Unhandled(other.into()),
+        }
+    }
+}
+
+impl AsyncProvideCredentials for ImdsCredentialsProvider {
+    fn provide_credentials<'a>(&'a self) -> BoxFuture<'a, Result<Credentials, CredentialsError>> {
+        Box::pin(self.credentials())
+    }
+}
+
+/// Builder for [`ImdsCredentialsProvider`]
+#[derive(Debug, Default)]
+pub struct Builder {
+    imds_client: Option<imds::Client>,
+    env: Option<Env>,
+    unreachable_backoff: Option<Duration>,
+}
+
+impl Builder {
+    /// Override the IMDS client used to load credentials
+    pub fn imds_client(mut self, client: imds::Client) -> Self {
+        self.imds_client = Some(client);
+        self
+    }
+
+    /// Override the environment used to configure the provider
+    pub fn env(mut self, env: Env) -> Self {
+        self.env = Some(env);
+        self
+    }
+
+    /// Override how long IMDS is skipped after it could not be reached (default: 5 minutes)
+    pub fn unreachable_backoff(mut self, backoff: Duration) -> Self {
+        self.unreachable_backoff = Some(backoff);
+        self
+    }
+
+    pub fn build(self) -> ImdsCredentialsProvider {
+        let env = self.env.unwrap_or_default();
+        let client = match self.imds_client {
+            Some(client) => Ok(client),
+            None => imds::Client::builder().env(env.clone()).build(),
+        };
+        ImdsCredentialsProvider {
+            client,
+            env,
+            unreachable_backoff: self
+                .unreachable_backoff
+                .unwrap_or(DEFAULT_UNREACHABLE_BACKOFF),
+            unreachable_until: Mutex::new(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::imds;
+    use crate::imds::test::{
+        imds_request, imds_response, make_client, token_request, token_response,
+    };
+    use crate::provider::ImdsCredentialsProvider;
+    use crate::{AsyncProvideCredentials, CredentialsError};
+    use aws_types::os_shim::Env;
+    use smithy_client::erase::DynConnector;
+    use smithy_client::test_connection::TestConnection;
+    use smithy_http::body::SdkBody;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+    use tower::BoxError;
+
+    const TOKEN_A: &str = "token_a";
+
+    fn provider(connection: &TestConnection<&'static str>) -> ImdsCredentialsProvider {
+        ImdsCredentialsProvider::builder()
+            .imds_client(make_client(connection))
+            .env(Env::from_slice(&[]))
+            .build()
+    }
+
+    #[tokio::test]
+    async fn load_credentials() {
+        let connection = TestConnection::new(vec![
+            (
+                token_request("http://169.254.169.254", 21600),
+                token_response(21600, TOKEN_A),
+            ),
+            (
+                imds_request(
+                    "http://169.254.169.254/latest/meta-data/iam/security-credentials/",
+                    TOKEN_A,
+                ),
+                imds_response(r#"profile-name"#),
+            ),
+            (
+                imds_request(
+                    "http://169.254.169.254/latest/meta-data/iam/security-credentials/profile-name",
+                    TOKEN_A,
+                ),
+                imds_response("{\n  \"Code\" : \"Success\",\n  \"LastUpdated\" : \"2021-09-20T21:42:26Z\",\n  \"Type\" : \"AWS-HMAC\",\n  \"AccessKeyId\" : \"ASIARTEST\",\n  \"SecretAccessKey\" : \"testsecret\",\n  \"Token\" : \"testtoken\",\n  \"Expiration\" : \"2021-09-21T04:16:53Z\"\n}"),
+            ),
+        ]);
+        let creds = provider(&connection)
+            .provide_credentials()
+            .await
+            .expect("valid credentials");
+        assert_eq!(creds.access_key_id(), "ASIARTEST");
+        assert_eq!(creds.secret_access_key(), "testsecret");
+        assert_eq!(creds.session_token(), Some("testtoken"));
+        assert_eq!(
+            creds.expiry(),
+            Some(UNIX_EPOCH + Duration::from_secs(1632197813))
+        );
+        assert_eq!(creds.provider_name(), "IMDSv2");
+        for request in connection.requests().iter() {
+            request.assert_matches(vec![]);
+        }
+    }
+
+    #[tokio::test]
+    async fn no_instance_profile() {
+        let connection = TestConnection::new(vec![
+            (
+                token_request("http://169.254.169.254", 21600),
+                token_response(21600, TOKEN_A),
+            ),
+            (
+                imds_request(
+                    "http://169.254.169.254/latest/meta-data/iam/security-credentials/",
+                    TOKEN_A,
+                ),
+                http::Response::builder().status(404).body("").unwrap(),
+            ),
+        ]);
+        match provider(&connection).provide_credentials().await {
+            Err(CredentialsError::CredentialsNotLoaded) => {}
+            other => panic!("expected credentials not loaded, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn error_document() {
+        let connection = TestConnection::new(vec![
+            (
+                token_request("http://169.254.169.254", 21600),
+                token_response(21600, TOKEN_A),
+            ),
+            (
+                imds_request(
+                    "http://169.254.169.254/latest/meta-data/iam/security-credentials/",
+                    TOKEN_A,
+                ),
+                imds_response("profile-name"),
+            ),
+            (
+                imds_request(
+                    "http://169.254.169.254/latest/meta-data/iam/security-credentials/profile-name",
+                    TOKEN_A,
+                ),
+                imds_response(
+                    r#"{"Code": "AssumeRoleUnauthorizedAccess", "Message": "EC2 cannot assume the role"}"#,
+                ),
+            ),
+        ]);
+        match provider(&connection).provide_credentials().await {
+            Err(CredentialsError::Unhandled(err)) => assert!(
+                format!("{}", err).contains("AssumeRoleUnauthorizedAccess"),
+                "{}",
+                err
+            ),
+            other => panic!("expected unhandled error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn imds_disabled() {
+        let connection = TestConnection::new(vec![]);
+        let provider = ImdsCredentialsProvider::builder()
+            .imds_client(make_client(&connection))
+            .env(Env::from_slice(&[("AWS_EC2_METADATA_DISABLED", "TRUE")]))
+            .build();
+        match provider.provide_credentials().await {
+            Err(CredentialsError::CredentialsNotLoaded) => {}
+            other => panic!("expected credentials not loaded, got {:?}", other),
+        }
+        assert!(connection.requests().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unreachable_imds_is_skipped() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let connector = {
+            let attempts = attempts.clone();
+            tower::service_fn(move |_request: http::Request<SdkBody>| {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                async { Err::<http::Response<SdkBody>, BoxError>("connection refused".into()) }
+            })
+        };
+        let client = imds::Client::builder()
+            .env(Env::from_slice(&[]))
+            .connector(DynConnector::new(connector))
+            .build()
+            .expect("valid client");
+        let provider = ImdsCredentialsProvider::builder()
+            .imds_client(client)
+            .env(Env::from_slice(&[]))
+            .unreachable_backoff(Duration::from_secs(60))
+            .build();
+        let assert_not_loaded = |result| match result {
+            Err(CredentialsError::CredentialsNotLoaded) => {}
+            other => panic!("expected credentials not loaded, got {:?}", other),
+        };
+
+        assert_not_loaded(provider.provide_credentials().await);
+        let first_attempts = attempts.load(Ordering::SeqCst);
+        assert!(first_attempts > 0);
+
+        // within the backoff, IMDS is not contacted
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_not_loaded(provider.provide_credentials().await);
+        assert_eq!(attempts.load(Ordering::SeqCst), first_attempts);
+
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert_not_loaded(provider.provide_credentials().await);
+        assert_eq!(attempts.load(Ordering::SeqCst), 2 * first_attempts);
+    }
+}
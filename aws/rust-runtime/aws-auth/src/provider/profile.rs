@@ -0,0 +1,274 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+use crate::provider::CredentialProcessProvider;
+use crate::{Credentials, CredentialsError, ProvideCredentials};
+use aws_types::os_shim::{Env, Fs};
+use aws_types::profile::{self, Profile};
+
+const PROFILE_PROVIDER: &str = "ProfileFile";
+
+/// Load Credentials from the shared config & credentials files
+///
+/// The active profile is selected with `AWS_PROFILE` (`default` if unset). The file locations may
+/// be overridden with `AWS_CONFIG_FILE` and `AWS_SHARED_CREDENTIALS_FILE`. See
+/// [`aws_types::profile`](aws_types::profile) for details of how the files are merged.
+///
+/// Profiles may contain static credentials (`aws_access_key_id` & `aws_secret_access_key`) or a
+/// `credential_process` command, which is run with [`CredentialProcessProvider`]. Static
+/// credentials take precedence.
+///
+/// Profiles that assume a role (`role_arn`) are not supported: assuming a role requires an STS
+/// client, which cannot be a dependency of this crate. No credentials are loaded from such a
+/// profile, so a chain falls through to its next provider. Use
+/// `aws_auth_providers::default_provider` or `aws_auth_providers::profile::ProfileFileCredentialsProvider`
+/// to assume the role.
+///
+/// The profile files are re-read each time credentials are requested.
+#[derive(Debug)]
+pub struct ProfileFileCredentialsProvider {
+    fs: Fs,
+    env: Env,
+}
+
+impl ProfileFileCredentialsProvider {
+    /// Create a Profile provider that loads from the real file system & environment
+    pub fn new() -> Self {
+        ProfileFileCredentialsProvider {
+            fs: Fs::real(),
+            env: Env::real(),
+        }
+    }
+
+    /// Create a Profile provider that loads from a custom file system & environment, eg. for testing
+    pub fn for_fs(fs: Fs, env: Env) -> Self {
+        ProfileFileCredentialsProvider { fs, env }
+    }
+}
+
+impl Default for ProfileFileCredentialsProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProvideCredentials for ProfileFileCredentialsProvider {
+    fn provide_credentials(&self) -> Result<Credentials, CredentialsError> {
+        let profiles = profile::load(&self.fs, &self.env)
+            .map_err(|err| CredentialsError::Unhandled(err.into()))?;
+        let profile = profiles
+            .get_profile(profiles.selected_profile())
+            .ok_or(CredentialsError::CredentialsNotLoaded)?;
+        if profile.get("role_arn").is_some() {
+            tracing::warn!(
+                profile = %profile.name(),
+                "profile assumes a role, which is not supported by this provider. \
+                 Use the profile provider of aws-auth-providers instead."
+            );
+            return Err(CredentialsError::CredentialsNotLoaded);
+        }
+        match Self::static_credentials(profile)? {
+            Some(credentials) => Ok(credentials),
+            None => match profile.get("credential_process") {
+                Some(command) => CredentialProcessProvider::new(command).provide_credentials(),
+                None => Err(CredentialsError::CredentialsNotLoaded),
+            },
+        }
+    }
+}
+
+impl ProfileFileCredentialsProvider {
+    /// Load static credentials (`aws_access_key_id` & `aws_secret_access_key`) from a profile
+    ///
+    /// Returns `None` if the profile does not set `aws_access_key_id`.
+    pub fn static_credentials(profile: &Profile) -> Result<Option<Credentials>, CredentialsError> {
+        let access_key = match profile.get("aws_access_key_id") {
+            Some(access_key) => access_key,
+            None => return Ok(None),
+        };
+        let secret_key = profile.get("aws_secret_access_key").ok_or_else(|| {
+            CredentialsError::Unhandled(
+                format!(
+                    "profile `{}` set aws_access_key_id but not aws_secret_access_key",
+                    profile.name()
+                )
+                .into(),
+            )
+        })?;
+        Ok(Some(Credentials::new(
+            access_key,
+            secret_key,
+            profile
+                .get("aws_session_token")
+                .map(|token| token.to_string()),
+            None,
+            PROFILE_PROVIDER,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::provider::profile::ProfileFileCredentialsProvider;
+    use crate::provider::CredentialsProviderChain;
+    use crate::{Credentials, CredentialsError, ProvideCredentials};
+    use aws_types::os_shim::{Env, Fs};
+    use std::collections::HashMap;
+
+    fn provider(files: &[(&str, &str)], env: &[(&str, &str)]) -> ProfileFileCredentialsProvider {
+        let fs = files
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.as_bytes().to_vec()))
+            .collect::<HashMap<_, _>>();
+        let mut env = env.to_vec();
+        env.push(("HOME", "/home"));
+        ProfileFileCredentialsProvider::for_fs(Fs::from_map(fs), Env::from_slice(&env))
+    }
+
+    #[test]
+    fn default_profile_from_credentials_file() {
+        let provider = provider(
+            &[(
+                "/home/.aws/credentials",
+                "[default]\naws_access_key_id = AKID\naws_secret_access_key = secret\naws_session_token = token",
+            )],
+            &[],
+        );
+        let creds = provider.provide_credentials().expect("valid credentials");
+        assert_eq!(creds.access_key_id(), "AKID");
+        assert_eq!(creds.secret_access_key(), "secret");
+        assert_eq!(creds.session_token(), Some("token"));
+        assert_eq!(creds.expiry(), None);
+        assert_eq!(creds.provider_name(), "ProfileFile");
+    }
+
+    #[test]
+    fn aws_profile_selects_profile() {
+        let provider = provider(
+            &[
+                (
+                    "/home/.aws/config",
+                    "[profile dev]\naws_access_key_id = AKID\naws_secret_access_key = from-config",
+                ),
+                (
+                    "/home/.aws/credentials",
+                    "[default]\naws_access_key_id = default\naws_secret_access_key = default",
+                ),
+            ],
+            &[("AWS_PROFILE", "dev")],
+        );
+        let creds = provider.provide_credentials().expect("valid credentials");
+        assert_eq!(creds.access_key_id(), "AKID");
+        assert_eq!(creds.secret_access_key(), "from-config");
+        assert_eq!(creds.session_token(), None);
+    }
+
+    #[test]
+    fn custom_file_locations() {
+        let provider = provider(
+            &[(
+                "/custom/credentials",
+                "[default]\naws_access_key_id = AKID\naws_secret_access_key = secret",
+            )],
+            &[("AWS_SHARED_CREDENTIALS_FILE", "/custom/credentials")],
+        );
+        let creds = provider.provide_credentials().expect("valid credentials");
+        assert_eq!(creds.access_key_id(), "AKID");
+    }
+
+    #[test]
+    fn missing_files_or_profile() {
+        let no_files = provider(&[], &[]);
+        assert!(matches!(
+            no_files.provide_credentials(),
+            Err(CredentialsError::CredentialsNotLoaded)
+        ));
+
+        let wrong_profile = provider(
+            &[(
+                "/home/.aws/credentials",
+                "[default]\naws_access_key_id = AKID\naws_secret_access_key = secret",
+            )],
+            &[("AWS_PROFILE", "other")],
+        );
+        assert!(matches!(
+            wrong_profile.provide_credentials(),
+            Err(CredentialsError::CredentialsNotLoaded)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn credential_process() {
+        let provider = provider(
+            &[(
+                "/home/.aws/config",
+                r#"[default]
+credential_process = echo '{"Version": 1, "AccessKeyId": "AKID", "SecretAccessKey": "secret"}'"#,
+            )],
+            &[],
+        );
+        let creds = provider.provide_credentials().expect("valid credentials");
+        assert_eq!(creds.access_key_id(), "AKID");
+        assert_eq!(creds.provider_name(), "CredentialProcess");
+    }
+
+    #[test]
+    fn static_credentials_take_precedence_over_process() {
+        let provider = provider(
+            &[(
+                "/home/.aws/config",
+                "[default]\naws_access_key_id = AKID\naws_secret_access_key = secret\ncredential_process = exit 1",
+            )],
+            &[],
+        );
+        let creds = provider.provide_credentials().expect("valid credentials");
+        assert_eq!(creds.provider_name(), "ProfileFile");
+    }
+
+    #[test]
+    fn invalid_profiles() {
+        let missing_secret = provider(
+            &[(
+                "/home/.aws/credentials",
+                "[default]\naws_access_key_id = AKID",
+            )],
+            &[],
+        );
+        assert!(matches!(
+            missing_secret.provide_credentials(),
+            Err(CredentialsError::Unhandled(_))
+        ));
+
+        let bad_syntax = provider(
+            &[("/home/.aws/config", "[default\nregion = us-east-1")],
+            &[],
+        );
+        assert!(matches!(
+            bad_syntax.provide_credentials(),
+            Err(CredentialsError::Unhandled(_))
+        ));
+    }
+
+    #[test]
+    fn role_profiles_fall_through() {
+        let assume_role = provider(
+            &[(
+                "/home/.aws/config",
+                "[default]\nrole_arn = arn:aws:iam::123456789012:role/demo\nsource_profile = base\n[profile base]\naws_access_key_id = AKID\naws_secret_access_key = secret",
+            )],
+            &[],
+        );
+        assert!(matches!(
+            assume_role.provide_credentials(),
+            Err(CredentialsError::CredentialsNotLoaded)
+        ));
+
+        let chain = CredentialsProviderChain::first_try("Profile", assume_role)
+            .or_else("Static", Credentials::from_keys("FALLBACK", "secret", None));
+        let creds = chain.provide_credentials().expect("valid credentials");
+        assert_eq!(creds.access_key_id(), "FALLBACK");
+    }
+}
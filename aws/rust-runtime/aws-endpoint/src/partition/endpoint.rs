@@ -46,6 +46,7 @@ impl Protocol {
 }
 
 #[derive(Eq, PartialEq, Copy, Clone, Debug)]
+#[non_exhaustive]
 pub enum SignatureVersion {
     V4,
 }
@@ -5,18 +5,22 @@
 
 pub mod user_agent;
 
-use smithy_http::result::SdkError;
+use aws_types::clock_skew::ClockSkewCorrected;
+use smithy_http::result::{ConnectorError, SdkError};
 use smithy_http::retry::ClassifyResponse;
 use smithy_types::retry::{ErrorKind, ProvideErrorKind, RetryKind};
 use std::time::Duration;
 
 /// A retry policy that models AWS error codes as outlined in the SEP
 ///
-/// In order of priority:
+/// Requests that failed to be dispatched are retried if the connector classified the failure as
+/// retryable (see [`ConnectorError`](ConnectorError)). For error responses, in order of priority:
 /// 1. The `x-amz-retry-after` header is checked
 /// 2. The modeled error retry mode is checked
-/// 3. The code is checked against a predetermined list of throttling errors & transient error codes
-/// 4. The status code is checked against a predetermined list of status codes
+/// 3. The code is checked against a predetermined list of clock skew errors: these are retried if
+///    the clock skew was corrected after the response (see [`ClockSkewCorrected`](ClockSkewCorrected))
+/// 4. The code is checked against a predetermined list of throttling errors & transient error codes
+/// 5. The status code is checked against a predetermined list of status codes
 #[non_exhaustive]
 #[derive(Clone)]
 pub struct AwsErrorRetryPolicy;
@@ -39,6 +43,14 @@ const THROTTLING_ERRORS: &[&str] = &[
     "EC2ThrottledException",
 ];
 const TRANSIENT_ERRORS: &[&str] = &["RequestTimeout", "RequestTimeoutException"];
+const CLOCK_SKEW_ERRORS: &[&str] = &[
+    "RequestTimeTooSkewed",
+    "RequestExpired",
+    "RequestInTheFuture",
+    "InvalidSignatureException",
+    "SignatureDoesNotMatch",
+    "AuthFailure",
+];
 
 impl AwsErrorRetryPolicy {
     /// Create an `AwsErrorRetryPolicy` with the default set of known error & status codes
@@ -61,6 +73,14 @@ where
         let (err, response) = match err {
             Ok(_) => return RetryKind::NotRetryable,
             Err(SdkError::ServiceError { err, raw }) => (err, raw),
+            Err(SdkError::DispatchFailure(err)) => {
+                return match ConnectorError::find(err.as_ref())
+                    .and_then(ConnectorError::retryable_error_kind)
+                {
+                    Some(kind) => RetryKind::Error(kind),
+                    None => RetryKind::NotRetryable,
+                };
+            }
             Err(_) => return RetryKind::NotRetryable,
         };
         if let Some(retry_after_delay) = response
@@ -75,6 +95,13 @@ where
             return RetryKind::Error(kind);
         };
         if let Some(code) = err.code() {
+            // Signing again with the corrected clock is enough to fix the request. If the clock
+            // was already correct, the error has another cause and retrying won't help.
+            if CLOCK_SKEW_ERRORS.contains(&code)
+                && response.extensions().get::<ClockSkewCorrected>().is_some()
+            {
+                return RetryKind::Error(ErrorKind::ClientError);
+            }
             if THROTTLING_ERRORS.contains(&code) {
                 return RetryKind::Error(ErrorKind::ThrottlingError);
             }
@@ -93,8 +120,9 @@ where
 #[cfg(test)]
 mod test {
     use crate::AwsErrorRetryPolicy;
+    use aws_types::clock_skew::ClockSkewCorrected;
     use smithy_http::body::SdkBody;
-    use smithy_http::result::{SdkError, SdkSuccess};
+    use smithy_http::result::{ConnectorError, SdkError, SdkSuccess};
     use smithy_http::retry::ClassifyResponse;
     use smithy_types::retry::{ErrorKind, ProvideErrorKind, RetryKind};
     use std::time::Duration;
@@ -196,6 +224,25 @@ mod test {
         )
     }
 
+    #[test]
+    fn classify_clock_skew() {
+        let policy = AwsErrorRetryPolicy::new();
+        let skewed = || CodedError {
+            code: "RequestTimeTooSkewed",
+        };
+        assert_eq!(
+            policy.classify(make_err(skewed(), http::Response::new("skewed")).as_ref()),
+            RetryKind::NotRetryable
+        );
+
+        let mut test_response = http::Response::new("skewed");
+        test_response.extensions_mut().insert(ClockSkewCorrected);
+        assert_eq!(
+            policy.classify(make_err(skewed(), test_response).as_ref()),
+            RetryKind::Error(ErrorKind::ClientError)
+        );
+    }
+
     #[test]
     fn classify_generic() {
         let err = smithy_types::Error::builder().code("SlowDown").build();
@@ -243,4 +290,23 @@ mod test {
             RetryKind::Explicit(Duration::from_millis(5000))
         );
     }
+
+    #[test]
+    fn classify_dispatch_failures() {
+        fn classify(err: Box<dyn std::error::Error + Send + Sync>) -> RetryKind {
+            let err = SdkError::<UnmodeledError>::DispatchFailure(err);
+            let result: Result<&SdkSuccess<()>, _> = Err(&err);
+            AwsErrorRetryPolicy::new().classify(result)
+        }
+        assert_eq!(
+            classify(Box::new(ConnectorError::transient("connection reset"))),
+            RetryKind::Error(ErrorKind::TransientError)
+        );
+        assert_eq!(
+            classify(Box::new(ConnectorError::other("invalid certificate"))),
+            RetryKind::NotRetryable
+        );
+        // failures of connectors that don't classify their errors aren't retried
+        assert_eq!(classify("connection reset".into()), RetryKind::NotRetryable);
+    }
 }
@@ -0,0 +1,140 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Clock skew correction middleware
+//!
+//! [`ClockSkewLayer`] shares a [`ClockSkew`] with every request of a client, so that requests are
+//! signed with the time of the service, and learns the offset of the local clock from the `Date`
+//! header of responses.
+
+use aws_types::clock_skew::{ClockSkew, ClockSkewCorrected};
+use http::header::DATE;
+use smithy_http::body::SdkBody;
+use smithy_http::operation;
+use smithy_types::instant::Format;
+use smithy_types::Instant;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+use tower::{Layer, Service};
+
+/// Layer that corrects the signing time of requests by the clock skew learned from responses
+///
+/// Before a request is dispatched, the [`ClockSkew`] is added to its property bag to be used by
+/// the signer. When the `Date` of a response shows that the (corrected) local clock is off, the
+/// offset is updated and [`ClockSkewCorrected`] is added to the extensions of the response so that
+/// the request can be retried.
+#[derive(Clone, Debug, Default)]
+pub struct ClockSkewLayer {
+    clock_skew: ClockSkew,
+}
+
+impl ClockSkewLayer {
+    pub fn new(clock_skew: ClockSkew) -> Self {
+        ClockSkewLayer { clock_skew }
+    }
+}
+
+impl<S> Layer<S> for ClockSkewLayer {
+    type Service = ClockSkewService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ClockSkewService {
+            inner,
+            clock_skew: self.clock_skew.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ClockSkewService<S> {
+    inner: S,
+    clock_skew: ClockSkew,
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+impl<S> Service<operation::Request> for ClockSkewService<S>
+where
+    S: Service<operation::Request, Response = http::Response<SdkBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: operation::Request) -> Self::Future {
+        req.config_mut().insert(self.clock_skew.clone());
+        let clock_skew = self.clock_skew.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let mut response = response.await?;
+            let server_time = response
+                .headers()
+                .get(DATE)
+                .and_then(|date| date.to_str().ok())
+                .and_then(|date| Instant::from_str(date, Format::HttpDate).ok())
+                .and_then(|date| date.to_system_time());
+            if let Some(server_time) = server_time {
+                if clock_skew.update(server_time, SystemTime::now()) {
+                    tracing::debug!(date = ?response.headers().get(DATE), "corrected clock skew");
+                    response.extensions_mut().insert(ClockSkewCorrected);
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::clock_skew::ClockSkewLayer;
+    use aws_types::clock_skew::{ClockSkew, ClockSkewCorrected};
+    use smithy_http::body::SdkBody;
+    use smithy_http::operation;
+    use smithy_types::instant::Format;
+    use smithy_types::Instant;
+    use std::convert::Infallible;
+    use std::time::{Duration, SystemTime};
+    use tower::{service_fn, Layer, Service, ServiceExt};
+
+    #[tokio::test]
+    async fn learn_clock_skew() {
+        let clock_skew = ClockSkew::new();
+        let server_time = SystemTime::now() + Duration::from_secs(3600);
+        let mut svc = ClockSkewLayer::new(clock_skew.clone()).layer(service_fn(
+            move |req: operation::Request| async move {
+                // the clock skew is available to the signer
+                assert!(req.config().get::<ClockSkew>().is_some());
+                Ok::<_, Infallible>(
+                    http::Response::builder()
+                        .header(
+                            "Date",
+                            Instant::from_system_time(server_time).fmt(Format::HttpDate),
+                        )
+                        .body(SdkBody::from(""))
+                        .unwrap(),
+                )
+            },
+        ));
+        let request = || operation::Request::new(http::Request::new(SdkBody::from("")));
+        let response = svc.ready().await.unwrap().call(request()).await.unwrap();
+        assert!(response.extensions().get::<ClockSkewCorrected>().is_some());
+        let offset = clock_skew
+            .now()
+            .duration_since(SystemTime::now())
+            .expect("the corrected clock is ahead");
+        assert!(offset > Duration::from_secs(3500), "{:?}", offset);
+
+        // once corrected, the skew is not corrected again
+        let response = svc.ready().await.unwrap().call(request()).await.unwrap();
+        assert!(response.extensions().get::<ClockSkewCorrected>().is_none());
+    }
+}
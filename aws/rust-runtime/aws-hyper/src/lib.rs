@@ -7,40 +7,66 @@
 pub use smithy_client::test_connection;
 
 pub use smithy_client::retry::Config as RetryConfig;
+pub use smithy_client::retry::{OperationRetryConfig, RetryMode};
+pub use smithy_client::timeout::Config as TimeoutConfig;
 
+pub mod clock_skew;
+pub mod presign;
+
+use crate::clock_skew::ClockSkewLayer;
 use aws_endpoint::AwsEndpointStage;
 use aws_http::user_agent::UserAgentStage;
-use aws_sig_auth::middleware::SigV4SigningStage;
+use aws_sig_auth::middleware::{PayloadHashStage, SigV4SigningStage};
 use aws_sig_auth::signer::SigV4Signer;
+use aws_types::clock_skew::ClockSkew;
 pub use smithy_http::result::{SdkError, SdkSuccess};
-use smithy_http_tower::map_request::MapRequestLayer;
+use smithy_http_tower::map_request::{AsyncMapRequestLayer, MapRequestLayer};
 use std::fmt::Debug;
 use tower::layer::util::Stack;
 use tower::ServiceBuilder;
 
 type AwsMiddlewareStack = Stack<
-    MapRequestLayer<SigV4SigningStage>,
-    Stack<MapRequestLayer<UserAgentStage>, MapRequestLayer<AwsEndpointStage>>,
+    AsyncMapRequestLayer<SigV4SigningStage>,
+    Stack<
+        AsyncMapRequestLayer<PayloadHashStage>,
+        Stack<
+            MapRequestLayer<UserAgentStage>,
+            Stack<MapRequestLayer<AwsEndpointStage>, ClockSkewLayer>,
+        >,
+    >,
 >;
 
+/// AWS middleware
+///
+/// The clock skew learned from responses is shared by all the requests sent through the same
+/// `AwsMiddleware`, ie. by all the requests of a client.
 #[derive(Debug, Default)]
 #[non_exhaustive]
-pub struct AwsMiddleware;
+pub struct AwsMiddleware {
+    clock_skew: ClockSkew,
+}
+
 impl<S> tower::Layer<S> for AwsMiddleware {
     type Service = <AwsMiddlewareStack as tower::Layer<S>>::Service;
 
     fn layer(&self, inner: S) -> Self::Service {
-        let signer = MapRequestLayer::for_mapper(SigV4SigningStage::new(SigV4Signer::new()));
+        let signer = AsyncMapRequestLayer::for_mapper(SigV4SigningStage::new(SigV4Signer::new()));
+        let payload_hash = AsyncMapRequestLayer::for_mapper(PayloadHashStage::new());
         let endpoint_resolver = MapRequestLayer::for_mapper(AwsEndpointStage);
         let user_agent = MapRequestLayer::for_mapper(UserAgentStage::new());
+        let clock_skew = ClockSkewLayer::new(self.clock_skew.clone());
         // These layers can be considered as occuring in order, that is:
-        // 1. Resolve an endpoint
-        // 2. Add a user agent
-        // 3. Sign
-        // (4. Dispatch over the wire)
+        // 1. Share the clock skew of the client (and learn it from the response)
+        // 2. Resolve an endpoint
+        // 3. Add a user agent
+        // 4. Hash replayable streaming bodies
+        // 5. Sign
+        // (6. Dispatch over the wire)
         ServiceBuilder::new()
+            .layer(clock_skew)
             .layer(endpoint_resolver)
             .layer(user_agent)
+            .layer(payload_hash)
             .layer(signer)
             .service(inner)
     }
@@ -112,4 +138,13 @@ mod tests {
         let s = format!("{:?}", client);
         assert!(s.contains("quota_available"));
     }
+
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    #[test]
+    fn client_exposes_retry_state() {
+        let client = crate::Client::https();
+        let state = client.retry_state();
+        assert_eq!(state.quota_available(), state.quota_capacity());
+        assert_eq!(state.max_attempts(), 3);
+    }
 }
@@ -9,13 +9,17 @@ use aws_endpoint::set_endpoint_resolver;
 use aws_http::user_agent::AwsUserAgent;
 use aws_http::AwsErrorRetryPolicy;
 use aws_hyper::test_connection::TestConnection;
-use aws_hyper::{Client, RetryConfig};
+use aws_hyper::{Client, RetryConfig, TimeoutConfig};
 use aws_sig_auth::signer::OperationSigningConfig;
 use aws_types::region::Region;
 use aws_types::SigningService;
 use bytes::Bytes;
+use hmac::{Hmac, Mac, NewMac};
 use http::header::{AUTHORIZATION, HOST, USER_AGENT};
-use http::{Response, Uri};
+use http::{HeaderMap, Response, Uri};
+use sha2::{Digest, Sha256};
+use smithy_eventstream::frame::{DeferredSigner, HeaderValue, MarshallMessage, Message};
+use smithy_eventstream::stream::SignedEventStreamBody;
 use smithy_http::body::SdkBody;
 use smithy_http::operation;
 use smithy_http::operation::Operation;
@@ -26,7 +30,8 @@ use std::convert::Infallible;
 use std::error::Error;
 use std::fmt;
 use std::fmt::{Display, Formatter};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, UNIX_EPOCH};
 use tokio::time::Instant;
 
@@ -74,7 +79,12 @@ where
 }
 
 fn test_operation() -> Operation<TestOperationParser, AwsErrorRetryPolicy> {
-    let req = operation::Request::new(http::Request::new(SdkBody::from("request body")))
+    let req = test_request(http::Request::new(SdkBody::from("request body")));
+    Operation::new(req, TestOperationParser).with_retry_policy(AwsErrorRetryPolicy::new())
+}
+
+fn test_request(req: http::Request<SdkBody>) -> operation::Request {
+    operation::Request::new(req)
         .augment(|req, mut conf| {
             set_endpoint_resolver(
                 &mut conf,
@@ -96,8 +106,7 @@ fn test_operation() -> Operation<TestOperationParser, AwsErrorRetryPolicy> {
             conf.insert(AwsUserAgent::for_tests());
             Result::<_, Infallible>::Ok(req)
         })
-        .unwrap();
-    Operation::new(req, TestOperationParser).with_retry_policy(AwsErrorRetryPolicy::new())
+        .unwrap()
 }
 
 #[cfg(any(feature = "native-tls", feature = "rustls"))]
@@ -134,6 +143,47 @@ async fn e2e_test() {
     conn.requests()[0].assert_matches(vec![]);
 }
 
+#[tokio::test]
+async fn retryable_streaming_body_test() {
+    fn req() -> http::Request<SdkBody> {
+        http::Request::new(SdkBody::from("request body"))
+    }
+    fn ok() -> http::Response<&'static str> {
+        http::Response::builder()
+            .status(200)
+            .body("response body")
+            .unwrap()
+    }
+    let conn = TestConnection::new(vec![(req(), ok()), (req(), ok())]);
+    let client = Client::new(conn.clone());
+    client
+        .call(test_operation())
+        .await
+        .expect("successful operation");
+    // A body that can be rebuilt but is only available as a stream, like `ByteStream::from_path`
+    let body = SdkBody::retryable(|| SdkBody::from(hyper::Body::from("request body")));
+    let streaming = Operation::new(test_request(http::Request::new(body)), TestOperationParser)
+        .with_retry_policy(AwsErrorRetryPolicy::new());
+    client.call(streaming).await.expect("successful operation");
+
+    let requests = conn.requests();
+    assert_eq!(requests.len(), 2);
+    let authorization = |request: &http::Request<SdkBody>| {
+        request.headers()[AUTHORIZATION]
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    // The streaming body was hashed ahead of signing, so it is signed like the in-memory body
+    // rather than with `UNSIGNED-PAYLOAD`
+    assert_eq!(
+        authorization(&requests[1].actual),
+        authorization(&requests[0].actual)
+    );
+    // The body was sent as a stream, not buffered
+    assert!(requests[1].actual.body().bytes().is_none());
+}
+
 #[tokio::test]
 async fn retry_test() {
     fn req() -> http::Request<SdkBody> {
@@ -205,6 +255,171 @@ async fn retry_test() {
     assert_time_passed(initial, Duration::from_secs(3));
 }
 
+#[tokio::test]
+async fn attempt_timeout_test() {
+    // The first attempt hangs, the second one succeeds
+    let calls = Arc::new(AtomicUsize::new(0));
+    let conn = {
+        let calls = calls.clone();
+        tower::service_fn(move |_: http::Request<SdkBody>| {
+            let first_call = calls.fetch_add(1, Ordering::SeqCst) == 0;
+            async move {
+                if first_call {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                }
+                Ok::<_, Infallible>(http::Response::new(SdkBody::from("response body")))
+            }
+        })
+    };
+    let client = Client::new(conn)
+        .with_retry_config(RetryConfig::default().with_base(|| 1_f64))
+        .with_timeout_config(TimeoutConfig::new().with_attempt_timeout(Duration::from_secs(5)));
+    tokio::time::pause();
+    let initial = tokio::time::Instant::now();
+    let resp = client
+        .call(test_operation())
+        .await
+        .expect("successful operation");
+    assert_eq!(resp, "Hello!");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    // the timed out attempt, followed by the backoff
+    assert_time_passed(initial, Duration::from_secs(6));
+}
+
+#[derive(Debug)]
+struct TestMarshaller;
+
+impl MarshallMessage for TestMarshaller {
+    type Input = &'static str;
+
+    fn marshall(&self, input: Self::Input) -> Result<Message, smithy_eventstream::error::Error> {
+        Ok(Message::new(input))
+    }
+}
+
+#[tokio::test]
+async fn event_stream_test() {
+    let (signer, sender) = DeferredSigner::new();
+    let events = futures_util::stream::iter(vec![Ok::<_, Infallible>("hello")]);
+    let body = SignedEventStreamBody::new(events, TestMarshaller, signer).into_sdk_body();
+    let mut req = http::Request::new(body);
+    *req.method_mut() = http::Method::POST;
+    let req = test_request(req)
+        .augment(|req, conf| {
+            conf.insert(sender);
+            Result::<_, Infallible>::Ok(req)
+        })
+        .unwrap();
+    let operation =
+        Operation::new(req, TestOperationParser).with_retry_policy(AwsErrorRetryPolicy::new());
+
+    // The connector reads the body, which signs the messages
+    let sent: Arc<Mutex<Option<(HeaderMap, Bytes)>>> = Default::default();
+    let conn = {
+        let sent = sent.clone();
+        tower::service_fn(move |req: http::Request<SdkBody>| {
+            let sent = sent.clone();
+            async move {
+                let (parts, body) = req.into_parts();
+                let body = hyper::body::to_bytes(body).await?;
+                *sent.lock().unwrap() = Some((parts.headers, body));
+                Ok::<_, tower::BoxError>(http::Response::new(SdkBody::from("response body")))
+            }
+        })
+    };
+    let client = Client::new(conn);
+    let resp = client.call(operation).await.expect("successful operation");
+    assert_eq!(resp, "Hello!");
+
+    let (headers, mut body) = sent.lock().unwrap().take().expect("request was sent");
+    assert_eq!(
+        headers["x-amz-content-sha256"],
+        "STREAMING-AWS4-HMAC-SHA256-EVENTS"
+    );
+    // The canonical request of the request that opens the stream, see
+    // https://docs.aws.amazon.com/general/latest/gr/sigv4-create-canonical-request.html
+    let canonical_request = "POST\n/\n\n\
+        host:test-service.test-region.amazonaws.com\n\
+        x-amz-content-sha256:STREAMING-AWS4-HMAC-SHA256-EVENTS\n\
+        x-amz-date:20210215T184017Z\n\
+        x-amz-user-agent:aws-sdk-rust/0.123.test api/test-service/0.123 os/windows/XPSP3 lang/rust/1.50.0\n\n\
+        host;x-amz-content-sha256;x-amz-date;x-amz-user-agent\n\
+        STREAMING-AWS4-HMAC-SHA256-EVENTS";
+    let seed_signature = test_signature(&format!(
+        "AWS4-HMAC-SHA256\n20210215T184017Z\n{}\n{}",
+        TEST_SCOPE,
+        sha256_hex(canonical_request.as_bytes())
+    ));
+    assert_eq!(
+        headers[AUTHORIZATION],
+        format!("AWS4-HMAC-SHA256 Credential=access_key/{}, SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-user-agent, Signature={}", TEST_SCOPE, seed_signature)
+    );
+
+    // Each message is chained to the signature of the request, then to the previous message. The
+    // string to sign of a message covers its `:date` header, encoded as the length of the name,
+    // the name, the type of a timestamp (8) and the big-endian milliseconds since the epoch.
+    let mut date_header = vec![5];
+    date_header.extend_from_slice(b":date");
+    date_header.push(8);
+    date_header.extend_from_slice(&1_613_414_417_000_i64.to_be_bytes());
+    let message_signature = |previous_signature: &str, payload: &[u8]| {
+        test_signature(&format!(
+            "AWS4-HMAC-SHA256-PAYLOAD\n20210215T184017Z\n{}\n{}\n{}\n{}",
+            TEST_SCOPE,
+            previous_signature,
+            sha256_hex(&date_header),
+            sha256_hex(payload)
+        ))
+    };
+    let chunk_signature = |message: &Message| {
+        message
+            .header(":chunk-signature")
+            .and_then(HeaderValue::as_byte_array)
+            .expect("signed message")
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>()
+    };
+    let event = Message::read_from(&mut body).unwrap();
+    assert_eq!(
+        Message::read_from(&mut event.payload().clone()).unwrap(),
+        Message::new(&b"hello"[..])
+    );
+    assert_eq!(
+        chunk_signature(&event),
+        message_signature(&seed_signature, event.payload())
+    );
+    let end = Message::read_from(&mut body).unwrap();
+    assert!(end.payload().is_empty());
+    assert_eq!(
+        chunk_signature(&end),
+        message_signature(&chunk_signature(&event), b"")
+    );
+    assert!(body.is_empty());
+}
+
+/// Credential scope of the requests signed by `test_request`
+const TEST_SCOPE: &str = "20210215/test-region/test-service-signing/aws4_request";
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Sign `string_to_sign` with the signing key of `test_request`, derived as described in
+/// https://docs.aws.amazon.com/general/latest/gr/sigv4-calculate-signature.html
+fn test_signature(string_to_sign: &str) -> String {
+    fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+        mac.update(data.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+    let key = hmac(b"AWS4secret_key", "20210215");
+    let key = hmac(&key, "test-region");
+    let key = hmac(&key, "test-service-signing");
+    let key = hmac(&key, "aws4_request");
+    hex::encode(hmac(&key, string_to_sign))
+}
+
 /// Validate that time has passed with a 5ms tolerance
 ///
 /// This is to account for some non-determinism in the Tokio timer
@@ -0,0 +1,340 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Streaming payload signing with `aws-chunked` encoding
+//!
+//! When a body can't be hashed before the request is sent, S3 accepts it in chunks that are each
+//! signed with a signature chained to the signature of the previous chunk. The first chunk is
+//! chained to the signature of the request headers (the "seed signature"). See
+//! <https://docs.aws.amazon.com/AmazonS3/latest/API/sigv4-streaming.html>.
+
+use crate::sigv4_util::{credential_scope, format_date_time, hmac_sha256, sha256_hex};
+use bytes::{Bytes, BytesMut};
+use http_body::{Body, SizeHint};
+use smithy_http::body::SdkBody;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+
+/// `x-amz-content-sha256` of requests with an `aws-chunked` body
+pub(crate) const STREAMING_PAYLOAD: &str = "STREAMING-AWS4-HMAC-SHA256-PAYLOAD";
+
+/// Size of the chunks the payload is split into. Only the last chunk may be smaller.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+const CHUNK_SIGNATURE: &str = ";chunk-signature=";
+const CRLF: &str = "\r\n";
+/// Length of a hex-encoded SHA-256 signature
+const SIGNATURE_LENGTH: u64 = 64;
+
+/// SHA-256 of the empty string
+const EMPTY_PAYLOAD_HASH: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// The state needed to sign the chunks of a payload
+#[derive(Debug, Clone)]
+pub(crate) struct ChunkSigner {
+    signing_key: Vec<u8>,
+    amz_date: String,
+    scope: String,
+    seed_signature: String,
+}
+
+impl ChunkSigner {
+    /// `signing_key` and `time` must be the ones used to compute `seed_signature`
+    pub(crate) fn new(
+        signing_key: Vec<u8>,
+        time: SystemTime,
+        region: &str,
+        service: &str,
+        seed_signature: impl Into<String>,
+    ) -> Self {
+        ChunkSigner {
+            signing_key,
+            amz_date: format_date_time(time),
+            scope: credential_scope(time, region, service),
+            seed_signature: seed_signature.into(),
+        }
+    }
+
+    fn sign_chunk(&self, previous_signature: &str, chunk: &[u8]) -> String {
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256-PAYLOAD\n{}\n{}\n{}\n{}\n{}",
+            self.amz_date,
+            self.scope,
+            previous_signature,
+            EMPTY_PAYLOAD_HASH,
+            sha256_hex(chunk)
+        );
+        hex::encode(hmac_sha256(&self.signing_key, string_to_sign.as_bytes()))
+    }
+}
+
+/// The length of the `aws-chunked` encoding of a payload of `decoded_length` bytes
+///
+/// This is the `content-length` of the request, while `decoded_length` is sent as
+/// `x-amz-decoded-content-length`.
+pub(crate) fn encoded_length(decoded_length: u64) -> u64 {
+    let chunk_size = CHUNK_SIZE as u64;
+    let full_chunks = decoded_length / chunk_size;
+    let remainder = decoded_length % chunk_size;
+    let mut length = full_chunks * frame_length(chunk_size) + frame_length(0);
+    if remainder > 0 {
+        length += frame_length(remainder);
+    }
+    length
+}
+
+/// Length of the frame of a chunk of `chunk_length` bytes: `<hex length>;chunk-signature=<signature>\r\n<data>\r\n`
+fn frame_length(chunk_length: u64) -> u64 {
+    format!("{:x}", chunk_length).len() as u64
+        + CHUNK_SIGNATURE.len() as u64
+        + SIGNATURE_LENGTH
+        + CRLF.len() as u64
+        + chunk_length
+        + CRLF.len() as u64
+}
+
+/// Wrap `body` in an `aws-chunked` encoded body
+///
+/// `decoded_length` must be the length of `body`. If `body` is retryable, so is the returned body:
+/// it is rebuilt from the rebuilt `body` and signed with the same seed signature, so it yields
+/// the same frames.
+pub(crate) fn aws_chunked_body(body: SdkBody, decoded_length: u64, signer: ChunkSigner) -> SdkBody {
+    let signer = Arc::new(signer);
+    match body.try_clone() {
+        Some(_) => SdkBody::retryable(move || {
+            let body = body
+                .try_clone()
+                .expect("the body was checked to be retryable");
+            SdkBody::from_dyn(http_body::combinators::BoxBody::new(AwsChunkedBody::new(
+                body,
+                decoded_length,
+                signer.clone(),
+            )))
+        }),
+        None => SdkBody::from_dyn(http_body::combinators::BoxBody::new(AwsChunkedBody::new(
+            body,
+            decoded_length,
+            signer,
+        ))),
+    }
+}
+
+#[derive(Debug)]
+struct AwsChunkedBody {
+    inner: SdkBody,
+    signer: Arc<ChunkSigner>,
+    previous_signature: String,
+    buffer: BytesMut,
+    decoded_length: u64,
+    bytes_read: u64,
+    inner_done: bool,
+    done: bool,
+}
+
+impl AwsChunkedBody {
+    fn new(inner: SdkBody, decoded_length: u64, signer: Arc<ChunkSigner>) -> Self {
+        AwsChunkedBody {
+            inner,
+            previous_signature: signer.seed_signature.clone(),
+            signer,
+            buffer: BytesMut::new(),
+            decoded_length,
+            bytes_read: 0,
+            inner_done: false,
+            done: false,
+        }
+    }
+
+    /// Sign `chunk` and frame it
+    fn frame(&mut self, chunk: &[u8]) -> Bytes {
+        let signature = self.signer.sign_chunk(&self.previous_signature, chunk);
+        let mut frame = BytesMut::with_capacity(frame_length(chunk.len() as u64) as usize);
+        frame.extend_from_slice(format!("{:x}", chunk.len()).as_bytes());
+        frame.extend_from_slice(CHUNK_SIGNATURE.as_bytes());
+        frame.extend_from_slice(signature.as_bytes());
+        frame.extend_from_slice(CRLF.as_bytes());
+        frame.extend_from_slice(chunk);
+        frame.extend_from_slice(CRLF.as_bytes());
+        self.previous_signature = signature;
+        frame.freeze()
+    }
+}
+
+impl Body for AwsChunkedBody {
+    type Data = Bytes;
+    type Error = smithy_http::body::Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+            if this.buffer.len() >= CHUNK_SIZE {
+                let chunk = this.buffer.split_to(CHUNK_SIZE);
+                return Poll::Ready(Some(Ok(this.frame(&chunk))));
+            }
+            if this.inner_done {
+                // The final frame is an empty chunk, after the remainder of the payload
+                let chunk = this.buffer.split();
+                this.done = chunk.is_empty();
+                return Poll::Ready(Some(Ok(this.frame(&chunk))));
+            }
+            match Pin::new(&mut this.inner).poll_data(cx) {
+                Poll::Ready(Some(Ok(data))) => {
+                    this.bytes_read += data.len() as u64;
+                    this.buffer.extend_from_slice(&data);
+                }
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => {
+                    if this.bytes_read != this.decoded_length {
+                        this.done = true;
+                        return Poll::Ready(Some(Err(format!(
+                            "the body was {} bytes long but {} bytes were expected",
+                            this.bytes_read, this.decoded_length
+                        )
+                        .into())));
+                    }
+                    this.inner_done = true;
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact(encoded_length(self.decoded_length))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aws_chunked::{aws_chunked_body, encoded_length, ChunkSigner};
+    use crate::sigv4_util::signing_key;
+    use bytes::Bytes;
+    use http_body::Body;
+    use smithy_http::body::SdkBody;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    // Example from https://docs.aws.amazon.com/AmazonS3/latest/API/sigv4-streaming.html
+    fn example_signer() -> ChunkSigner {
+        let time = UNIX_EPOCH + Duration::from_secs(1369353600);
+        ChunkSigner::new(
+            signing_key(
+                "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY",
+                time,
+                "us-east-1",
+                "s3",
+            ),
+            time,
+            "us-east-1",
+            "s3",
+            "4f232c4386841ef735655705268965c44a0e4690baa4adea153f7db9fa80a0a9",
+        )
+    }
+
+    async fn read_frames(mut body: SdkBody) -> Vec<Bytes> {
+        let mut frames = vec![];
+        while let Some(frame) = body.data().await {
+            frames.push(frame.expect("valid frame"));
+        }
+        frames
+    }
+
+    fn frame_header(frame: &Bytes) -> &str {
+        let header = std::str::from_utf8(&frame[..frame.len().min(100)]).unwrap();
+        header.split("\r\n").next().unwrap()
+    }
+
+    #[tokio::test]
+    async fn s3_example() {
+        let payload = vec![b'a'; 66560];
+        assert_eq!(encoded_length(66560), 66824);
+        let body = aws_chunked_body(SdkBody::from(payload), 66560, example_signer());
+        assert_eq!(body.content_length(), Some(66824));
+
+        let frames = read_frames(body).await;
+        assert_eq!(frames.len(), 3);
+        assert_eq!(
+            frame_header(&frames[0]),
+            "10000;chunk-signature=ad80c730a21e5b8d04586a2213dd63b9a0e99e0e2307b0ade35a65485a288648"
+        );
+        assert_eq!(
+            frame_header(&frames[1]),
+            "400;chunk-signature=0055627c9e194cb4542bae2aa5492e3c1575bbb81b612b7d234b86a503ef5497"
+        );
+        assert_eq!(
+            frames[2],
+            "0;chunk-signature=b6c6ea8a5354eaf15b3cb7646744f4275b71ea724fed81ceb9323e279d449df9\r\n\r\n"
+        );
+        assert_eq!(frames.iter().map(|f| f.len()).sum::<usize>(), 66824);
+    }
+
+    #[tokio::test]
+    async fn body_is_rechunked() {
+        // The inner body yields data in pieces that don't line up with the chunks
+        let (mut sender, inner) = hyper::Body::channel();
+        let send = tokio::spawn(async move {
+            for _ in 0..7 {
+                sender
+                    .send_data(Bytes::from(vec![b'a'; 10000]))
+                    .await
+                    .unwrap();
+            }
+        });
+        let body = aws_chunked_body(SdkBody::from(inner), 70000, example_signer());
+        let frames = read_frames(body).await;
+        send.await.unwrap();
+        let lengths = frames.iter().map(frame_header).collect::<Vec<_>>();
+        assert!(lengths[0].starts_with("10000;"), "{:?}", lengths);
+        assert!(lengths[1].starts_with("1170;"), "{:?}", lengths);
+        assert!(lengths[2].starts_with("0;"), "{:?}", lengths);
+        assert_eq!(
+            frames.iter().map(|f| f.len() as u64).sum::<u64>(),
+            encoded_length(70000)
+        );
+    }
+
+    #[tokio::test]
+    async fn retryable_body() {
+        let body = aws_chunked_body(SdkBody::from("hello world"), 11, example_signer());
+        let retry = body.try_clone().expect("the inner body is retryable");
+        assert_eq!(read_frames(body).await, read_frames(retry).await);
+
+        let (_sender, inner) = hyper::Body::channel();
+        let body = aws_chunked_body(SdkBody::from(inner), 11, example_signer());
+        assert!(body.try_clone().is_none());
+    }
+
+    #[tokio::test]
+    async fn invalid_length() {
+        let mut body = aws_chunked_body(SdkBody::from("hello"), 11, example_signer());
+        assert!(body.data().await.expect("error").is_err());
+        assert!(body.data().await.is_none());
+    }
+
+    #[test]
+    fn lengths() {
+        // an empty payload is only the final frame
+        assert_eq!(encoded_length(0), 86);
+        assert_eq!(encoded_length(65536), 65626 + 86);
+    }
+}
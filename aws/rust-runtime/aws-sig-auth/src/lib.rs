@@ -7,5 +7,10 @@
 //!
 //! In the future, additional signature algorithms can be enabled as Cargo Features.
 
+mod aws_chunked;
+pub mod event_stream;
 pub mod middleware;
+mod query_signing;
 pub mod signer;
+mod sigv4_util;
+mod sigv4a;
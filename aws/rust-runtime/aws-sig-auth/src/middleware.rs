@@ -3,11 +3,24 @@
  * SPDX-License-Identifier: Apache-2.0.
  */
 
-use crate::signer::{OperationSigningConfig, RequestConfig, SigV4Signer, SigningError};
-use aws_auth::{Credentials, CredentialsError, CredentialsProvider};
-use smithy_http::middleware::MapRequest;
+use crate::event_stream::{SigV4MessageSigner, STREAMING_EVENTS_PAYLOAD};
+use crate::signer::{
+    HttpSignatureType, OperationSigningConfig, PayloadHash, RequestConfig, SigV4Signer,
+    SigningAlgorithm, SigningError,
+};
+use aws_auth::provider::SpawnBlockingAdapter;
+use aws_auth::{AsyncCredentialsProvider, CredentialsError, CredentialsProvider};
+use http_body::Body;
+use sha2::{Digest, Sha256};
+use smithy_eventstream::frame::DeferredSignerSender;
+use smithy_http::body::SdkBody;
+use smithy_http::middleware::AsyncMapRequest;
 use smithy_http::operation::Request;
 use smithy_http::property_bag::PropertyBag;
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
 use std::time::SystemTime;
 
 /// Middleware stage to sign requests with SigV4
@@ -16,9 +29,11 @@ use std::time::SystemTime;
 /// a signature.
 ///
 /// Prior to signing, the following fields MUST be present in the property bag:
-/// - [`SigningRegion`](SigningRegion): The region used when signing the request, eg. `us-east-1`
+/// - [`SigningRegion`](SigningRegion): The region used when signing the request, eg. `us-east-1`.
+///   With SigV4A, this is the set of regions the signature is valid in, eg. `*`.
 /// - [`SigningService`](SigningService): The name of the service to use when signing the request, eg. `dynamodb`
-/// - [`CredentialsProvider`](CredentialsProvider): A credentials provider to retrieve credentials
+/// - [`CredentialsProvider`](CredentialsProvider) or [`AsyncCredentialsProvider`](AsyncCredentialsProvider):
+///   A credentials provider to retrieve credentials. If both are present, the async provider is used.
 /// - [`OperationSigningConfig`](OperationSigningConfig): Operation specific signing configuration, eg.
 ///   changes to URL encoding behavior, or headers that must be omitted.
 /// If any of these fields are missing, the middleware will return an error.
@@ -26,6 +41,17 @@ use std::time::SystemTime;
 /// The following fields MAY be present in the property bag:
 /// - [`SystemTime`](SystemTime): The timestamp to use when signing the request. If this field is not present
 ///   [`SystemTime::now`](SystemTime::now) will be used.
+/// - [`ClockSkew`](ClockSkew): The offset of the local clock to the clock of the service. If this
+///   field is present and no `SystemTime` is set, the current time is corrected by the offset.
+/// - [`PayloadHash`](PayloadHash): The SHA-256 of a streaming body, computed by [`PayloadHashStage`].
+///   If this field is present, the payload is signed with it instead of `UNSIGNED-PAYLOAD`.
+/// - [`DeferredSignerSender`](DeferredSignerSender): The body of the request is a stream of events
+///   signed by a [`DeferredSigner`](smithy_eventstream::frame::DeferredSigner). The request is signed
+///   with [`STREAMING_EVENTS_PAYLOAD`] as its payload hash, then a [`SigV4MessageSigner`] seeded from
+///   the signature of the request is sent to the body. Only SigV4 header signing supports event streams.
+///
+/// Credentials are loaded asynchronously, so this stage is an [`AsyncMapRequest`] and should be
+/// added to a middleware stack with `AsyncMapRequestLayer`.
 #[derive(Clone, Debug)]
 pub struct SigV4SigningStage {
     signer: SigV4Signer,
@@ -37,6 +63,7 @@ impl SigV4SigningStage {
     }
 }
 
+use aws_types::clock_skew::ClockSkew;
 use aws_types::region::SigningRegion;
 use aws_types::SigningService;
 use thiserror::Error;
@@ -59,17 +86,32 @@ pub enum SigningStageError {
     CredentialsLoadingError(#[from] CredentialsError),
 }
 
+/// Extract the credentials provider from a [`PropertyBag`](smithy_http::property_bag::PropertyBag)
+///
+/// An async provider takes precedence over a synchronous provider. Synchronous providers may block
+/// while loading credentials (eg. the default provider chain contacting IMDS), so they are invoked
+/// on a blocking thread rather than on the async runtime.
+fn credentials_provider(
+    config: &PropertyBag,
+) -> Result<AsyncCredentialsProvider, SigningStageError> {
+    if let Some(provider) = config.get::<AsyncCredentialsProvider>() {
+        return Ok(provider.clone());
+    }
+    config
+        .get::<CredentialsProvider>()
+        .map(|provider| {
+            Arc::new(SpawnBlockingAdapter::new(provider.clone())) as AsyncCredentialsProvider
+        })
+        .ok_or(SigningStageError::MissingCredentialsProvider)
+}
+
 /// Extract a signing config from a [`PropertyBag`](smithy_http::property_bag::PropertyBag)
 fn signing_config(
     config: &PropertyBag,
-) -> Result<(&OperationSigningConfig, RequestConfig, Credentials), SigningStageError> {
+) -> Result<(&OperationSigningConfig, RequestConfig), SigningStageError> {
     let operation_config = config
         .get::<OperationSigningConfig>()
         .ok_or(SigningStageError::MissingSigningConfig)?;
-    let cred_provider = config
-        .get::<CredentialsProvider>()
-        .ok_or(SigningStageError::MissingCredentialsProvider)?;
-    let creds = cred_provider.provide_credentials()?;
     let region = config
         .get::<SigningRegion>()
         .ok_or(SigningStageError::MissingSigningRegion)?;
@@ -77,51 +119,213 @@ fn signing_config(
         .get::<SigningService>()
         .ok_or(SigningStageError::MissingSigningService)?;
     let request_config = RequestConfig {
-        request_ts: config
-            .get::<SystemTime>()
-            .copied()
-            .unwrap_or_else(SystemTime::now),
+        request_ts: match (config.get::<SystemTime>(), config.get::<ClockSkew>()) {
+            (Some(time), _) => *time,
+            (None, Some(clock_skew)) => clock_skew.now(),
+            (None, None) => SystemTime::now(),
+        },
         region,
         service: signing_service,
+        payload_hash: config.get::<PayloadHash>(),
     };
-    Ok((operation_config, request_config, creds))
+    Ok((operation_config, request_config))
 }
 
-impl MapRequest for SigV4SigningStage {
+impl AsyncMapRequest for SigV4SigningStage {
     type Error = SigningStageError;
+    type Future = Pin<Box<dyn Future<Output = Result<Request, Self::Error>> + Send + 'static>>;
+
+    fn apply(&self, req: Request) -> Self::Future {
+        let signer = self.signer.clone();
+        Box::pin(async move {
+            // Validate the rest of the configuration before loading credentials
+            let provider = {
+                let config = req.config();
+                signing_config(&config)?;
+                credentials_provider(&config)?
+            };
+            let creds = provider.provide_credentials().await?;
+            req.augment(|mut req, config| {
+                let event_stream = config.get::<DeferredSignerSender>().is_some();
+                if event_stream && config.get::<PayloadHash>().is_none() {
+                    config.insert(PayloadHash::from_hex(STREAMING_EVENTS_PAYLOAD.to_string()));
+                }
+                let (operation_config, request_config) = signing_config(config)?;
+                let mut operation_config = Cow::Borrowed(operation_config);
+                if event_stream {
+                    if operation_config.algorithm != SigningAlgorithm::SigV4
+                        || operation_config.signature_type != HttpSignatureType::HttpRequestHeaders
+                    {
+                        return Err(SigningStageError::InvalidBodyType);
+                    }
+                    // Event stream services (eg. Transcribe) require `x-amz-content-sha256` on
+                    // the request that opens the stream
+                    operation_config
+                        .to_mut()
+                        .signing_options
+                        .content_sha256_header = true;
+                }
+                let signature = signer
+                    .sign(&operation_config, &request_config, &creds, &mut req)
+                    .map_err(SigningStageError::SigningFailure)?;
+                if let Some(sender) = config.get::<DeferredSignerSender>() {
+                    let mut message_signer = SigV4MessageSigner::new(
+                        signature.as_str(),
+                        creds,
+                        request_config.region.clone(),
+                        request_config.service.clone(),
+                        config.get::<ClockSkew>().cloned().unwrap_or_default(),
+                    );
+                    // A signing time fixed in the property bag (eg. by tests) applies to the
+                    // messages as well
+                    if let Some(time) = config.get::<SystemTime>() {
+                        message_signer = message_signer.with_time_override(*time);
+                    }
+                    sender.send(Box::new(message_signer));
+                }
+                Ok(req)
+            })
+        })
+    }
+}
+
+/// Middleware stage to compute the SHA-256 of replayable streaming bodies
+///
+/// The signer can hash a body that is in memory, but a streaming body would have to be read before
+/// it is sent. When the body is retryable (eg. a [`ByteStream`](smithy_http::byte_stream::ByteStream)
+/// created with `ByteStream::from_path`), this stage streams a rebuilt copy of the body once to hash
+/// it and stores the result as a [`PayloadHash`] in the property bag, where
+/// [`SigV4SigningStage`] picks it up. The hash is kept in the property bag so it is not computed
+/// again when the request is retried.
+///
+/// Since this reads the whole body an extra time, the body is only hashed when the
+/// [`OperationSigningConfig`] can't sign a streaming payload otherwise: services that expect
+/// `x-amz-content-sha256` (S3) receive the body with `aws-chunked` encoding instead, and presigned
+/// requests aren't hashed. The body is read and hashed on a blocking thread so that a large file
+/// doesn't keep an async worker busy.
+///
+/// This stage should be added to the middleware stack before the signing stage with
+/// `AsyncMapRequestLayer`.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct PayloadHashStage;
+
+impl PayloadHashStage {
+    pub fn new() -> Self {
+        PayloadHashStage
+    }
+}
 
-    fn apply(&self, req: Request) -> Result<Request, Self::Error> {
-        req.augment(|mut req, config| {
-            let (operation_config, request_config, creds) = signing_config(config)?;
+impl AsyncMapRequest for PayloadHashStage {
+    type Error = smithy_http::body::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Request, Self::Error>> + Send + 'static>>;
 
-            self.signer
-                .sign(&operation_config, &request_config, &creds, &mut req)
-                .map_err(|err| SigningStageError::SigningFailure(err))?;
+    fn apply(&self, mut req: Request) -> Self::Future {
+        Box::pin(async move {
+            let hash_required = {
+                let config = req.config();
+                config.get::<PayloadHash>().is_none()
+                    && config.get::<DeferredSignerSender>().is_none()
+                    && config
+                        .get::<OperationSigningConfig>()
+                        .map(OperationSigningConfig::requires_payload_hash)
+                        .unwrap_or(false)
+            };
+            if !hash_required || req.request_mut().body().bytes().is_some() {
+                return Ok(req);
+            }
+            let body = match req.request_mut().body().try_clone() {
+                Some(body) => body,
+                None => return Ok(req),
+            };
+            let hash = hash_off_runtime(body).await?;
+            req.config_mut().insert(hash);
             Ok(req)
         })
     }
 }
 
+/// Hash a body on a blocking thread
+///
+/// The body is still polled by the runtime it was created on (eg. a file read by `tokio::fs`). If
+/// there is no runtime to spawn a blocking thread on, the body is hashed in place.
+async fn hash_off_runtime(body: SdkBody) -> Result<PayloadHash, smithy_http::body::Error> {
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => tokio::task::spawn_blocking(move || runtime.block_on(hash_body(body)))
+            .await
+            .map_err(|err| err.into())
+            .and_then(|hash| hash),
+        Err(_) => hash_body(body).await,
+    }
+}
+
+async fn hash_body(mut body: SdkBody) -> Result<PayloadHash, smithy_http::body::Error> {
+    let mut sha256 = Sha256::new();
+    while let Some(data) = body.data().await {
+        sha256.update(&data?);
+    }
+    Ok(PayloadHash::from_hex(hex::encode(sha256.finalize())))
+}
+
 #[cfg(test)]
 mod test {
-    use crate::middleware::{SigV4SigningStage, SigningStageError};
-    use crate::signer::{OperationSigningConfig, SigV4Signer};
-    use aws_auth::CredentialsProvider;
+    use crate::middleware::{
+        signing_config, PayloadHashStage, SigV4SigningStage, SigningStageError,
+    };
+    use crate::signer::{
+        HttpSignatureType, OperationSigningConfig, PayloadHash, SigV4Signer, SigningAlgorithm,
+    };
+    use aws_auth::{
+        AsyncProvideCredentials, BoxFuture, Credentials, CredentialsError, CredentialsProvider,
+    };
     use aws_endpoint::partition::endpoint::{Protocol, SignatureVersion};
     use aws_endpoint::{set_endpoint_resolver, AwsEndpointStage};
-    use aws_types::region::Region;
+    use aws_types::clock_skew::ClockSkew;
+    use aws_types::region::{Region, SigningRegion};
     use aws_types::SigningService;
     use http::header::AUTHORIZATION;
+    use smithy_eventstream::frame::DeferredSigner;
     use smithy_http::body::SdkBody;
-    use smithy_http::middleware::MapRequest;
+    use smithy_http::byte_stream::ByteStream;
+    use smithy_http::middleware::{AsyncMapRequest, MapRequest};
     use smithy_http::operation;
+    use smithy_http::property_bag::PropertyBag;
     use std::convert::Infallible;
+    use std::io::Write;
     use std::sync::Arc;
-    use std::time::{Duration, UNIX_EPOCH};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    #[tokio::test]
+    async fn event_streams_require_sigv4() {
+        let req = http::Request::builder()
+            .uri("https://transcribestreaming.us-east-1.amazonaws.com/stream-transcription")
+            .body(SdkBody::from(""))
+            .unwrap();
+        let (_signer, sender) = DeferredSigner::new();
+        let req = operation::Request::new(req)
+            .augment(|req, conf| {
+                let mut config = OperationSigningConfig::default_config();
+                config.algorithm = SigningAlgorithm::SigV4a;
+                conf.insert(config);
+                conf.insert(SigningRegion::from_static("*"));
+                conf.insert(SigningService::from_static("transcribe"));
+                let cred_provider: CredentialsProvider =
+                    Arc::new(aws_auth::Credentials::from_keys("AKIAfoo", "bar", None));
+                conf.insert(cred_provider);
+                conf.insert(sender);
+                Result::<_, Infallible>::Ok(req)
+            })
+            .expect("succeeds");
+        let signer = SigV4SigningStage::new(SigV4Signer::new());
+        assert!(matches!(
+            signer.apply(req).await,
+            Err(SigningStageError::InvalidBodyType)
+        ));
+    }
 
     // check that the endpoint middleware followed by signing middleware produce the expected result
-    #[test]
-    fn endpoint_plus_signer() {
+    #[tokio::test]
+    async fn endpoint_plus_signer() {
         let provider = Arc::new(aws_endpoint::partition::endpoint::Metadata {
             uri_template: "kinesis.{region}.amazonaws.com",
             protocol: Protocol::Https,
@@ -147,6 +351,7 @@ mod test {
         errs.push(
             signer
                 .apply(req.try_clone().expect("can clone"))
+                .await
                 .expect_err("no signing config"),
         );
         let mut config = OperationSigningConfig::default_config();
@@ -155,12 +360,13 @@ mod test {
         errs.push(
             signer
                 .apply(req.try_clone().expect("can clone"))
+                .await
                 .expect_err("no cred provider"),
         );
         let cred_provider: CredentialsProvider =
             Arc::new(aws_auth::Credentials::from_keys("AKIAfoo", "bar", None));
         req.config_mut().insert(cred_provider);
-        let req = signer.apply(req).expect("signing succeeded");
+        let req = signer.apply(req).await.expect("signing succeeded");
         // make sure we got the correct error types in any order
         assert!(errs.iter().all(|el| matches!(
             el,
@@ -180,4 +386,184 @@ mod test {
             .expect("auth header must be present");
         assert_eq!(auth_header, "AWS4-HMAC-SHA256 Credential=AKIAfoo/20210120/us-east-1/kinesis/aws4_request, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature=af71a409f0229dfd6e88409cd1b11f5c2803868d6869888e53bbf9ee12a97ea0");
     }
+
+    #[tokio::test]
+    async fn sigv4a_signer() {
+        let req = http::Request::builder()
+            .uri("https://mfzwi23gnjvgw.mrap.accesspoint.s3-global.amazonaws.com/key")
+            .body(SdkBody::from(""))
+            .unwrap();
+        let req = operation::Request::new(req)
+            .augment(|req, conf| {
+                let mut config = OperationSigningConfig::default_config();
+                config.algorithm = SigningAlgorithm::SigV4a;
+                config.signing_options.content_sha256_header = true;
+                conf.insert(config);
+                conf.insert(SigningRegion::from_static("*"));
+                conf.insert(SigningService::from_static("s3"));
+                conf.insert(UNIX_EPOCH + Duration::new(1611160427, 0));
+                let cred_provider: CredentialsProvider =
+                    Arc::new(aws_auth::Credentials::from_keys("AKIAfoo", "bar", None));
+                conf.insert(cred_provider);
+                Result::<_, Infallible>::Ok(req)
+            })
+            .expect("succeeds");
+        let signer = SigV4SigningStage::new(SigV4Signer::new());
+        let (req, _) = signer
+            .apply(req)
+            .await
+            .expect("signing succeeded")
+            .into_parts();
+        assert_eq!(req.headers()["x-amz-region-set"], "*");
+        assert_eq!(
+            req.headers()["x-amz-content-sha256"],
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        let auth_header = req.headers()[AUTHORIZATION].to_str().unwrap();
+        assert!(
+            auth_header.starts_with("AWS4-ECDSA-P256-SHA256 Credential=AKIAfoo/20210120/s3/aws4_request, SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-region-set, Signature="),
+            "{}",
+            auth_header
+        );
+    }
+
+    #[tokio::test]
+    async fn async_provider_takes_precedence() {
+        struct AsyncProvider;
+        impl AsyncProvideCredentials for AsyncProvider {
+            fn provide_credentials<'a>(
+                &'a self,
+            ) -> BoxFuture<'a, Result<Credentials, CredentialsError>> {
+                Box::pin(async move {
+                    tokio::task::yield_now().await;
+                    Ok(Credentials::from_keys("AKIAasync", "secret", None))
+                })
+            }
+        }
+
+        let req = http::Request::builder()
+            .uri("https://kinesis.us-east-1.amazonaws.com")
+            .body(SdkBody::from(""))
+            .unwrap();
+        let req = operation::Request::new(req)
+            .augment(|req, conf| {
+                conf.insert(SigningRegion::from(Region::new("us-east-1")));
+                conf.insert(UNIX_EPOCH + Duration::new(1611160427, 0));
+                conf.insert(SigningService::from_static("kinesis"));
+                conf.insert(OperationSigningConfig::default_config());
+                aws_auth::set_provider(
+                    conf,
+                    Arc::new(Credentials::from_keys("AKIAsync", "secret", None)),
+                );
+                aws_auth::set_async_provider(conf, Arc::new(AsyncProvider));
+                Result::<_, Infallible>::Ok(req)
+            })
+            .expect("succeeds");
+        let signer = SigV4SigningStage::new(SigV4Signer::new());
+        let (req, _) = signer
+            .apply(req)
+            .await
+            .expect("signing succeeded")
+            .into_parts();
+        let auth_header = req
+            .headers()
+            .get(AUTHORIZATION)
+            .expect("auth header must be present")
+            .to_str()
+            .unwrap();
+        assert!(auth_header.contains("Credential=AKIAasync/"));
+    }
+
+    #[tokio::test]
+    async fn hash_replayable_body() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        for i in 0..10000 {
+            writeln!(file, "line {}", i).unwrap();
+        }
+        let contents = std::fs::read(file.path()).unwrap();
+        let body = ByteStream::from_path(file.path()).await.unwrap();
+        let mut req = operation::Request::new(http::Request::new(body.into_inner()));
+        req.config_mut()
+            .insert(OperationSigningConfig::default_config());
+        let req = PayloadHashStage::new()
+            .apply(req)
+            .await
+            .expect("body can be read");
+        assert_eq!(
+            req.config().get::<PayloadHash>(),
+            Some(&PayloadHash::from_bytes(&contents))
+        );
+        // the body itself was not consumed
+        let (req, _) = req.into_parts();
+        let body = ByteStream::new(req.into_body()).collect().await.unwrap();
+        assert_eq!(body.into_bytes(), contents);
+    }
+
+    #[test]
+    fn clock_skew_corrects_signing_time() {
+        let mut config = PropertyBag::new();
+        config.insert(OperationSigningConfig::default_config());
+        config.insert(SigningRegion::from(Region::new("us-east-1")));
+        config.insert(SigningService::from_static("kinesis"));
+        let clock_skew = ClockSkew::new();
+        let now = SystemTime::now();
+        clock_skew.update(now + Duration::from_secs(3600), now);
+        config.insert(clock_skew);
+        let (_, request_config) = signing_config(&config).expect("valid config");
+        let offset = request_config
+            .request_ts
+            .duration_since(now)
+            .expect("the signing time is ahead");
+        assert!(offset >= Duration::from_secs(3600), "{:?}", offset);
+        assert!(offset < Duration::from_secs(3660), "{:?}", offset);
+
+        // an explicit signing time is used as is
+        let time = UNIX_EPOCH + Duration::from_secs(1611160427);
+        config.insert(time);
+        let (_, request_config) = signing_config(&config).expect("valid config");
+        assert_eq!(request_config.request_ts, time);
+    }
+
+    #[tokio::test]
+    async fn hash_only_when_the_payload_would_be_unsigned() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "hello").unwrap();
+        let s3 = {
+            let mut config = OperationSigningConfig::default_config();
+            config.signing_options.content_sha256_header = true;
+            config
+        };
+        let presigned = {
+            let mut config = OperationSigningConfig::default_config();
+            config.signature_type = HttpSignatureType::HttpRequestQueryParams;
+            config
+        };
+        for config in &[None, Some(s3), Some(presigned)] {
+            let body = ByteStream::from_path(file.path()).await.unwrap();
+            let mut req = operation::Request::new(http::Request::new(body.into_inner()));
+            if let Some(config) = config {
+                req.config_mut().insert(config.clone());
+            }
+            let req = PayloadHashStage::new().apply(req).await.unwrap();
+            assert!(req.config().get::<PayloadHash>().is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn skip_bodies_that_cant_be_hashed() {
+        // in-memory bodies are hashed by the signer
+        let mut req = operation::Request::new(http::Request::new(SdkBody::from("hello")));
+        req.config_mut()
+            .insert(OperationSigningConfig::default_config());
+        let req = PayloadHashStage::new().apply(req).await.unwrap();
+        assert!(req.config().get::<PayloadHash>().is_none());
+
+        // streaming bodies that can't be replayed are not hashed
+        let (_sender, body) = hyper::Body::channel();
+        let mut req = operation::Request::new(http::Request::new(SdkBody::from(body)));
+        req.config_mut()
+            .insert(OperationSigningConfig::default_config());
+        let req = PayloadHashStage::new().apply(req).await.unwrap();
+        assert!(req.config().get::<PayloadHash>().is_none());
+    }
 }
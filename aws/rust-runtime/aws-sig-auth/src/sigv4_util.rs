@@ -0,0 +1,196 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! SigV4 primitives shared by the signing implementations of this crate
+
+use crate::query_signing;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac, NewMac};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+/// Failure to sign a request with the SigV4 or SigV4A signers of this crate
+#[derive(Debug)]
+pub(crate) enum SignatureError {
+    ExpirationTooLong(Duration),
+    MissingHost,
+    InvalidHeaderValue(String),
+    InvalidUri(http::Error),
+    InvalidSigningKey,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::ExpirationTooLong(expires_in) => write!(
+                f,
+                "presigned requests can be valid for at most {} seconds, got {} seconds",
+                query_signing::MAX_EXPIRATION.as_secs(),
+                expires_in.as_secs()
+            ),
+            SignatureError::MissingHost => {
+                write!(f, "the request must have a host to be signed")
+            }
+            SignatureError::InvalidHeaderValue(header) => {
+                write!(f, "the value of the `{}` header is not valid UTF-8", header)
+            }
+            SignatureError::InvalidUri(err) => {
+                write!(f, "failed to construct the signed URI: {}", err)
+            }
+            SignatureError::InvalidSigningKey => write!(
+                f,
+                "failed to derive a SigV4A signing key from the credentials"
+            ),
+        }
+    }
+}
+
+impl Error for SignatureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SignatureError::InvalidUri(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Format a time as a SigV4 timestamp, eg. `20130524T000000Z`
+pub(crate) fn format_date_time(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+/// The credential scope of a signature, eg. `20130524/us-east-1/s3/aws4_request`
+pub(crate) fn credential_scope(time: SystemTime, region: &str, service: &str) -> String {
+    format!("{}/{}/{}/aws4_request", format_date(time), region, service)
+}
+
+/// Hex-encoded SHA-256 of `data`
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Derive the key used to sign requests for a given day, region and service
+pub(crate) fn signing_key(
+    secret_key: &str,
+    time: SystemTime,
+    region: &str,
+    service: &str,
+) -> Vec<u8> {
+    let key = hmac_sha256(
+        format!("AWS4{}", secret_key).as_bytes(),
+        format_date(time).as_bytes(),
+    );
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, service.as_bytes());
+    hmac_sha256(&key, b"aws4_request")
+}
+
+/// Remove empty, `.` and `..` segments from a URI path
+///
+/// The path is borrowed if it is already normalized. A trailing slash is preserved.
+pub(crate) fn normalize_uri_path(path: &str) -> Cow<'_, str> {
+    let mut segments: Vec<&str> = vec![];
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    let mut normalized = format!("/{}", segments.join("/"));
+    let trailing_slash = path.ends_with('/') || path.ends_with("/.") || path.ends_with("/..");
+    if trailing_slash && !segments.is_empty() {
+        normalized.push('/');
+    }
+    if normalized == path {
+        Cow::Borrowed(path)
+    } else {
+        Cow::Owned(normalized)
+    }
+}
+
+fn format_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).format("%Y%m%d").to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sigv4_util::{
+        credential_scope, format_date_time, normalize_uri_path, sha256_hex, signing_key,
+    };
+    use std::borrow::Cow;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn format_time() {
+        let time = UNIX_EPOCH + Duration::from_secs(1440938160);
+        assert_eq!(format_date_time(time), "20150830T123600Z");
+        assert_eq!(
+            credential_scope(time, "us-east-1", "iam"),
+            "20150830/us-east-1/iam/aws4_request"
+        );
+    }
+
+    // Example from https://docs.aws.amazon.com/general/latest/gr/sigv4-calculate-signature.html
+    #[test]
+    fn derive_signing_key() {
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            UNIX_EPOCH + Duration::from_secs(1440938160),
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex::encode(key),
+            "c4afb1cc5771d871763a393e44b703571b55cc28424d1a5e86da6ed3c154a4b9"
+        );
+    }
+
+    #[test]
+    fn empty_payload_hash() {
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    // Paths of the normalize-path tests of the SigV4 test suite
+    #[test]
+    fn normalize_paths() {
+        let cases = [
+            ("/example/..", "/"),
+            ("/example1/example2/../..", "/"),
+            ("//", "/"),
+            ("/./", "/"),
+            ("/./example", "/example"),
+            ("//example//", "/example/"),
+            ("/example/.", "/example/"),
+            ("/a/b/../c", "/a/c"),
+            ("/..", "/"),
+            ("", "/"),
+        ];
+        for (path, expected) in cases.iter() {
+            assert_eq!(normalize_uri_path(path), *expected, "{}", path);
+        }
+        assert!(matches!(normalize_uri_path("/"), Cow::Borrowed(_)));
+        assert!(matches!(
+            normalize_uri_path("/example/path/"),
+            Cow::Borrowed(_)
+        ));
+    }
+}
@@ -0,0 +1,292 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! SigV4A: SigV4 with asymmetric ECDSA P-256 signatures
+//!
+//! Instead of an HMAC key scoped to a single region, the signing key is an ECDSA key derived from
+//! the credentials. A signature is valid for a set of regions (sent as `x-amz-region-set`), which
+//! is required eg. by S3 Multi-Region Access Points. The canonical request is the same as SigV4's.
+
+use crate::query_signing::{
+    canonical_headers, canonical_query_string, canonical_request, query_params, signed_headers,
+    string_to_sign,
+};
+use crate::sigv4_util::{format_date_time, hmac_sha256, SignatureError};
+use chrono::{DateTime, Utc};
+use http::header::{HeaderName, AUTHORIZATION};
+use http::HeaderValue;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use p256::elliptic_curve::ff::PrimeField;
+use p256::{FieldBytes, Scalar};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::time::SystemTime;
+
+pub(crate) const ALGORITHM: &str = "AWS4-ECDSA-P256-SHA256";
+
+/// The credential scope of a SigV4A signature, eg. `20150830/service/aws4_request`
+///
+/// Unlike SigV4, the scope has no region: the regions are set by the region set instead.
+pub(crate) fn credential_scope(time: SystemTime, service: &str) -> String {
+    format!(
+        "{}/{}/aws4_request",
+        DateTime::<Utc>::from(time).format("%Y%m%d"),
+        service
+    )
+}
+
+/// Derive the ECDSA P-256 key of a pair of credentials
+///
+/// The private key is derived with the NIST SP 800-108 KDF in counter mode, using HMAC-SHA256
+/// keyed with `AWS4A` + the secret key. A candidate that falls outside of the range of valid
+/// P-256 keys is discarded and the next counter value is tried.
+pub(crate) fn signing_key(access_key: &str, secret_key: &str) -> Option<SigningKey> {
+    let input_key = format!("AWS4A{}", secret_key);
+    for counter in 1..=254u8 {
+        let mut fixed_input = vec![0, 0, 0, 1];
+        fixed_input.extend_from_slice(ALGORITHM.as_bytes());
+        fixed_input.push(0);
+        fixed_input.extend_from_slice(access_key.as_bytes());
+        fixed_input.push(counter);
+        // the length of the derived key in bits
+        fixed_input.extend_from_slice(&[0, 0, 1, 0]);
+        let candidate =
+            <[u8; 32]>::try_from(hmac_sha256(input_key.as_bytes(), &fixed_input).as_slice())
+                .expect("HMAC-SHA256 produces 32 bytes");
+        // the candidate must be at most N - 2 so that the private key (candidate + 1) is in [1, N)
+        let candidate: Option<Scalar> = Scalar::from_repr(FieldBytes::from(candidate)).into();
+        if let Some(candidate) = candidate {
+            if let Ok(key) = SigningKey::from_bytes(&(candidate + Scalar::ONE).to_bytes()) {
+                return Some(key);
+            }
+        }
+    }
+    None
+}
+
+/// Hex-encoded DER ECDSA signature of the SHA-256 of `string_to_sign`
+pub(crate) fn sign(key: &SigningKey, string_to_sign: &str) -> String {
+    let signature: Signature = key.sign(string_to_sign.as_bytes());
+    hex::encode(signature.to_der().as_bytes())
+}
+
+/// Parameters of a SigV4A signature in the headers of a request
+pub(crate) struct HeaderSigningParams<'a> {
+    pub(crate) access_key: &'a str,
+    pub(crate) secret_key: &'a str,
+    pub(crate) security_token: Option<&'a str>,
+    /// Add the security token after signing instead of signing it
+    pub(crate) omit_session_token: bool,
+    /// The regions the signature is valid in, eg. `us-east-1,us-west-2` or `*`
+    pub(crate) region_set: &'a str,
+    pub(crate) service: &'a str,
+    pub(crate) time: SystemTime,
+    pub(crate) double_uri_encode: bool,
+    pub(crate) normalize_uri_path: bool,
+    /// Set `x-amz-content-sha256` to the payload hash
+    pub(crate) content_sha256_header: bool,
+    /// The hex-encoded SHA-256 of the payload or `UNSIGNED-PAYLOAD`
+    pub(crate) payload_hash: Cow<'a, str>,
+}
+
+/// Sign `request`, adding the signature and the signing parameters to its headers
+///
+/// Returns the hex-encoded signature.
+pub(crate) fn sign_headers<B>(
+    request: &mut http::Request<B>,
+    params: &HeaderSigningParams<'_>,
+) -> Result<String, SignatureError> {
+    let header_value = |name: &'static str, value: &str| {
+        HeaderValue::from_str(value)
+            .map(|value| (HeaderName::from_static(name), value))
+            .map_err(|_| SignatureError::InvalidHeaderValue(name.to_string()))
+    };
+    let amz_date = format_date_time(params.time);
+    let scope = credential_scope(params.time, params.service);
+    let mut signed = vec![
+        header_value("x-amz-date", &amz_date)?,
+        header_value("x-amz-region-set", params.region_set)?,
+    ];
+    if params.content_sha256_header {
+        signed.push(header_value("x-amz-content-sha256", &params.payload_hash)?);
+    }
+    let security_token = match params.security_token {
+        Some(token) => Some(header_value("x-amz-security-token", token)?),
+        None => None,
+    };
+    let mut unsigned = vec![];
+    match security_token {
+        Some(token) if params.omit_session_token => unsigned.push(token),
+        Some(token) => signed.push(token),
+        None => {}
+    }
+    for (name, value) in signed {
+        request.headers_mut().insert(name, value);
+    }
+
+    let headers = canonical_headers(request)?;
+    let signed_headers = signed_headers(&headers);
+    let canonical_request = canonical_request(
+        request,
+        &canonical_query_string(&mut query_params(request.uri())),
+        &headers,
+        params.double_uri_encode,
+        params.normalize_uri_path,
+        &params.payload_hash,
+    );
+    tracing::trace!(canonical_request = %canonical_request, "computed canonical request");
+    let string_to_sign = string_to_sign(ALGORITHM, &amz_date, &scope, &canonical_request);
+    let key = signing_key(params.access_key, params.secret_key)
+        .ok_or(SignatureError::InvalidSigningKey)?;
+    let signature = sign(&key, &string_to_sign);
+    let authorization = format!(
+        "{} Credential={}/{}, SignedHeaders={}, Signature={}",
+        ALGORITHM, params.access_key, scope, signed_headers, signature
+    );
+    let (name, authorization) = header_value(AUTHORIZATION.as_str(), &authorization)?;
+    request.headers_mut().insert(name, authorization);
+    for (name, value) in unsigned {
+        request.headers_mut().insert(name, value);
+    }
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::query_signing::{
+        canonical_headers, canonical_request, signed_headers, UNSIGNED_PAYLOAD,
+    };
+    use crate::sigv4_util::sha256_hex;
+    use crate::sigv4a::{credential_scope, sign_headers, signing_key, HeaderSigningParams};
+    use p256::ecdsa::signature::Verifier;
+    use p256::ecdsa::{Signature, VerifyingKey};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    const ACCESS_KEY: &str = "AKIDEXAMPLE";
+    const SECRET_KEY: &str = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+
+    fn public_key() -> VerifyingKey {
+        signing_key(ACCESS_KEY, SECRET_KEY)
+            .expect("valid key")
+            .verifying_key()
+    }
+
+    // Public key of the credentials of the SigV4A test suite
+    #[test]
+    fn derive_signing_key() {
+        let public_key = public_key().to_encoded_point(false);
+        assert_eq!(
+            hex::encode(public_key.x().unwrap()),
+            "b6618f6a65740a99e650b33b6b4b5bd0d43b176d721a3edfea7e7d2d56d936b1"
+        );
+        assert_eq!(
+            hex::encode(public_key.y().unwrap()),
+            "865ed22a7eadc9c5cb9d2cbaca1b3699139fedc5043dc6661864218330c8e518"
+        );
+    }
+
+    fn params() -> HeaderSigningParams<'static> {
+        HeaderSigningParams {
+            access_key: ACCESS_KEY,
+            secret_key: SECRET_KEY,
+            security_token: None,
+            omit_session_token: false,
+            region_set: "us-east-1",
+            service: "service",
+            // 20150830T123600Z
+            time: UNIX_EPOCH + Duration::from_secs(1440938160),
+            double_uri_encode: true,
+            normalize_uri_path: true,
+            content_sha256_header: false,
+            payload_hash: sha256_hex(b"").into(),
+        }
+    }
+
+    /// Check the signature of a request signed with [`params`]
+    fn verify(request: &http::Request<()>) {
+        let authorization = request.headers()["authorization"].to_str().unwrap();
+        let (prefix, signature) = authorization.split_at(authorization.find("Signature=").unwrap());
+        let signature =
+            Signature::from_der(&hex::decode(signature.trim_start_matches("Signature=")).unwrap())
+                .expect("DER signature");
+        let headers = canonical_headers(request).unwrap();
+        assert_eq!(
+            prefix,
+            format!(
+                "AWS4-ECDSA-P256-SHA256 Credential=AKIDEXAMPLE/20150830/service/aws4_request, SignedHeaders={}, ",
+                signed_headers(&headers)
+            )
+        );
+        let canonical_request =
+            canonical_request(request, "", &headers, true, true, &params().payload_hash);
+        let string_to_sign = format!(
+            "AWS4-ECDSA-P256-SHA256\n20150830T123600Z\n20150830/service/aws4_request\n{}",
+            sha256_hex(canonical_request.as_bytes())
+        );
+        public_key()
+            .verify(string_to_sign.as_bytes(), &signature)
+            .expect("valid signature");
+    }
+
+    // get-vanilla of the SigV4A test suite
+    #[test]
+    fn get_vanilla() {
+        let mut request = http::Request::builder()
+            .uri("https://example.amazonaws.com/")
+            .body(())
+            .unwrap();
+        sign_headers(&mut request, &params()).expect("valid request");
+        assert_eq!(request.headers()["x-amz-date"], "20150830T123600Z");
+        assert_eq!(request.headers()["x-amz-region-set"], "us-east-1");
+        let headers = canonical_headers(&request).unwrap();
+        assert_eq!(
+            canonical_request(&request, "", &headers, true, true, &params().payload_hash),
+            "GET\n/\n\nhost:example.amazonaws.com\nx-amz-date:20150830T123600Z\nx-amz-region-set:us-east-1\n\nhost;x-amz-date;x-amz-region-set\ne3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        verify(&request);
+    }
+
+    #[test]
+    fn session_token() {
+        let mut request = http::Request::builder()
+            .uri("https://example.amazonaws.com/")
+            .body(())
+            .unwrap();
+        let mut params = params();
+        params.security_token = Some("token");
+        params.content_sha256_header = true;
+        params.payload_hash = UNSIGNED_PAYLOAD.into();
+        sign_headers(&mut request, &params).expect("valid request");
+        assert_eq!(request.headers()["x-amz-security-token"], "token");
+        assert_eq!(request.headers()["x-amz-content-sha256"], UNSIGNED_PAYLOAD);
+        assert!(request.headers()["authorization"]
+            .to_str()
+            .unwrap()
+            .contains(
+                "SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-region-set;x-amz-security-token,"
+            ));
+
+        let mut request = http::Request::builder()
+            .uri("https://example.amazonaws.com/")
+            .body(())
+            .unwrap();
+        params.omit_session_token = true;
+        sign_headers(&mut request, &params).expect("valid request");
+        assert_eq!(request.headers()["x-amz-security-token"], "token");
+        assert!(request.headers()["authorization"]
+            .to_str()
+            .unwrap()
+            .contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-region-set,"));
+    }
+
+    #[test]
+    fn scope_has_no_region() {
+        assert_eq!(
+            credential_scope(UNIX_EPOCH + Duration::from_secs(1440938160), "s3"),
+            "20150830/s3/aws4_request"
+        );
+    }
+}
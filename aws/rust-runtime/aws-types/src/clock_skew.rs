@@ -0,0 +1,110 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Clock skew correction
+//!
+//! AWS rejects requests signed with a timestamp that is too far from the time of the service
+//! (`RequestTimeTooSkewed`). If the clock of the host drifts, the offset between the local clock
+//! and the clock of the service can be learned from the `Date` header of responses and applied to
+//! the timestamps of later requests.
+
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// Offsets below this are ignored: they are within the precision of the `Date` header and the
+/// latency of the response
+const TOLERANCE: Duration = Duration::from_secs(60);
+
+/// The offset between the local clock and the clock of a service, shared between requests
+///
+/// Cloning a `ClockSkew` shares the offset: an offset learned from a response applies to every
+/// clone.
+#[derive(Clone, Debug, Default)]
+pub struct ClockSkew {
+    /// Milliseconds to add to the local time to get the time of the service
+    offset_millis: Arc<AtomicI64>,
+}
+
+impl ClockSkew {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The current time of the service, according to the local clock corrected by the offset
+    pub fn now(&self) -> SystemTime {
+        self.correct(SystemTime::now())
+    }
+
+    /// Correct a local time by the offset
+    pub fn correct(&self, local_time: SystemTime) -> SystemTime {
+        let offset = self.offset_millis.load(Ordering::Relaxed);
+        if offset >= 0 {
+            local_time + Duration::from_millis(offset as u64)
+        } else {
+            local_time - Duration::from_millis(offset.unsigned_abs())
+        }
+    }
+
+    /// Update the offset from the time of a response of the service
+    ///
+    /// `server_time` is the time of the service (eg. from the `Date` header) and `local_time`
+    /// the local time the response was received at. The offset is only updated if the corrected
+    /// local time is off by more than a minute; `true` is returned if it was updated.
+    pub fn update(&self, server_time: SystemTime, local_time: SystemTime) -> bool {
+        let offset = match server_time.duration_since(local_time) {
+            Ok(ahead) => ahead.as_millis() as i64,
+            Err(behind) => -(behind.duration().as_millis() as i64),
+        };
+        let current = self.offset_millis.load(Ordering::Relaxed);
+        if (offset - current).unsigned_abs() < TOLERANCE.as_millis() as u64 {
+            return false;
+        }
+        self.offset_millis.store(offset, Ordering::Relaxed);
+        true
+    }
+}
+
+/// Marker set in the extensions of a response after which the clock skew was corrected
+///
+/// A request that failed because of clock skew is worth retrying once the skew is corrected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClockSkewCorrected;
+
+#[cfg(test)]
+mod test {
+    use crate::clock_skew::ClockSkew;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn no_offset_by_default() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        assert_eq!(ClockSkew::new().correct(time), time);
+    }
+
+    #[test]
+    fn offset_is_shared() {
+        let skew = ClockSkew::new();
+        let local = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        assert!(skew.clone().update(local + Duration::from_secs(900), local));
+        assert_eq!(skew.correct(local), local + Duration::from_secs(900));
+
+        assert!(skew.update(local - Duration::from_secs(600), local));
+        assert_eq!(skew.correct(local), local - Duration::from_secs(600));
+    }
+
+    #[test]
+    fn small_offsets_are_ignored() {
+        let skew = ClockSkew::new();
+        let local = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        assert!(!skew.update(local + Duration::from_secs(2), local));
+        assert_eq!(skew.correct(local), local);
+
+        assert!(skew.update(local + Duration::from_secs(900), local));
+        // the corrected clock is accurate enough
+        assert!(!skew.update(local + Duration::from_secs(905), local));
+        assert_eq!(skew.correct(local), local + Duration::from_secs(900));
+    }
+}
@@ -4,6 +4,9 @@
  */
 
 pub mod build_metadata;
+pub mod clock_skew;
+pub mod os_shim;
+pub mod profile;
 pub mod region;
 
 use std::borrow::Cow;
@@ -0,0 +1,176 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Abstractions for testable access to the operating system
+//!
+//! Providers that read from the environment or the filesystem (eg. profile files) accept an [`Env`]
+//! and an [`Fs`] instead of calling `std::env` & `std::fs` directly. In production, these delegate
+//! to the real OS. In tests, they can be loaded from a map.
+
+use std::collections::HashMap;
+use std::env::VarError;
+use std::ffi::OsString;
+use std::fmt::{self, Debug, Formatter};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::Arc;
+
+/// File system abstraction
+///
+/// Simple abstraction enabling in-memory mocking of the file system
+///
+/// # Examples
+/// Construct a file system which delegates to `std::fs`:
+/// ```rust
+/// let fs = aws_types::os_shim::Fs::real();
+/// ```
+///
+/// Construct an in-memory file system for testing:
+/// ```rust
+/// use std::collections::HashMap;
+/// let mut files = HashMap::new();
+/// files.insert("/home/.aws/config".to_string(), "[default]\nregion = us-east-1".into());
+/// let fs = aws_types::os_shim::Fs::from_map(files);
+/// ```
+#[derive(Clone)]
+pub struct Fs(FsInner);
+
+#[derive(Clone)]
+enum FsInner {
+    Real,
+    Fake(Arc<HashMap<OsString, Vec<u8>>>),
+}
+
+impl Debug for Fs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            FsInner::Real => write!(f, "Fs::Real"),
+            FsInner::Fake(files) => f
+                .debug_tuple("Fs::Fake")
+                .field(&files.keys().collect::<Vec<_>>())
+                .finish(),
+        }
+    }
+}
+
+impl Default for Fs {
+    fn default() -> Self {
+        Fs::real()
+    }
+}
+
+impl Fs {
+    pub fn real() -> Self {
+        Fs(FsInner::Real)
+    }
+
+    pub fn from_map(data: HashMap<String, Vec<u8>>) -> Self {
+        let fs = data.into_iter().map(|(k, v)| (k.into(), v)).collect();
+        Fs(FsInner::Fake(Arc::new(fs)))
+    }
+
+    /// Read the entire contents of a file
+    ///
+    /// **Note**: This function is currently `sync` because it is only used during credential
+    /// provider loading which is also synchronous.
+    pub fn read_to_end(&self, path: impl AsRef<Path>) -> std::io::Result<Vec<u8>> {
+        let path = path.as_ref();
+        match &self.0 {
+            FsInner::Real => std::fs::read(path),
+            FsInner::Fake(fs) => fs
+                .get(path.as_os_str())
+                .cloned()
+                .ok_or_else(|| std::io::Error::from(ErrorKind::NotFound)),
+        }
+    }
+}
+
+/// Environment variable abstraction
+///
+/// Environment variables are global to a process, and, as such, are difficult to test with a multi-
+/// threaded test runner like Rust's. This enables loading environment variables either from the
+/// actual process environment ([`std::env::var`](std::env::var)) or from a hash map.
+///
+/// # Examples
+/// ```rust
+/// let env = aws_types::os_shim::Env::from_slice(&[("AWS_PROFILE", "dev")]);
+/// assert_eq!(env.get("AWS_PROFILE").unwrap(), "dev");
+/// ```
+#[derive(Clone)]
+pub struct Env(EnvInner);
+
+#[derive(Clone)]
+enum EnvInner {
+    Real,
+    Fake(Arc<HashMap<String, String>>),
+}
+
+impl Debug for Env {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            EnvInner::Real => write!(f, "Env::Real"),
+            EnvInner::Fake(env) => f.debug_tuple("Env::Fake").field(env).finish(),
+        }
+    }
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Env::real()
+    }
+}
+
+impl Env {
+    pub fn real() -> Self {
+        Env(EnvInner::Real)
+    }
+
+    pub fn from_slice(vars: &[(&str, &str)]) -> Self {
+        let map: HashMap<_, _> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Self::from(map)
+    }
+
+    pub fn get(&self, k: &str) -> Result<String, VarError> {
+        match &self.0 {
+            EnvInner::Real => std::env::var(k),
+            EnvInner::Fake(map) => map.get(k).cloned().ok_or(VarError::NotPresent),
+        }
+    }
+}
+
+impl From<HashMap<String, String>> for Env {
+    fn from(map: HashMap<String, String>) -> Self {
+        Env(EnvInner::Fake(Arc::new(map)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::os_shim::{Env, Fs};
+    use std::collections::HashMap;
+    use std::env::VarError;
+
+    #[test]
+    fn fake_fs() {
+        let mut files = HashMap::new();
+        files.insert("/some/file".to_string(), b"hello".to_vec());
+        let fs = Fs::from_map(files);
+        assert_eq!(fs.read_to_end("/some/file").unwrap(), b"hello");
+        assert_eq!(
+            fs.read_to_end("/other/file").unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn fake_env() {
+        let env = Env::from_slice(&[("FOO", "BAR")]);
+        assert_eq!(env.get("FOO").unwrap(), "BAR");
+        assert_eq!(env.get("OTHER"), Err(VarError::NotPresent));
+    }
+}
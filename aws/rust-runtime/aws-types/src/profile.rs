@@ -0,0 +1,526 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Shared configuration & credentials files ("profile files")
+//!
+//! Profile files are INI-style files shared between the AWS SDKs and the AWS CLI. Two files are
+//! loaded and merged:
+//! - The config file (`~/.aws/config` unless overridden by `AWS_CONFIG_FILE`). Sections are named
+//!   `[profile name]`, except for the default profile which may also be named `[default]`.
+//! - The credentials file (`~/.aws/credentials` unless overridden by `AWS_SHARED_CREDENTIALS_FILE`).
+//!   Sections are named `[name]`. When a property is defined in both files, the credentials file wins.
+//!
+//! The active profile is selected with `AWS_PROFILE` and falls back to `default`.
+//!
+//! Like the AWS CLI, sections that don't define a valid profile (eg. a name containing whitespace,
+//! or a `[profile name]` section in the credentials file) are skipped with a warning rather than
+//! failing to load the files.
+
+use crate::os_shim::{Env, Fs};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_PROFILE: &str = "default";
+const PROFILE_PREFIX: &str = "profile";
+
+/// A set of profiles loaded from the config & credentials files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileSet {
+    profiles: HashMap<String, Profile>,
+    selected_profile: Cow<'static, str>,
+}
+
+impl ProfileSet {
+    /// Retrieve a key from the selected profile
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.profiles
+            .get(self.selected_profile.as_ref())
+            .and_then(|profile| profile.get(key))
+    }
+
+    /// Retrieve a profile by name
+    pub fn get_profile(&self, profile_name: &str) -> Option<&Profile> {
+        self.profiles.get(profile_name)
+    }
+
+    /// Returns the name of the selected profile
+    ///
+    /// The selected profile is set by `AWS_PROFILE`. If it is unset, `default` is used. Note that
+    /// the selected profile may not actually be defined in either file.
+    pub fn selected_profile(&self) -> &str {
+        self.selected_profile.as_ref()
+    }
+
+    /// Returns true if no profiles were loaded
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+    }
+
+    fn parse(
+        config: Option<Source>,
+        credentials: Option<Source>,
+        selected_profile: Cow<'static, str>,
+    ) -> Result<Self, ProfileError> {
+        let mut profiles = HashMap::new();
+        if let Some(config) = config {
+            parse_file(&config, FileKind::Config, &mut profiles)?;
+        }
+        if let Some(credentials) = credentials {
+            parse_file(&credentials, FileKind::Credentials, &mut profiles)?;
+        }
+        Ok(ProfileSet {
+            profiles,
+            selected_profile,
+        })
+    }
+}
+
+/// An individual named profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    name: String,
+    properties: HashMap<String, String>,
+}
+
+impl Profile {
+    pub fn new(name: impl Into<String>, properties: HashMap<String, String>) -> Self {
+        Self {
+            name: name.into(),
+            properties,
+        }
+    }
+
+    /// The name of this profile
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Retrieve a property from this profile
+    ///
+    /// Properties with sub-properties (eg. `s3 =` followed by indented lines) are returned with
+    /// each sub-property on its own line.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).map(|v| v.as_str())
+    }
+}
+
+/// Failed to load the profile files
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ProfileError {
+    /// A profile file existed but could not be read
+    CouldNotReadFile {
+        path: PathBuf,
+        cause: std::io::Error,
+    },
+
+    /// A profile file was not valid
+    InvalidSyntax {
+        path: PathBuf,
+        line_number: usize,
+        message: Cow<'static, str>,
+    },
+}
+
+impl Display for ProfileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileError::CouldNotReadFile { path, cause } => {
+                write!(
+                    f,
+                    "could not read profile file {}: {}",
+                    path.display(),
+                    cause
+                )
+            }
+            ProfileError::InvalidSyntax {
+                path,
+                line_number,
+                message,
+            } => write!(
+                f,
+                "error parsing {} on line {}: {}",
+                path.display(),
+                line_number,
+                message
+            ),
+        }
+    }
+}
+
+impl Error for ProfileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ProfileError::CouldNotReadFile { cause, .. } => Some(cause),
+            _ => None,
+        }
+    }
+}
+
+/// Load the profile files and merge them into a [`ProfileSet`]
+///
+/// Missing files are not an error: they are treated as empty. To test code that depends on
+/// profiles, construct `fs` & `env` with [`Fs::from_map`] & [`Env::from_slice`].
+pub fn load(fs: &Fs, env: &Env) -> Result<ProfileSet, ProfileError> {
+    let home = home_dir(env);
+    let config = read_file(fs, env, "AWS_CONFIG_FILE", "config", home.as_deref())?;
+    let credentials = read_file(
+        fs,
+        env,
+        "AWS_SHARED_CREDENTIALS_FILE",
+        "credentials",
+        home.as_deref(),
+    )?;
+    let selected_profile = env
+        .get("AWS_PROFILE")
+        .map(Cow::Owned)
+        .unwrap_or(Cow::Borrowed(DEFAULT_PROFILE));
+    ProfileSet::parse(config, credentials, selected_profile)
+}
+
+/// The raw contents of a profile file
+struct Source {
+    path: PathBuf,
+    contents: String,
+}
+
+fn read_file(
+    fs: &Fs,
+    env: &Env,
+    override_var: &str,
+    default_name: &str,
+    home: Option<&str>,
+) -> Result<Option<Source>, ProfileError> {
+    let path = match env.get(override_var) {
+        Ok(path) => expand_home(&path, home),
+        Err(_) => match home {
+            Some(home) => Path::new(home).join(".aws").join(default_name),
+            None => return Ok(None),
+        },
+    };
+    match fs.read_to_end(&path) {
+        Ok(data) => Ok(Some(Source {
+            contents: String::from_utf8_lossy(&data).into_owned(),
+            path,
+        })),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(cause) => Err(ProfileError::CouldNotReadFile { path, cause }),
+    }
+}
+
+fn home_dir(env: &Env) -> Option<String> {
+    if let Ok(home) = env.get("HOME") {
+        return Some(home);
+    }
+    if let Ok(profile) = env.get("USERPROFILE") {
+        return Some(profile);
+    }
+    match (env.get("HOMEDRIVE"), env.get("HOMEPATH")) {
+        (Ok(drive), Ok(path)) => Some(format!("{}{}", drive, path)),
+        _ => None,
+    }
+}
+
+fn expand_home(path: &str, home: Option<&str>) -> PathBuf {
+    match (path.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with(&['/', '\\'][..]) => {
+            Path::new(home).join(rest.trim_start_matches(&['/', '\\'][..]))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum FileKind {
+    Config,
+    Credentials,
+}
+
+/// Parse a single profile file, merging its contents into `profiles`
+fn parse_file(
+    source: &Source,
+    kind: FileKind,
+    profiles: &mut HashMap<String, Profile>,
+) -> Result<(), ProfileError> {
+    let error = |line_number: usize, message: &'static str| ProfileError::InvalidSyntax {
+        path: source.path.clone(),
+        line_number,
+        message: message.into(),
+    };
+    enum State<'a> {
+        Start,
+        Ignored,
+        InProfile {
+            name: String,
+            last_property: Option<&'a str>,
+        },
+    }
+    let mut state = State::Start;
+    for (idx, line) in source.contents.lines().enumerate() {
+        let line_number = idx + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+            continue;
+        }
+        if trimmed.starts_with('[') {
+            let header = strip_comment(trimmed);
+            let name = header
+                .strip_prefix('[')
+                .and_then(|h| h.strip_suffix(']'))
+                .ok_or_else(|| error(line_number, "profile definition must end with ']'"))?
+                .trim();
+            state = match profile_name(name, kind) {
+                Some(name) if name.is_empty() || name.contains(char::is_whitespace) => {
+                    tracing::warn!(
+                        "ignoring section `[{}]` of {} on line {}: invalid profile name",
+                        header,
+                        source.path.display(),
+                        line_number
+                    );
+                    State::Ignored
+                }
+                Some(name) => {
+                    profiles
+                        .entry(name.to_string())
+                        .or_insert_with(|| Profile::new(name, HashMap::new()));
+                    State::InProfile {
+                        name: name.to_string(),
+                        last_property: None,
+                    }
+                }
+                None => State::Ignored,
+            };
+            continue;
+        }
+        let is_continuation = line.starts_with(char::is_whitespace);
+        match &mut state {
+            State::Start => return Err(error(line_number, "expected a profile definition")),
+            State::Ignored => continue,
+            State::InProfile {
+                name,
+                last_property,
+            } => {
+                let profile = profiles.get_mut(name.as_str()).expect("inserted above");
+                if is_continuation {
+                    let key = last_property
+                        .ok_or_else(|| error(line_number, "expected a property definition"))?;
+                    let value = profile
+                        .properties
+                        .get_mut(key)
+                        .expect("property was inserted");
+                    if !value.is_empty() {
+                        value.push('\n');
+                    }
+                    value.push_str(trimmed);
+                    continue;
+                }
+                let (key, value) = split_property(trimmed).ok_or_else(|| {
+                    error(line_number, "expected an '=' sign defining a property")
+                })?;
+                if key.is_empty() {
+                    return Err(error(line_number, "property did not have a name"));
+                }
+                profile
+                    .properties
+                    .insert(key.to_string(), strip_comment(value).to_string());
+                *last_property = Some(key);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Determine the profile name for a section header
+///
+/// Returns `None` if this section should be ignored.
+fn profile_name(section: &str, kind: FileKind) -> Option<&str> {
+    match kind {
+        FileKind::Credentials => Some(section),
+        FileKind::Config if section == DEFAULT_PROFILE => Some(section),
+        FileKind::Config => section
+            .strip_prefix(PROFILE_PREFIX)
+            .filter(|rest| rest.starts_with(char::is_whitespace))
+            .map(|rest| rest.trim()),
+    }
+}
+
+fn split_property(line: &str) -> Option<(&str, &str)> {
+    let mut parts = line.splitn(2, '=');
+    let key = parts.next()?.trim();
+    let value = parts.next()?.trim();
+    Some((key, value))
+}
+
+/// Strip a trailing comment
+///
+/// Comments after a value must be preceded by whitespace so that values like `abc#123` are
+/// preserved.
+fn strip_comment(value: &str) -> &str {
+    let mut prev_whitespace = false;
+    for (idx, c) in value.char_indices() {
+        if (c == '#' || c == ';') && prev_whitespace {
+            return value[..idx].trim_end();
+        }
+        prev_whitespace = c.is_whitespace();
+    }
+    value
+}
+
+#[cfg(test)]
+mod test {
+    use crate::os_shim::{Env, Fs};
+    use crate::profile::{load, ProfileError};
+    use std::collections::HashMap;
+
+    fn fs(files: &[(&str, &str)]) -> Fs {
+        Fs::from_map(
+            files
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.as_bytes().to_vec()))
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+
+    #[test]
+    fn no_files() {
+        let profiles = load(&fs(&[]), &Env::from_slice(&[("HOME", "/home")])).unwrap();
+        assert!(profiles.is_empty());
+        assert_eq!(profiles.selected_profile(), "default");
+        assert_eq!(profiles.get("region"), None);
+    }
+
+    #[test]
+    fn config_and_credentials_are_merged() {
+        let fs = fs(&[
+            (
+                "/home/.aws/config",
+                "[default]\nregion = us-east-1\n[profile dev]\nregion = us-west-2\naws_access_key_id = from-config",
+            ),
+            (
+                "/home/.aws/credentials",
+                "[dev]\naws_access_key_id = AKID\naws_secret_access_key = secret",
+            ),
+        ]);
+        let env = Env::from_slice(&[("HOME", "/home"), ("AWS_PROFILE", "dev")]);
+        let profiles = load(&fs, &env).unwrap();
+        assert_eq!(profiles.selected_profile(), "dev");
+        assert_eq!(profiles.get("region"), Some("us-west-2"));
+        assert_eq!(profiles.get("aws_access_key_id"), Some("AKID"));
+        assert_eq!(profiles.get("aws_secret_access_key"), Some("secret"));
+        assert_eq!(
+            profiles.get_profile("default").unwrap().get("region"),
+            Some("us-east-1")
+        );
+    }
+
+    #[test]
+    fn file_location_overrides() {
+        let fs = fs(&[
+            ("/custom/config", "[profile other]\nregion = eu-west-1"),
+            ("/home/creds", "[other]\naws_access_key_id = AKID"),
+        ]);
+        let env = Env::from_slice(&[
+            ("HOME", "/home"),
+            ("AWS_CONFIG_FILE", "/custom/config"),
+            ("AWS_SHARED_CREDENTIALS_FILE", "~/creds"),
+            ("AWS_PROFILE", "other"),
+        ]);
+        let profiles = load(&fs, &env).unwrap();
+        assert_eq!(profiles.get("region"), Some("eu-west-1"));
+        assert_eq!(profiles.get("aws_access_key_id"), Some("AKID"));
+    }
+
+    #[test]
+    fn config_sections_without_profile_prefix_are_ignored() {
+        let fs = fs(&[(
+            "/home/.aws/config",
+            "[dev]\nregion = us-east-2\n[sso-session foo]\nsso_region = us-east-1",
+        )]);
+        let profiles = load(&fs, &Env::from_slice(&[("HOME", "/home")])).unwrap();
+        assert!(profiles.is_empty());
+    }
+
+    #[test]
+    fn invalid_profiles_are_skipped() {
+        let fs = fs(&[
+            (
+                "/home/.aws/config",
+                "[profile my profile]\nregion = us-east-2\n[profile dev]\nregion = us-west-2",
+            ),
+            (
+                "/home/.aws/credentials",
+                "[profile dev]\naws_access_key_id = ignored\n[dev]\naws_access_key_id = AKID",
+            ),
+        ]);
+        let env = Env::from_slice(&[("HOME", "/home"), ("AWS_PROFILE", "dev")]);
+        let profiles = load(&fs, &env).unwrap();
+        assert_eq!(profiles.get("region"), Some("us-west-2"));
+        assert_eq!(profiles.get("aws_access_key_id"), Some("AKID"));
+        assert!(profiles.get_profile("my profile").is_none());
+        assert!(profiles.get_profile("profile dev").is_none());
+    }
+
+    #[test]
+    fn comments_and_continuations() {
+        let fs = fs(&[(
+            "/home/.aws/config",
+            r#"
+# a comment
+; another comment
+[default] # trailing comment
+region = us-east-1 ; trailing comment
+password = abc#123
+s3 =
+  max_concurrent_requests = 10
+  addressing_style = path
+"#,
+        )]);
+        let profiles = load(&fs, &Env::from_slice(&[("HOME", "/home")])).unwrap();
+        assert_eq!(profiles.get("region"), Some("us-east-1"));
+        assert_eq!(profiles.get("password"), Some("abc#123"));
+        assert_eq!(
+            profiles.get("s3"),
+            Some("max_concurrent_requests = 10\naddressing_style = path")
+        );
+    }
+
+    #[test]
+    fn invalid_syntax() {
+        let env = Env::from_slice(&[("HOME", "/home")]);
+        let cases = [
+            "region = us-east-1",
+            "[default\nregion = us-east-1",
+            "[default]\nnot a property",
+            "[default]\n= value",
+        ];
+        for case in cases.iter() {
+            let err = load(&fs(&[("/home/.aws/config", case)]), &env)
+                .expect_err("profile should be invalid");
+            assert!(
+                matches!(err, ProfileError::InvalidSyntax { .. }),
+                "{}: {}",
+                case,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn error_includes_location() {
+        let env = Env::from_slice(&[("HOME", "/home")]);
+        let err = load(
+            &fs(&[("/home/.aws/config", "[default]\nregion = x\nbad")]),
+            &env,
+        )
+        .unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "error parsing /home/.aws/config on line 3: expected an '=' sign defining a property"
+        );
+    }
+}
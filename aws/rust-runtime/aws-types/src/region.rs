@@ -3,8 +3,11 @@
  * SPDX-License-Identifier: Apache-2.0.
  */
 
+use crate::os_shim::{Env, Fs};
+use crate::profile;
 use std::borrow::Cow;
 use std::env::VarError;
+use std::fmt::{self, Debug, Formatter};
 
 /// The region to send requests to.
 ///
@@ -53,8 +56,78 @@ impl<'a> ProvideRegion for &'a Region {
     }
 }
 
+/// The default region provider chain
+///
+/// The region is loaded from, in order:
+/// 1. Environment variables: [`EnvironmentProvider`](EnvironmentProvider)
+/// 2. The `region` of the active profile in the shared config files:
+///    [`ProfileFileRegionProvider`](ProfileFileRegionProvider)
+///
+/// To also fall back to the region of the EC2 instance, add `aws_auth::imds::ImdsRegionProvider`
+/// to [`RegionProviderChain::default_provider`](RegionProviderChain::default_provider) with
+/// [`or_else`](RegionProviderChain::or_else).
 pub fn default_provider() -> impl ProvideRegion {
-    EnvironmentProvider::new()
+    RegionProviderChain::default_provider()
+}
+
+/// Region provider that checks a series of inner providers
+///
+/// Each provider is checked in turn and the first region returned is used.
+///
+/// ## Example
+/// ```rust
+/// use aws_types::region::{EnvironmentProvider, ProvideRegion, Region, RegionProviderChain};
+/// let provider = RegionProviderChain::first_try(EnvironmentProvider::new())
+///     .or_else(Region::new("us-east-1"));
+/// assert!(provider.region().is_some());
+/// ```
+pub struct RegionProviderChain {
+    providers: Vec<Box<dyn ProvideRegion>>,
+}
+
+impl Debug for RegionProviderChain {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegionProviderChain")
+            .field("providers", &self.providers.len())
+            .finish()
+    }
+}
+
+impl RegionProviderChain {
+    /// Create a `RegionProviderChain` that begins by evaluating this provider
+    pub fn first_try(provider: impl ProvideRegion + 'static) -> Self {
+        RegionProviderChain {
+            providers: vec![Box::new(provider)],
+        }
+    }
+
+    /// Add a fallback provider to the chain
+    pub fn or_else(mut self, provider: impl ProvideRegion + 'static) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
+    /// The default chain: environment variables, then the shared config files
+    ///
+    /// See [`default_provider`](default_provider).
+    pub fn default_provider() -> Self {
+        Self::first_try(EnvironmentProvider::new()).or_else(ProfileFileRegionProvider::new())
+    }
+}
+
+impl ProvideRegion for RegionProviderChain {
+    fn region(&self) -> Option<Region> {
+        self.providers.iter().find_map(|provider| provider.region())
+    }
+}
+
+impl<T> ProvideRegion for Box<T>
+where
+    T: ProvideRegion + ?Sized,
+{
+    fn region(&self) -> Option<Region> {
+        self.as_ref().region()
+    }
 }
 
 #[non_exhaustive]
@@ -86,6 +159,46 @@ impl ProvideRegion for EnvironmentProvider {
     }
 }
 
+/// Load the region from the shared config files
+///
+/// The `region` of the active profile (selected with `AWS_PROFILE`) is returned. See
+/// [`profile`](crate::profile) for how the files are located and merged. The files are re-read
+/// each time the region is requested; if they cannot be loaded, no region is returned.
+#[derive(Debug)]
+pub struct ProfileFileRegionProvider {
+    fs: Fs,
+    env: Env,
+}
+
+impl Default for ProfileFileRegionProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProfileFileRegionProvider {
+    pub fn new() -> Self {
+        ProfileFileRegionProvider {
+            fs: Fs::real(),
+            env: Env::real(),
+        }
+    }
+
+    /// Create a Profile region provider that loads from a custom file system & environment, eg. for testing
+    pub fn for_fs(fs: Fs, env: Env) -> Self {
+        ProfileFileRegionProvider { fs, env }
+    }
+}
+
+impl ProvideRegion for ProfileFileRegionProvider {
+    fn region(&self) -> Option<Region> {
+        let profiles = profile::load(&self.fs, &self.env).ok()?;
+        profiles
+            .get("region")
+            .map(|region| Region::new(region.to_string()))
+    }
+}
+
 /// The region to use when signing requests
 ///
 /// Generally, user code will not need to interact with `SigningRegion`. See `[Region](crate::Region)`.
@@ -112,7 +225,10 @@ impl SigningRegion {
 
 #[cfg(test)]
 mod test {
-    use crate::region::{EnvironmentProvider, ProvideRegion, Region};
+    use crate::os_shim::{Env, Fs};
+    use crate::region::{
+        EnvironmentProvider, ProfileFileRegionProvider, ProvideRegion, Region, RegionProviderChain,
+    };
     use std::collections::HashMap;
     use std::env::VarError;
 
@@ -145,4 +261,58 @@ mod test {
         env.insert("AWS_DEFAULT_REGION", "us-east-2");
         assert_eq!(test_provider(env).region(), Some(Region::new("us-east-2")));
     }
+
+    fn profile_provider(config: &str, env: &[(&str, &str)]) -> ProfileFileRegionProvider {
+        let mut fs = HashMap::new();
+        fs.insert("/home/.aws/config".to_string(), config.as_bytes().to_vec());
+        let mut env = env.to_vec();
+        env.push(("HOME", "/home"));
+        ProfileFileRegionProvider::for_fs(Fs::from_map(fs), Env::from_slice(&env))
+    }
+
+    #[test]
+    fn region_from_profile() {
+        let config = "[default]\nregion = us-west-2\n\n[profile dev]\nregion = eu-west-1";
+        assert_eq!(
+            profile_provider(config, &[]).region(),
+            Some(Region::new("us-west-2"))
+        );
+        assert_eq!(
+            profile_provider(config, &[("AWS_PROFILE", "dev")]).region(),
+            Some(Region::new("eu-west-1"))
+        );
+        assert_eq!(
+            profile_provider(config, &[("AWS_PROFILE", "other")]).region(),
+            None
+        );
+    }
+
+    #[test]
+    fn invalid_profile_has_no_region() {
+        assert_eq!(
+            profile_provider("[default\nregion = us-west-2", &[]).region(),
+            None
+        );
+    }
+
+    #[test]
+    fn chain_uses_first_region() {
+        let chain = RegionProviderChain::first_try(test_provider(HashMap::new()))
+            .or_else(profile_provider("[default]\nregion = us-west-2", &[]))
+            .or_else(Region::new("us-east-1"));
+        assert_eq!(chain.region(), Some(Region::new("us-west-2")));
+
+        let mut env = HashMap::new();
+        env.insert("AWS_REGION", "ap-south-1");
+        let chain = RegionProviderChain::first_try(test_provider(env))
+            .or_else(profile_provider("[default]\nregion = us-west-2", &[]));
+        assert_eq!(chain.region(), Some(Region::new("ap-south-1")));
+    }
+
+    #[test]
+    fn empty_chain() {
+        let chain = RegionProviderChain::first_try(test_provider(HashMap::new()))
+            .or_else(profile_provider("", &[]));
+        assert_eq!(chain.region(), None);
+    }
 }
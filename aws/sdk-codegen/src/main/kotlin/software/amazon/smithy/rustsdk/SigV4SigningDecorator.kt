@@ -30,8 +30,8 @@ import software.amazon.smithy.rust.codegen.util.hasTrait
  * The SigV4SigningDecorator:
  * - adds a `signing_service()` method to `config` to return the default signing service
  * - sets the `SigningService` during operation construction
- * - sets a default `OperationSigningConfig` A future enhancement will customize this for specific services that need
- *   different behavior.
+ * - sets an `OperationSigningConfig`, customized for services that need different behavior (eg. S3 signs
+ *   `x-amz-content-sha256`, does not double-encode or normalize the URI path)
  */
 class SigV4SigningDecorator : RustCodegenDecorator {
     override val name: String = "SigV4Signing"
@@ -90,6 +90,19 @@ fun disableDoubleEncode(service: ServiceShape) = when {
     else -> false
 }
 
+fun disableUriPathNormalization(service: ServiceShape) = when {
+    service.id == ShapeId.from("com.amazonaws.s3#AmazonS3") -> true
+    else -> false
+}
+
+/**
+ * Services that expect the session token to be added to the request after signing
+ */
+fun omitSessionToken(service: ServiceShape) = when {
+    service.id == ShapeId.from("com.amazonaws.iotdataplane#IotMoveDataPlaneService") -> true
+    else -> false
+}
+
 class SigV4SigningFeature(private val runtimeConfig: RuntimeConfig, private val service: ServiceShape) :
     OperationCustomization() {
     override fun section(section: OperationSection): Writable {
@@ -109,6 +122,12 @@ class SigV4SigningFeature(private val runtimeConfig: RuntimeConfig, private val
                 if (disableDoubleEncode(service)) {
                     rust("signing_config.signing_options.double_uri_encode = false;")
                 }
+                if (disableUriPathNormalization(service)) {
+                    rust("signing_config.signing_options.normalize_uri_path = false;")
+                }
+                if (omitSessionToken(service)) {
+                    rust("signing_config.signing_options.omit_session_token = true;")
+                }
                 rustTemplate(
                     """
                 ${section.request}.config_mut().insert(signing_config);
@@ -5,8 +5,13 @@
 
 package software.amazon.smithy.rustsdk
 
+import io.kotest.matchers.string.shouldContain
+import io.kotest.matchers.string.shouldNotContain
 import org.junit.jupiter.api.Test
 import software.amazon.smithy.aws.traits.auth.SigV4Trait
+import software.amazon.smithy.model.shapes.ServiceShape
+import software.amazon.smithy.rust.codegen.rustlang.RustWriter
+import software.amazon.smithy.rust.codegen.smithy.customize.OperationSection
 import software.amazon.smithy.rust.codegen.testutil.compileAndTest
 import software.amazon.smithy.rust.codegen.testutil.stubConfigProject
 import software.amazon.smithy.rust.codegen.testutil.unitTest
@@ -25,4 +30,35 @@ internal class SigV4SigningCustomizationTest {
         }
         project.compileAndTest()
     }
+
+    private fun renderSigningConfig(serviceId: String): String {
+        val service = ServiceShape.builder().version("test").id(serviceId).build()
+        val writer = RustWriter.forModule("operation")
+        SigV4SigningFeature(AwsTestRuntimeConfig, service)
+            .section(OperationSection.MutateRequest("request", "_config"))(writer)
+        return writer.toString()
+    }
+
+    @Test
+    fun `s3 disables uri path normalization`() {
+        val rendered = renderSigningConfig("com.amazonaws.s3#AmazonS3")
+        rendered shouldContain "signing_config.signing_options.normalize_uri_path = false;"
+        rendered shouldContain "signing_config.signing_options.double_uri_encode = false;"
+        rendered shouldContain "signing_config.signing_options.content_sha256_header = true;"
+        rendered shouldNotContain "omit_session_token"
+    }
+
+    @Test
+    fun `iot data plane omits the session token`() {
+        val rendered = renderSigningConfig("com.amazonaws.iotdataplane#IotMoveDataPlaneService")
+        rendered shouldContain "signing_config.signing_options.omit_session_token = true;"
+        rendered shouldNotContain "normalize_uri_path"
+    }
+
+    @Test
+    fun `other services use the default signing options`() {
+        val rendered = renderSigningConfig("test#Service")
+        rendered shouldContain "OperationSigningConfig::default_config()"
+        rendered shouldNotContain "signing_config.signing_options"
+    }
 }
@@ -21,6 +21,7 @@ val smithyVersion: String by project
 val sdkOutputDir = buildDir.resolve("aws-sdk")
 val runtimeModules = listOf(
     "smithy-types",
+    "smithy-eventstream",
     "smithy-json",
     "smithy-query",
     "smithy-xml",
@@ -29,7 +30,7 @@ val runtimeModules = listOf(
     "smithy-client",
     "protocol-test-helpers"
 )
-val awsModules = listOf("aws-auth", "aws-endpoint", "aws-types", "aws-hyper", "aws-sig-auth", "aws-http")
+val awsModules = listOf("aws-auth", "aws-auth-providers", "aws-endpoint", "aws-types", "aws-hyper", "aws-sig-auth", "aws-http")
 
 buildscript {
     val smithyVersion: String by project
@@ -187,9 +188,12 @@ tasks.register<Copy>("relocateAwsRuntime") {
 /**
  * The aws/rust-runtime crates depend on local versions of the Smithy core runtime enabling local compilation. However,
  * those paths need to be replaced in the final build. We should probably fix this with some symlinking.
+ *
+ * Crates that depend on generated services (eg. `aws-auth-providers` depends on STS) refer to them in `sdk/build`;
+ * in the final build they are siblings.
  */
 fun rewritePathDependency(line: String): String {
-    return line.replace("../../rust-runtime/", "")
+    return line.replace("../../rust-runtime/", "").replace("../../sdk/build/aws-sdk/", "../")
 }
 
 tasks.register<Copy>("relocateRuntime") {
@@ -0,0 +1,103 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+package software.amazon.smithy.rust.codegen.smithy.customizations
+
+import software.amazon.smithy.model.shapes.MemberShape
+import software.amazon.smithy.model.shapes.OperationShape
+import software.amazon.smithy.model.shapes.Shape
+import software.amazon.smithy.model.shapes.ShapeId
+import software.amazon.smithy.model.shapes.StructureShape
+import software.amazon.smithy.model.traits.HttpHeaderTrait
+import software.amazon.smithy.model.traits.HttpLabelTrait
+import software.amazon.smithy.model.traits.HttpPrefixHeadersTrait
+import software.amazon.smithy.model.traits.HttpQueryTrait
+import software.amazon.smithy.model.traits.HttpResponseCodeTrait
+import software.amazon.smithy.model.traits.SensitiveTrait
+import software.amazon.smithy.rust.codegen.rustlang.Writable
+import software.amazon.smithy.rust.codegen.rustlang.rust
+import software.amazon.smithy.rust.codegen.rustlang.writable
+import software.amazon.smithy.rust.codegen.smithy.RuntimeType
+import software.amazon.smithy.rust.codegen.smithy.customize.OperationCustomization
+import software.amazon.smithy.rust.codegen.smithy.customize.OperationSection
+import software.amazon.smithy.rust.codegen.smithy.generators.ProtocolConfig
+import software.amazon.smithy.rust.codegen.util.dq
+import software.amazon.smithy.rust.codegen.util.inputShape
+import software.amazon.smithy.rust.codegen.util.outputShape
+
+/**
+ * Records the parts of the requests and responses of an operation that carry `@sensitive` members
+ * (`smithy_http::operation::SensitiveData`) so that logging middleware can redact them.
+ */
+class SensitiveDataGenerator(protocolConfig: ProtocolConfig, operationShape: OperationShape) :
+    OperationCustomization() {
+    private val model = protocolConfig.model
+    private val runtimeConfig = protocolConfig.runtimeConfig
+    private val input = operationShape.inputShape(model)
+    private val responses = listOf(operationShape.outputShape(model)) +
+        operationShape.errors.map { model.expectShape(it, StructureShape::class.java) }
+
+    private val sensitiveData by lazy { sensitiveData() }
+
+    override fun section(section: OperationSection): Writable {
+        if (sensitiveData.isEmpty()) {
+            return emptySection
+        }
+        return when (section) {
+            is OperationSection.MutateRequest -> writable {
+                rust(
+                    "${section.request}.config_mut().insert(#T::new()${sensitiveData.joinToString("")});",
+                    RuntimeType.operationModule(runtimeConfig).member("SensitiveData")
+                )
+            }
+            else -> emptySection
+        }
+    }
+
+    /** Calls to the builder methods of `SensitiveData` */
+    private fun sensitiveData(): List<String> {
+        val headers = (listOf(input) + responses).flatMap { shape ->
+            shape.members().filter { it.isSensitive() }.mapNotNull { member ->
+                member.getTrait(HttpHeaderTrait::class.java).map { ".with_header(${it.value.dq()})" }.orElse(null)
+                    ?: member.getTrait(HttpPrefixHeadersTrait::class.java).map { ".with_header_prefix(${it.value.dq()})" }.orElse(null)
+            }
+        }.distinct()
+        val uri = input.members().any { it.isSensitive() && (it.hasTrait(HttpLabelTrait::class.java) || it.hasTrait(HttpQueryTrait::class.java)) }
+        val requestBody = input.bodyMembers().any { it.containsSensitive(mutableSetOf()) }
+        val responseBody = responses.any { response -> response.bodyMembers().any { it.containsSensitive(mutableSetOf()) } }
+        return headers +
+            listOfNotNull(
+                ".with_uri()".takeIf { uri },
+                ".with_request_body()".takeIf { requestBody },
+                ".with_response_body()".takeIf { responseBody }
+            )
+    }
+
+    private fun MemberShape.isSensitive() = this.getMemberTrait(model, SensitiveTrait::class.java).isPresent
+
+    /** Members that are serialized into the body, ie. that aren't bound to any other part of the message */
+    private fun StructureShape.bodyMembers() = this.members().filter { member ->
+        listOf(
+            HttpHeaderTrait::class.java,
+            HttpPrefixHeadersTrait::class.java,
+            HttpLabelTrait::class.java,
+            HttpQueryTrait::class.java,
+            HttpResponseCodeTrait::class.java
+        ).none { member.hasTrait(it) }
+    }
+
+    private fun Shape.containsSensitive(visited: MutableSet<ShapeId>): Boolean {
+        if (this.hasTrait(SensitiveTrait::class.java)) {
+            return true
+        }
+        if (!visited.add(this.id)) {
+            return false
+        }
+        return when (this) {
+            is MemberShape -> model.expectShape(this.target).containsSensitive(visited)
+            else -> this.members().any { it.containsSensitive(visited) }
+        }
+    }
+}
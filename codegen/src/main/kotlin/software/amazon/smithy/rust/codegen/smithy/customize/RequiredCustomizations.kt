@@ -10,6 +10,7 @@ import software.amazon.smithy.rust.codegen.smithy.customizations.AllowClippyLint
 import software.amazon.smithy.rust.codegen.smithy.customizations.CrateVersionGenerator
 import software.amazon.smithy.rust.codegen.smithy.customizations.EndpointPrefixGenerator
 import software.amazon.smithy.rust.codegen.smithy.customizations.IdempotencyTokenGenerator
+import software.amazon.smithy.rust.codegen.smithy.customizations.SensitiveDataGenerator
 import software.amazon.smithy.rust.codegen.smithy.customizations.SmithyTypesPubUseGenerator
 import software.amazon.smithy.rust.codegen.smithy.generators.LibRsCustomization
 import software.amazon.smithy.rust.codegen.smithy.generators.ProtocolConfig
@@ -30,7 +31,7 @@ class RequiredCustomizations : RustCodegenDecorator {
         return baseCustomizations + IdempotencyTokenGenerator(protocolConfig, operation) + EndpointPrefixGenerator(
             protocolConfig,
             operation
-        )
+        ) + SensitiveDataGenerator(protocolConfig, operation)
     }
 
     override fun libRsCustomizations(
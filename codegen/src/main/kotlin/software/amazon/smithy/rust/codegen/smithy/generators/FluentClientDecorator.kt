@@ -160,12 +160,12 @@ class FluentClientGenerator(protocolConfig: ProtocolConfig) {
             /// use aws_http::user_agent::UserAgentStage;
             /// use aws_sig_auth::middleware::SigV4SigningStage;
             /// use aws_sig_auth::signer::SigV4Signer;
-            /// use smithy_http_tower::map_request::MapRequestLayer;
+            /// use smithy_http_tower::map_request::{AsyncMapRequestLayer, MapRequestLayer};
             /// use tower::layer::util::Stack;
             /// use tower::ServiceBuilder;
             ///
             /// type AwsMiddlewareStack =
-            ///     Stack<MapRequestLayer<SigV4SigningStage>,
+            ///     Stack<AsyncMapRequestLayer<SigV4SigningStage>,
             ///         Stack<MapRequestLayer<UserAgentStage>,
             ///             MapRequestLayer<AwsEndpointStage>>>,
             ///
@@ -175,7 +175,7 @@ class FluentClientGenerator(protocolConfig: ProtocolConfig) {
             ///     type Service = <AwsMiddlewareStack as tower::Layer<S>>::Service;
             ///
             ///     fn layer(&self, inner: S) -> Self::Service {
-            ///         let signer = MapRequestLayer::for_mapper(SigV4SigningStage::new(SigV4Signer::new())); _signer: MapRequestLaye
+            ///         let signer = AsyncMapRequestLayer::for_mapper(SigV4SigningStage::new(SigV4Signer::new())); _signer: AsyncMapRequestLaye
             ///         let endpoint_resolver = MapRequestLayer::for_mapper(AwsEndpointStage); _endpoint_resolver: MapRequestLayer<Aw
             ///         let user_agent = MapRequestLayer::for_mapper(UserAgentStage::new()); _user_agent: MapRequestLayer<UserAgentSt
             ///         // These layers can be considered as occuring in order, that is:
@@ -186,7 +186,7 @@ class FluentClientGenerator(protocolConfig: ProtocolConfig) {
             ///         ServiceBuilder::new() _ServiceBuilder<Identity>
             ///             .layer(endpoint_resolver) _ServiceBuilder<Stack<MapRequestLayer<_>, _>>
             ///             .layer(user_agent) _ServiceBuilder<Stack<MapRequestLayer<_>, _>>
-            ///             .layer(signer) _ServiceBuilder<Stack<MapRequestLayer<_>, _>>
+            ///             .layer(signer) _ServiceBuilder<Stack<AsyncMapRequestLayer<_>, _>>
             ///             .service(inner)
             ///     }
             /// }
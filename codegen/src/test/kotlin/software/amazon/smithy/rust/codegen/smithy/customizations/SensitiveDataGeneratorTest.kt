@@ -0,0 +1,147 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+package software.amazon.smithy.rust.codegen.smithy.customizations
+
+import io.kotest.matchers.string.shouldContain
+import io.kotest.matchers.string.shouldNotContain
+import org.junit.jupiter.api.Test
+import software.amazon.smithy.model.shapes.OperationShape
+import software.amazon.smithy.model.shapes.ShapeId
+import software.amazon.smithy.rust.codegen.rustlang.RustWriter
+import software.amazon.smithy.rust.codegen.smithy.customize.OperationSection
+import software.amazon.smithy.rust.codegen.smithy.transformers.OperationNormalizer
+import software.amazon.smithy.rust.codegen.testutil.asSmithyModel
+import software.amazon.smithy.rust.codegen.testutil.testProtocolConfig
+
+internal class SensitiveDataGeneratorTest {
+    private val baseModel = """
+        namespace test
+
+        @idempotent
+        @http(method: "PUT", uri: "/secrets/{id}")
+        operation PutSecret {
+            input: PutSecretRequest,
+            errors: [InvalidSecret]
+        }
+
+        @readonly
+        @http(method: "GET", uri: "/secrets/{id}")
+        operation GetSecretMetadata {
+            input: GetSecretMetadataRequest,
+            output: GetSecretMetadataResponse
+        }
+
+        @readonly
+        @http(method: "GET", uri: "/secrets")
+        operation ListSecrets {
+            input: ListSecretsRequest,
+            output: ListSecretsResponse
+        }
+
+        @sensitive
+        string SecretId
+
+        @sensitive
+        string Token
+
+        @sensitive
+        map SecretMetadata {
+            key: String,
+            value: String
+        }
+
+        structure Secret {
+            @sensitive
+            value: String
+        }
+
+        structure PutSecretRequest {
+            @required
+            @httpLabel
+            id: SecretId,
+
+            @httpHeader("X-Secret-Token")
+            token: Token,
+
+            @httpHeader("X-Client")
+            client: String,
+
+            secret: Secret
+        }
+
+        @error("client")
+        structure InvalidSecret {
+            message: String,
+
+            rejected: Secret
+        }
+
+        structure GetSecretMetadataRequest {
+            @required
+            @httpLabel
+            id: String
+        }
+
+        structure GetSecretMetadataResponse {
+            @httpPrefixHeaders("X-Secret-Meta-")
+            metadata: SecretMetadata,
+
+            created: Timestamp
+        }
+
+        structure ListSecretsRequest {
+            @httpQuery("prefix")
+            prefix: String
+        }
+
+        structure ListSecretsResponse {
+            names: Names
+        }
+
+        list Names {
+            member: String
+        }
+    """.asSmithyModel()
+    private val model = OperationNormalizer(baseModel).transformModel(
+        inputBodyFactory = OperationNormalizer.NoBody,
+        outputBodyFactory = OperationNormalizer.NoBody
+    )
+
+    private fun renderSensitiveData(operation: String): String {
+        val operationShape = model.expectShape(ShapeId.from("test#$operation"), OperationShape::class.java)
+        val writer = RustWriter.forModule("operation")
+        SensitiveDataGenerator(testProtocolConfig(model), operationShape)
+            .section(OperationSection.MutateRequest("request", "_config"))(writer)
+        return writer.toString()
+    }
+
+    @Test
+    fun `records the sensitive parts of requests and errors`() {
+        val rendered = renderSensitiveData("PutSecret")
+        rendered shouldContain "request.config_mut().insert("
+        rendered shouldContain "SensitiveData::new()"
+        rendered shouldContain ".with_header(\"X-Secret-Token\")"
+        rendered shouldNotContain "X-Client"
+        rendered shouldContain ".with_uri()"
+        rendered shouldContain ".with_request_body()"
+        // the body of the error contains a sensitive member
+        rendered shouldContain ".with_response_body()"
+    }
+
+    @Test
+    fun `records sensitive prefix headers of responses`() {
+        val rendered = renderSensitiveData("GetSecretMetadata")
+        rendered shouldContain ".with_header_prefix(\"X-Secret-Meta-\")"
+        rendered shouldNotContain ".with_uri()"
+        rendered shouldNotContain ".with_request_body()"
+        rendered shouldNotContain ".with_response_body()"
+    }
+
+    @Test
+    fun `operations without sensitive members are left alone`() {
+        renderSensitiveData("ListSecrets") shouldNotContain "SensitiveData"
+    }
+}
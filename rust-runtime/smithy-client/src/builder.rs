@@ -1,4 +1,4 @@
-use crate::{bounds, erase, retry, BoxError, Client};
+use crate::{bounds, erase, retry, timeout, BoxError, Client};
 use smithy_http::body::SdkBody;
 
 /// A builder that provides more customization options when constructing a [`Client`].
@@ -11,6 +11,8 @@ pub struct Builder<C = (), M = (), R = retry::Standard> {
     connector: C,
     middleware: M,
     retry_policy: R,
+    timeout_config: timeout::Config,
+    pub(crate) connect_timeout: timeout::SharedConnectTimeout,
 }
 
 // It'd be nice to include R where R: Default here, but then the caller ends up always having to
@@ -54,6 +56,8 @@ impl<M, R> Builder<(), M, R> {
             connector,
             retry_policy: self.retry_policy,
             middleware: self.middleware,
+            timeout_config: self.timeout_config,
+            connect_timeout: self.connect_timeout,
         }
     }
 
@@ -108,6 +112,8 @@ impl<C, R> Builder<C, (), R> {
             connector: self.connector,
             retry_policy: self.retry_policy,
             middleware,
+            timeout_config: self.timeout_config,
+            connect_timeout: self.connect_timeout,
         }
     }
 
@@ -151,6 +157,8 @@ impl<C, M> Builder<C, M, retry::Standard> {
             connector: self.connector,
             retry_policy,
             middleware: self.middleware,
+            timeout_config: self.timeout_config,
+            connect_timeout: self.connect_timeout,
         }
     }
 }
@@ -163,6 +171,24 @@ impl<C, M> Builder<C, M> {
 }
 
 impl<C, M, R> Builder<C, M, R> {
+    /// Set the timeout configuration of the eventual client.
+    ///
+    /// The connect timeout is applied by the connectors that the builder creates (eg.
+    /// `Builder::rustls`), whether the configuration is set before or after the connector is
+    /// chosen. Custom connectors must apply their own connect timeout.
+    pub fn set_timeout_config(&mut self, config: timeout::Config) {
+        self.connect_timeout.set(config.connect_timeout());
+        self.timeout_config = config;
+    }
+
+    /// Use the given timeout configuration for the eventual client.
+    ///
+    /// See [`Builder::set_timeout_config`].
+    pub fn timeout_config(mut self, config: timeout::Config) -> Self {
+        self.set_timeout_config(config);
+        self
+    }
+
     /// Use a connector that wraps the current connector.
     pub fn map_connector<F, C2>(self, map: F) -> Builder<C2, M, R>
     where
@@ -172,6 +198,8 @@ impl<C, M, R> Builder<C, M, R> {
             connector: map(self.connector),
             middleware: self.middleware,
             retry_policy: self.retry_policy,
+            timeout_config: self.timeout_config,
+            connect_timeout: self.connect_timeout,
         }
     }
 
@@ -184,6 +212,8 @@ impl<C, M, R> Builder<C, M, R> {
             connector: self.connector,
             middleware: map(self.middleware),
             retry_policy: self.retry_policy,
+            timeout_config: self.timeout_config,
+            connect_timeout: self.connect_timeout,
         }
     }
 
@@ -193,6 +223,8 @@ impl<C, M, R> Builder<C, M, R> {
             connector: self.connector,
             retry_policy: self.retry_policy,
             middleware: self.middleware,
+            timeout_config: self.timeout_config,
+            connect_timeout: self.connect_timeout,
         }
     }
 }
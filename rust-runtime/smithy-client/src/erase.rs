@@ -50,6 +50,8 @@ where
             connector: self.connector,
             middleware: DynMiddleware::new(self.middleware),
             retry_policy: self.retry_policy,
+            timeout_config: self.timeout_config,
+            connect_timeout: self.connect_timeout,
         }
     }
 }
@@ -88,6 +90,8 @@ where
             connector: DynConnector::new(self.connector),
             middleware: self.middleware,
             retry_policy: self.retry_policy,
+            timeout_config: self.timeout_config,
+            connect_timeout: self.connect_timeout,
         }
     }
 
@@ -1,16 +1,65 @@
+use crate::timeout::{ConnectTimeout, SharedConnectTimeout};
 use crate::Builder;
+use hyper::client::connect::Connect;
 use smithy_http::body::SdkBody;
+use smithy_http::result::ConnectorError;
 pub use smithy_http::result::{SdkError, SdkSuccess};
+use std::error::Error;
+use std::fmt;
+use std::io::ErrorKind;
+use std::sync::Arc;
 use tower::Service;
 
 /// Adapter from a [`hyper::Client`] to a connector useable by a [`Client`](crate::Client).
+///
+/// Errors from hyper are returned as is. When they fail a request sent by a [`Client`](crate::Client),
+/// they are wrapped in a [`ConnectorError`], classified as transient when retrying the request could
+/// succeed (connect errors, timeouts, connections closed or reset by the server).
+///
+/// The adapters created by [`Builder`] (eg. [`Builder::rustls`]) apply the connect timeout of the
+/// client. An adapter created from a `hyper::Client` uses the connector of that client as is.
 #[derive(Clone, Debug)]
 #[non_exhaustive]
-pub struct HyperAdapter<C>(hyper::Client<C, SdkBody>);
+pub struct HyperAdapter<C>(Inner<C>);
+
+#[derive(Clone, Debug)]
+enum Inner<C> {
+    Client(hyper::Client<C, SdkBody>),
+    WithConnectTimeout(TimedClient),
+}
+
+/// A `hyper::Client` whose connector is wrapped in a [`ConnectTimeout`]
+///
+/// The type of the wrapped connector is erased so that it doesn't show in the type of the adapter.
+#[derive(Clone)]
+struct TimedClient(
+    Arc<dyn Fn(http::Request<SdkBody>) -> hyper::client::ResponseFuture + Send + Sync>,
+);
+
+impl fmt::Debug for TimedClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TimedClient").finish()
+    }
+}
+
+impl<C> HyperAdapter<C> {
+    /// Connect with `connector`, within the connect timeout currently set in `timeout`
+    #[cfg_attr(not(any(feature = "rustls", feature = "native-tls")), allow(dead_code))]
+    fn with_connect_timeout(connector: C, timeout: SharedConnectTimeout) -> Self
+    where
+        ConnectTimeout<C>: Connect + Clone + Send + Sync + 'static,
+    {
+        let client = hyper::Client::builder()
+            .build::<_, SdkBody>(ConnectTimeout::shared(connector, timeout));
+        HyperAdapter(Inner::WithConnectTimeout(TimedClient(Arc::new(
+            move |req| client.request(req),
+        ))))
+    }
+}
 
 impl<C> Service<http::Request<SdkBody>> for HyperAdapter<C>
 where
-    C: hyper::client::connect::Connect + Clone + Send + Sync + 'static,
+    C: Connect + Clone + Send + Sync + 'static,
 {
     type Response = http::Response<SdkBody>;
     type Error = hyper::Error;
@@ -24,18 +73,77 @@ where
         &mut self,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Result<(), Self::Error>> {
-        self.0.poll_ready(cx)
+        match &mut self.0 {
+            Inner::Client(client) => client.poll_ready(cx),
+            // a hyper client is always ready
+            Inner::WithConnectTimeout(_) => std::task::Poll::Ready(Ok(())),
+        }
     }
 
     fn call(&mut self, req: http::Request<SdkBody>) -> Self::Future {
-        let fut = self.0.call(req);
+        let fut = match &mut self.0 {
+            Inner::Client(client) => client.call(req),
+            Inner::WithConnectTimeout(client) => (client.0)(req),
+        };
         Box::pin(async move { Ok(fut.await?.map(SdkBody::from)) })
     }
 }
 
+/// Wrap the `hyper::Error` of a dispatch failure in a [`ConnectorError`]
+///
+/// Other errors are left as is, since they are either already classified or don't come from hyper.
+pub(crate) fn classify_dispatch_failure<E>(err: SdkError<E>) -> SdkError<E> {
+    match err {
+        SdkError::DispatchFailure(err) => match err.downcast::<hyper::Error>() {
+            Ok(err) => SdkError::DispatchFailure(Box::new(to_connector_error(*err))),
+            Err(err) => SdkError::DispatchFailure(err),
+        },
+        err => err,
+    }
+}
+
+/// Classify a hyper error
+///
+/// Requests are canceled by hyper when their connection closes before the response is read, and
+/// fail with an incomplete message when it closes while the response is read. In both cases, as
+/// when the connection is reset, the request may have been processed by the service: like the other
+/// AWS SDKs, such failures are retried for every operation, relying on idempotency tokens (filled in
+/// by generated clients) to make retries of non-idempotent operations safe.
+fn to_connector_error(err: hyper::Error) -> ConnectorError {
+    if err.is_connect()
+        || err.is_timeout()
+        || err.is_incomplete_message()
+        || err.is_canceled()
+        || is_io_failure(&err)
+    {
+        ConnectorError::transient(err)
+    } else {
+        ConnectorError::other(err)
+    }
+}
+
+/// Did the connection fail underneath hyper (eg. reset by the server)?
+fn is_io_failure(err: &hyper::Error) -> bool {
+    let mut source = err.source();
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<std::io::Error>() {
+            return matches!(
+                err.kind(),
+                ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::TimedOut
+                    | ErrorKind::UnexpectedEof
+            );
+        }
+        source = err.source();
+    }
+    false
+}
+
 impl<C> From<hyper::Client<C, SdkBody>> for HyperAdapter<C> {
     fn from(hc: hyper::Client<C, SdkBody>) -> Self {
-        Self(hc)
+        Self(Inner::Client(hc))
     }
 }
 
@@ -43,7 +151,7 @@ impl<M, R> Builder<(), M, R> {
     /// Connect to the service using the provided `hyper` client.
     pub fn hyper<HC>(self, connector: hyper::Client<HC, SdkBody>) -> Builder<HyperAdapter<HC>, M, R>
     where
-        HC: hyper::client::connect::Connect + Clone + Send + Sync + 'static,
+        HC: Connect + Clone + Send + Sync + 'static,
     {
         self.connector(HyperAdapter::from(connector))
     }
@@ -84,8 +192,8 @@ impl<M, R> Builder<(), M, R> {
     ) -> Builder<HyperAdapter<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>>, M, R>
     {
         let https = hyper_rustls::HttpsConnector::with_native_roots();
-        let client = hyper::Client::builder().build::<_, SdkBody>(https);
-        self.connector(HyperAdapter::from(client))
+        let connect_timeout = self.connect_timeout.clone();
+        self.connector(HyperAdapter::with_connect_timeout(https, connect_timeout))
     }
 
     /// Connect to the service over HTTPS using Rustls.
@@ -106,7 +214,176 @@ impl<M, R> Builder<(), M, R> {
         self,
     ) -> Builder<HyperAdapter<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>, M, R> {
         let https = hyper_tls::HttpsConnector::new();
-        let client = hyper::Client::builder().build::<_, SdkBody>(https);
-        self.connector(HyperAdapter::from(client))
+        let connect_timeout = self.connect_timeout.clone();
+        self.connector(HyperAdapter::with_connect_timeout(https, connect_timeout))
+    }
+}
+
+#[cfg(all(test, any(feature = "rustls", feature = "native-tls")))]
+mod test {
+    use crate::hyper_impls::{to_connector_error, HyperAdapter};
+    use crate::static_tests::TestOperation;
+    use crate::timeout::{self, TimeoutError, TimeoutKind};
+    use crate::{retry, Builder};
+    use hyper::client::HttpConnector;
+    use smithy_http::body::SdkBody;
+    use smithy_http::operation::{self, Operation};
+    use smithy_http::result::ConnectorError;
+    use smithy_types::retry::ErrorKind;
+    use std::error::Error;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+    use tower::layer::util::Identity;
+    use tower::{Service, ServiceExt};
+
+    async fn send(addr: SocketAddr) -> ConnectorError {
+        let err = send_with_hyper(addr).await;
+        to_connector_error(err)
+    }
+
+    async fn send_with_hyper(addr: SocketAddr) -> hyper::Error {
+        let mut adapter =
+            HyperAdapter::from(hyper::Client::builder().build::<_, SdkBody>(HttpConnector::new()));
+        let request = http::Request::builder()
+            .uri(format!("http://{}/", addr))
+            .body(SdkBody::empty())
+            .unwrap();
+        adapter
+            .ready()
+            .await
+            .unwrap()
+            .call(request)
+            .await
+            .expect_err("the request should fail")
+    }
+
+    /// Serve every connection by writing `response` then closing the connection
+    async fn serve(response: &'static [u8]) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let _ = socket.write_all(response).await;
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn connection_refused_is_transient() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let err = send(addr).await;
+        assert_eq!(
+            err.retryable_error_kind(),
+            Some(ErrorKind::TransientError),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatch_failures_are_classified() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = Builder::new()
+            .hyper(hyper::Client::builder().build(HttpConnector::new()))
+            .middleware(Identity::new())
+            .build()
+            .with_retry_config(retry::Config::default().disable_retries());
+        let request = http::Request::builder()
+            .uri(format!("http://{}/", addr))
+            .body(SdkBody::empty())
+            .unwrap();
+        let operation = Operation::new(operation::Request::new(request), TestOperation);
+        let err = client
+            .call(operation)
+            .await
+            .expect_err("the connection is refused");
+        let found = ConnectorError::find(&err).expect("connector error");
+        assert_eq!(
+            found.retryable_error_kind(),
+            Some(ErrorKind::TransientError)
+        );
+        assert!(found.source().unwrap().is::<hyper::Error>(), "{:?}", err);
+    }
+
+    // The test servers speak HTTP/1, which hyper only enables with rustls
+    #[cfg(feature = "rustls")]
+    #[tokio::test]
+    async fn connection_closed_is_transient() {
+        let addr = serve(b"").await;
+        // hyper cancels the request when the connection closes before the response is read
+        let err = send_with_hyper(addr).await;
+        assert!(
+            err.is_canceled() || err.is_incomplete_message(),
+            "{:?}",
+            err
+        );
+        let err = send(addr).await;
+        assert_eq!(
+            err.retryable_error_kind(),
+            Some(ErrorKind::TransientError),
+            "{:?}",
+            err
+        );
+    }
+
+    #[cfg(feature = "rustls")]
+    #[tokio::test]
+    async fn invalid_response_is_not_retried() {
+        let err = send(serve(b"not http\r\n\r\n").await).await;
+        assert_eq!(err.retryable_error_kind(), None, "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn connect_timeout_set_after_the_connector() {
+        // The server accepts connections but never answers the TLS handshake
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut connections = vec![];
+            loop {
+                connections.push(listener.accept().await.unwrap());
+            }
+        });
+
+        #[cfg(feature = "rustls")]
+        let builder = Builder::new().rustls();
+        #[cfg(not(feature = "rustls"))]
+        let builder = Builder::new().native_tls();
+        let mut client = builder
+            .middleware(Identity::new())
+            .build()
+            .with_retry_config(retry::Config::default().disable_retries());
+        client.set_timeout_config(
+            timeout::Config::new()
+                .with_connect_timeout(Duration::from_millis(100))
+                .with_total_timeout(Duration::from_secs(5)),
+        );
+        let request = http::Request::builder()
+            .uri(format!("https://localhost:{}/", addr.port()))
+            .body(SdkBody::empty())
+            .unwrap();
+        let operation = Operation::new(operation::Request::new(request), TestOperation);
+        let err = client
+            .call(operation)
+            .await
+            .expect_err("connecting times out");
+        assert_eq!(
+            TimeoutError::find(&err).map(TimeoutError::kind),
+            Some(TimeoutKind::Connect),
+            "{:?}",
+            err
+        );
     }
 }
@@ -13,6 +13,7 @@
 pub mod bounds;
 pub mod erase;
 pub mod retry;
+pub mod timeout;
 
 // https://github.com/rust-lang/rust/issues/72081
 #[allow(rustdoc::private_doc_tests)]
@@ -65,6 +66,7 @@ use smithy_http_tower::dispatch::DispatchLayer;
 use smithy_http_tower::parse_response::ParseResponseLayer;
 use smithy_types::retry::ProvideErrorKind;
 use std::error::Error;
+use timeout::{with_timeout, AttemptTimeoutLayer, TimeoutKind};
 use tower::{Layer, Service, ServiceBuilder, ServiceExt};
 
 type BoxError = Box<dyn Error + Send + Sync>;
@@ -97,6 +99,8 @@ pub struct Client<
     connector: Connector,
     middleware: Middleware,
     retry_policy: RetryPolicy,
+    timeout_config: timeout::Config,
+    connect_timeout: timeout::SharedConnectTimeout,
 }
 
 // Quick-create for people who just want "the default".
@@ -125,6 +129,34 @@ impl<C, M> Client<C, M> {
         self.set_retry_config(config);
         self
     }
+
+    /// A snapshot of the retry state shared by the requests of this client (eg. the retry quota)
+    pub fn retry_state(&self) -> retry::RetryState {
+        self.retry_policy.retry_state()
+    }
+}
+
+impl<C, M, R> Client<C, M, R> {
+    /// Set the timeout configuration.
+    ///
+    /// The connect timeout only applies to connectors created by the [`Builder`] (eg.
+    /// `Builder::rustls`), see [`Builder::set_timeout_config`]. It applies to the next
+    /// connections they establish.
+    pub fn set_timeout_config(&mut self, config: timeout::Config) {
+        self.connect_timeout.set(config.connect_timeout());
+        self.timeout_config = config;
+    }
+
+    /// Adjust the client with the given timeout configuration.
+    pub fn with_timeout_config(mut self, config: timeout::Config) -> Self {
+        self.set_timeout_config(config);
+        self
+    }
+
+    /// The retry policy of this client
+    pub fn retry_policy(&self) -> &R {
+        &self.retry_policy
+    }
 }
 
 impl<C, M, R> Client<C, M, R>
@@ -152,7 +184,7 @@ where
     /// implementing unsupported features.
     pub async fn call_raw<O, T, E, Retry>(
         &self,
-        input: Operation<O, Retry>,
+        mut input: Operation<O, Retry>,
     ) -> Result<SdkSuccess<T>, SdkError<E>>
     where
         R::Policy: bounds::SmithyRetryPolicy<O, T, E, Retry>,
@@ -165,17 +197,40 @@ where
         bounds::Parsed<<M as bounds::SmithyMiddleware<C>>::Service, O, Retry>:
             Service<Operation<O, Retry>, Response = SdkSuccess<T>, Error = SdkError<E>> + Clone,
     {
+        // Keep a recorder provided by the caller
+        let recorder = input
+            .config()
+            .get::<retry::AttemptRecorder>()
+            .cloned()
+            .unwrap_or_default();
+        input.config_mut().insert(recorder.clone());
+
         let connector = self.connector.clone();
         let mut svc = ServiceBuilder::new()
             // Create a new request-scoped policy
             .retry(self.retry_policy.new_request_policy())
+            .layer(AttemptTimeoutLayer::new(
+                self.timeout_config.attempt_timeout(),
+            ))
             .layer(ParseResponseLayer::<O, Retry>::new())
             // These layers can be considered as occuring in order. That is, first invoke the
             // customer-provided middleware, then dispatch dispatch over the wire.
             .layer(&self.middleware)
             .layer(DispatchLayer::new())
             .service(connector);
-        svc.ready().await?.call(input).await
+        let call = async {
+            self.retry_policy.acquire_send_token().await;
+            svc.ready().await?.call(input).await
+        };
+        let mut result = with_timeout(
+            TimeoutKind::Total,
+            self.timeout_config.total_timeout(),
+            call,
+        )
+        .await;
+
+        recorder.finish(&mut result);
+        result
     }
 
     /// Statically check the validity of a `Client` without a request to send.
@@ -14,8 +14,15 @@
 //!   Its sole purpose in life is to create a [`RetryHandler`] for individual requests.
 //! - [`RetryHandler`]: A request-scoped retry policy, backed by request-local state and shared
 //!   state contained within [`Standard`].
-//! - [`Config`]: Static configuration (max retries, max backoff etc.)
+//! - [`Config`]: Static configuration (max attempts, max backoff etc.)
+//! - [`OperationRetryConfig`]: Overrides of the configuration for a single operation, set in its
+//!   property bag
+//! - [`RequestAttempts`]: The attempts made to send a request, recorded by an [`AttemptRecorder`]
+//! - [`RetryState`]: A snapshot of the retry quota of a client
+//! - [`RetryMode`]: Whether requests are also rate limited on the client side when they are
+//!   throttled
 
+use crate::timeout::{TimeoutError, TimeoutKind};
 use crate::{SdkError, SdkSuccess};
 use smithy_http::operation;
 use smithy_http::operation::Operation;
@@ -27,6 +34,9 @@ use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tracing::Instrument;
 
+mod adaptive;
+use adaptive::ClientRateLimiter;
+
 /// A policy instantiator.
 ///
 /// Implementors are essentially "policy factories" that can produce a new instance of a retry
@@ -38,22 +48,56 @@ pub trait NewRequestPolicy {
 
     /// Create a new policy mechanism instance.
     fn new_request_policy(&self) -> Self::Policy;
+
+    /// Wait until a new request may be sent.
+    ///
+    /// Policies that limit the sending rate of a client (eg. [`Standard`] in
+    /// [adaptive mode](RetryMode::Adaptive)) delay the first attempt of requests here. By default,
+    /// requests are sent right away.
+    fn acquire_send_token(&self) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>> {
+        Box::pin(async {})
+    }
+}
+
+/// Retry strategy of a client
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryMode {
+    /// Failed requests are retried with exponential backoff, within a retry quota
+    Standard,
+    /// In addition to the standard mode, the sending rate of the client is limited when requests are
+    /// throttled (`ErrorKind::ThrottlingError`)
+    ///
+    /// The rate is calculated with CUBIC: it is cut down on throttling and grows back as requests
+    /// succeed. Requests wait for a token of a token bucket filled at that rate before they are
+    /// sent.
+    Adaptive,
 }
 
 /// Retry Policy Configuration
 ///
-/// Without specific use cases, users should generally rely on the default values set by `[Config::default]`(Config::default).`
+/// Without specific use cases, users should generally rely on the default values set by [`Config::default`].
+///
+/// ```rust
+/// use smithy_client::retry::Config;
+/// use std::time::Duration;
+/// let conf = Config::default()
+///     .with_max_attempts(5)
+///     .with_max_backoff(Duration::from_secs(5));
+/// ```
 ///
-/// Currently these fields are private and no setters provided. As needed, this configuration will become user-modifiable in the future..
+/// The configuration of the client can be overridden for a single operation with an
+/// [`OperationRetryConfig`].
 #[derive(Clone, Debug)]
 pub struct Config {
     initial_retry_tokens: usize,
     retry_cost: usize,
     no_retry_increment: usize,
     timeout_retry_cost: usize,
-    max_retries: u32,
+    max_attempts: u32,
     max_backoff: Duration,
     base: fn() -> f64,
+    mode: RetryMode,
 }
 
 impl Config {
@@ -69,6 +113,49 @@ impl Config {
         self.base = base;
         self
     }
+
+    /// Set the maximum number of attempts of a request, including the initial attempt. Defaults to 3.
+    ///
+    /// A value of 1 disables retries, see [`Config::disable_retries`].
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Disable retries: requests are only attempted once
+    pub fn disable_retries(self) -> Self {
+        self.with_max_attempts(1)
+    }
+
+    /// Set the maximum delay between two attempts. Defaults to 20 seconds.
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Set the size of the retry quota shared by the requests of a client. Defaults to 500.
+    ///
+    /// Each retry consumes some quota, which is given back when the retried request succeeds. When
+    /// the quota is exhausted, failed requests are not retried.
+    pub fn with_initial_retry_tokens(mut self, initial_retry_tokens: usize) -> Self {
+        self.initial_retry_tokens = initial_retry_tokens;
+        self
+    }
+
+    /// Set the quota consumed by a retry. Defaults to 5.
+    ///
+    /// Retries of transient errors (eg. timeouts) consume twice that amount.
+    pub fn with_retry_cost(mut self, retry_cost: usize) -> Self {
+        self.retry_cost = retry_cost;
+        self.timeout_retry_cost = retry_cost * 2;
+        self
+    }
+
+    /// Set the retry mode. Defaults to [`RetryMode::Standard`].
+    pub fn with_mode(mut self, mode: RetryMode) -> Self {
+        self.mode = mode;
+        self
+    }
 }
 
 impl Default for Config {
@@ -78,15 +165,165 @@ impl Default for Config {
             retry_cost: RETRY_COST,
             no_retry_increment: 1,
             timeout_retry_cost: 10,
-            max_retries: MAX_RETRIES,
+            max_attempts: MAX_ATTEMPTS,
             max_backoff: Duration::from_secs(20),
             // by default, use a random base for exponential backoff
             base: fastrand::f64,
+            mode: RetryMode::Standard,
         }
     }
 }
 
-const MAX_RETRIES: u32 = 3;
+/// Retry configuration of a single operation
+///
+/// When it is in the property bag of an [`Operation`], it overrides the [`Config`] of the client for
+/// that operation. For example, to only attempt a non-idempotent operation once:
+/// ```rust
+/// use smithy_client::retry::OperationRetryConfig;
+/// # fn set_config<H, R>(operation: &mut smithy_http::operation::Operation<H, R>) {
+/// operation
+///     .config_mut()
+///     .insert(OperationRetryConfig::new().disable_retries());
+/// # }
+/// ```
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OperationRetryConfig {
+    max_attempts: Option<u32>,
+    max_backoff: Option<Duration>,
+}
+
+impl OperationRetryConfig {
+    /// Create an override that uses the configuration of the client for every setting
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Override the maximum number of attempts of the operation, including the initial attempt
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Only attempt the operation once
+    pub fn disable_retries(self) -> Self {
+        self.with_max_attempts(1)
+    }
+
+    /// Override the maximum delay between two attempts of the operation
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = Some(max_backoff);
+        self
+    }
+
+    fn apply(&self, config: &mut Config) {
+        if let Some(max_attempts) = self.max_attempts {
+            config.max_attempts = max_attempts;
+        }
+        if let Some(max_backoff) = self.max_backoff {
+            config.max_backoff = max_backoff;
+        }
+    }
+}
+
+/// The attempts made to send a request
+///
+/// [`Client`](crate::Client) records the attempts of each request and attaches them to the
+/// extensions of the raw response, in [`SdkSuccess`] and in the [`SdkError`] variants that have a
+/// response:
+/// ```rust
+/// use smithy_client::retry::RequestAttempts;
+/// # fn print_attempts<T>(success: &smithy_client::SdkSuccess<T>) {
+/// if let Some(attempts) = success.raw.extensions().get::<RequestAttempts>() {
+///     println!("succeeded after {} attempts", attempts.attempts());
+/// }
+/// # }
+/// ```
+///
+/// When no response was received ([`SdkError::DispatchFailure`], eg. a timeout), the attempts can
+/// be read from an [`AttemptRecorder`] added to the operation before it is sent.
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestAttempts {
+    attempts: u32,
+    backoffs: Vec<Duration>,
+    last_retry_kind: Option<RetryKind>,
+}
+
+impl RequestAttempts {
+    /// The number of attempts, including the initial attempt
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// The delays applied before each retry
+    pub fn backoffs(&self) -> &[Duration] {
+        &self.backoffs
+    }
+
+    /// How the result of the last attempt was classified
+    pub fn last_retry_kind(&self) -> Option<&RetryKind> {
+        self.last_retry_kind.as_ref()
+    }
+
+    /// Find the attempts of the request that failed with `err`, if a response was received
+    pub fn find<E>(err: &SdkError<E>) -> Option<&RequestAttempts> {
+        err.raw_response()
+            .and_then(|raw| raw.extensions().get::<RequestAttempts>())
+    }
+}
+
+/// Records the attempts of a request
+///
+/// When it is in the property bag of an [`Operation`], the [`RetryHandler`] records the result of
+/// every attempt of the operation in it. [`Client`](crate::Client) adds one to every operation it
+/// sends (a recorder added beforehand is kept) and attaches the [`RequestAttempts`] to the result.
+#[derive(Clone, Debug, Default)]
+pub struct AttemptRecorder(Arc<Mutex<RequestAttempts>>);
+
+impl AttemptRecorder {
+    /// Create an empty recorder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The attempts recorded so far
+    pub fn attempts(&self) -> RequestAttempts {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn record(&self, retry_kind: RetryKind, backoff: Option<Duration>) {
+        let mut attempts = self.0.lock().unwrap();
+        attempts.attempts += 1;
+        attempts.backoffs.extend(backoff);
+        attempts.last_retry_kind = Some(retry_kind);
+    }
+
+    /// Record the end of a request and attach its attempts to the raw response of `result`
+    ///
+    /// Attempts are recorded once their result is classified, so an attempt interrupted by the
+    /// total timeout isn't. A request that timed out before its first attempt was recorded still
+    /// made one attempt.
+    pub(crate) fn finish<T, E>(&self, result: &mut Result<SdkSuccess<T>, SdkError<E>>) {
+        let mut attempts = self.0.lock().unwrap();
+        if attempts.attempts == 0 {
+            match result {
+                Err(err) if TimeoutError::find(err).is_some() => attempts.attempts = 1,
+                _ => return,
+            }
+        }
+        match result {
+            Ok(SdkSuccess { raw, .. })
+            | Err(SdkError::ResponseError { raw, .. })
+            | Err(SdkError::ServiceError { raw, .. }) => {
+                raw.extensions_mut().insert(attempts.clone());
+            }
+            Err(SdkError::DispatchFailure(_)) | Err(SdkError::ConstructionFailure(_)) => {}
+        }
+    }
+}
+
+const MAX_ATTEMPTS: u32 = 3;
 const INITIAL_RETRY_TOKENS: usize = 500;
 const RETRY_COST: usize = 5;
 
@@ -95,10 +332,11 @@ const RETRY_COST: usize = 5;
 /// An implementation of the `standard` AWS retry strategy as specified in the SEP. A `Strategy` is scoped to a client.
 /// For an individual request, call [`Standard::new_request_policy()`](Standard::new_request_policy)
 ///
-/// In the future, adding support for the adaptive retry strategy will be added by adding a `TokenBucket` to
-/// `CrossRequestRetryState`
 /// Its main functionality is via `new_request_policy` which creates a `RetryHandler` to manage the retry for
 /// an individual request.
+///
+/// With [`RetryMode::Adaptive`], `CrossRequestRetryState` also holds a client side rate limiter that
+/// every attempt waits on.
 #[derive(Debug)]
 pub struct Standard {
     config: Config,
@@ -109,13 +347,33 @@ impl Standard {
     /// Construct a new standard retry policy from the given policy configuration.
     pub fn new(config: Config) -> Self {
         Self {
-            shared_state: CrossRequestRetryState::new(config.initial_retry_tokens),
+            shared_state: CrossRequestRetryState::new(config.initial_retry_tokens, config.mode),
             config,
         }
     }
 
+    /// The retry quota currently available to the requests of the client
+    pub fn retry_quota(&self) -> usize {
+        self.shared_state.retry_quota()
+    }
+
+    /// A snapshot of the retry state shared by the requests of the client
+    pub fn retry_state(&self) -> RetryState {
+        RetryState {
+            quota_available: self.retry_quota(),
+            quota_capacity: self.config.initial_retry_tokens,
+            max_attempts: self.config.max_attempts,
+        }
+    }
+
     /// Set the configuration for this retry policy.
     pub fn with_config(&mut self, config: Config) -> &mut Self {
+        if config.initial_retry_tokens != self.config.initial_retry_tokens {
+            *self.shared_state.quota_available.lock().unwrap() = config.initial_retry_tokens;
+        }
+        if config.mode != self.config.mode {
+            self.shared_state.rate_limiter = rate_limiter(config.mode);
+        }
         self.config = config;
         self
     }
@@ -131,6 +389,15 @@ impl NewRequestPolicy for Standard {
             config: self.config.clone(),
         }
     }
+
+    fn acquire_send_token(&self) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>> {
+        let rate_limiter = self.shared_state.rate_limiter.clone();
+        Box::pin(async move {
+            if let Some(rate_limiter) = rate_limiter {
+                rate_limiter.acquire().await;
+            }
+        })
+    }
 }
 
 impl Default for Standard {
@@ -139,6 +406,34 @@ impl Default for Standard {
     }
 }
 
+/// A snapshot of the retry state of a client, see [`Standard::retry_state`]
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryState {
+    quota_available: usize,
+    quota_capacity: usize,
+    max_attempts: u32,
+}
+
+impl RetryState {
+    /// The retry quota available to the requests of the client
+    ///
+    /// Each retry consumes some quota, which is given back when a request succeeds.
+    pub fn quota_available(&self) -> usize {
+        self.quota_available
+    }
+
+    /// The retry quota of the client when no request failed
+    pub fn quota_capacity(&self) -> usize {
+        self.quota_capacity
+    }
+
+    /// The maximum number of attempts of each request, including the initial attempt
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+}
+
 #[derive(Default, Clone, Debug)]
 struct RequestLocalRetryState {
     attempts: u32,
@@ -161,18 +456,31 @@ struct RetryPartition(Cow<'static, str>); */
 #[derive(Clone, Debug)]
 struct CrossRequestRetryState {
     quota_available: Arc<Mutex<usize>>,
+    rate_limiter: Option<ClientRateLimiter>,
+}
+
+fn rate_limiter(mode: RetryMode) -> Option<ClientRateLimiter> {
+    match mode {
+        RetryMode::Standard => None,
+        RetryMode::Adaptive => Some(ClientRateLimiter::new()),
+    }
 }
 
 // clippy is upset that we didn't use AtomicUsize here, but doing so makes the code
 // significantly more complicated for negligible benefit.
 #[allow(clippy::mutex_atomic)]
 impl CrossRequestRetryState {
-    pub fn new(initial_quota: usize) -> Self {
+    pub fn new(initial_quota: usize, mode: RetryMode) -> Self {
         Self {
             quota_available: Arc::new(Mutex::new(initial_quota)),
+            rate_limiter: rate_limiter(mode),
         }
     }
 
+    fn retry_quota(&self) -> usize {
+        *self.quota_available.lock().unwrap()
+    }
+
     fn quota_release(&self, value: Option<usize>, config: &Config) {
         let mut quota = self.quota_available.lock().unwrap();
         *quota += value.unwrap_or(config.no_retry_increment);
@@ -210,14 +518,40 @@ pub struct RetryHandler {
     config: Config,
 }
 
-#[cfg(test)]
 impl RetryHandler {
-    fn retry_quota(&self) -> usize {
-        *self.shared.quota_available.lock().unwrap()
+    /// The retry quota currently available to the requests of the client
+    pub fn retry_quota(&self) -> usize {
+        self.shared.retry_quota()
+    }
+
+    /// The number of attempts made so far with this handler, including the initial attempt
+    pub fn attempts(&self) -> u32 {
+        self.local.attempts + 1
+    }
+
+    fn has_attempts_left(&self) -> bool {
+        self.local.attempts + 1 < self.config.max_attempts
+    }
+
+    /// Retry after `backoff`, as the service explicitly asked
+    ///
+    /// Explicit retries don't consume retry quota, but they count towards the maximum attempts so
+    /// that a service can't make a request retry forever.
+    fn explicit_retry(&self, backoff: Duration) -> Option<(Self, Duration)> {
+        if !self.has_attempts_left() {
+            return None;
+        }
+        let next = RetryHandler {
+            local: RequestLocalRetryState {
+                attempts: self.local.attempts + 1,
+                last_quota_usage: self.local.last_quota_usage,
+            },
+            shared: self.shared.clone(),
+            config: self.config.clone(),
+        };
+        Some((next, backoff))
     }
-}
 
-impl RetryHandler {
     /// Determine the correct response given `retry_kind`
     ///
     /// If a retry is specified, this function returns `(next, backoff_duration)`
@@ -230,7 +564,7 @@ impl RetryHandler {
                 return None;
             }
             Err(e) => {
-                if self.local.attempts == self.config.max_retries - 1 {
+                if !self.has_attempts_left() {
                     return None;
                 }
                 self.shared.quota_acquire(&e, &self.config)?
@@ -274,17 +608,47 @@ where
         req: &Operation<Handler, R>,
         result: Result<&SdkSuccess<T>, &SdkError<E>>,
     ) -> Option<Self::Future> {
-        let policy = req.retry_policy();
-        let retry = policy.classify(result);
-        let (next, dur) = match retry {
-            RetryKind::Explicit(dur) => (self.clone(), dur),
-            RetryKind::NotRetryable => return None,
-            RetryKind::Error(err) => self.attempt_retry(Err(err))?,
-            _ => return None,
+        let overridden;
+        let handler = match req.config().get::<OperationRetryConfig>() {
+            Some(operation_config) => {
+                let mut next = self.clone();
+                operation_config.apply(&mut next.config);
+                overridden = next;
+                &overridden
+            }
+            None => self,
         };
+        let retry = match result {
+            // timeouts are retried even if the classifier of the operation doesn't know them
+            Err(err)
+                if matches!(
+                    TimeoutError::find(err).map(TimeoutError::kind),
+                    Some(TimeoutKind::Connect) | Some(TimeoutKind::Attempt)
+                ) =>
+            {
+                RetryKind::Error(ErrorKind::TransientError)
+            }
+            _ => req.retry_policy().classify(result),
+        };
+        if let Some(rate_limiter) = &self.shared.rate_limiter {
+            rate_limiter.update(retry == RetryKind::Error(ErrorKind::ThrottlingError));
+        }
+        let next = match retry {
+            RetryKind::Explicit(dur) => handler.explicit_retry(dur),
+            RetryKind::Error(err) => handler.attempt_retry(Err(err)),
+            _ => None,
+        };
+        if let Some(recorder) = req.config().get::<AttemptRecorder>() {
+            recorder.record(retry.clone(), next.as_ref().map(|(_, dur)| *dur));
+        }
+        let (next, dur) = next?;
 
+        let rate_limiter = self.shared.rate_limiter.clone();
         let fut = async move {
             tokio::time::sleep(dur).await;
+            if let Some(rate_limiter) = rate_limiter {
+                rate_limiter.acquire().await;
+            }
             next
         }
         .instrument(tracing::info_span!("retry", kind = &debug(retry)));
@@ -302,9 +666,23 @@ fn check_send_sync<T: Send>(t: T) -> T {
 
 #[cfg(test)]
 mod test {
-    use crate::retry::{Config, NewRequestPolicy, RetryHandler, Standard};
-    use smithy_types::retry::ErrorKind;
+    use crate::retry::{
+        AttemptRecorder, Config, NewRequestPolicy, OperationRetryConfig, RequestAttempts,
+        RetryHandler, RetryMode, Standard,
+    };
+    use crate::static_tests::TestOperation;
+    use crate::static_tests::TestOperationError;
+    use crate::timeout::{self, timeout_failure, TimeoutError, TimeoutKind};
+    use crate::{Builder, SdkError, SdkSuccess};
+    use smithy_http::body::SdkBody;
+    use smithy_http::operation::{self, Operation};
+    use smithy_http::result::ConnectorError;
+    use smithy_http::retry::ClassifyResponse;
+    use smithy_types::retry::{ErrorKind, RetryKind};
+    use std::error::Error;
     use std::time::Duration;
+    use tower::layer::util::Identity;
+    use tower::retry::Policy;
 
     fn assert_send_sync<T: Send + Sync>() {}
 
@@ -375,7 +753,7 @@ mod test {
     #[test]
     fn backoff_timing() {
         let mut conf = test_config();
-        conf.max_retries = 5;
+        conf.max_attempts = 5;
         let policy = Standard::new(conf).new_request_policy();
         let (policy, dur) = policy
             .attempt_retry(Err(ErrorKind::ServerError))
@@ -409,7 +787,7 @@ mod test {
     #[test]
     fn max_backoff_time() {
         let mut conf = test_config();
-        conf.max_retries = 5;
+        conf.max_attempts = 5;
         conf.max_backoff = Duration::from_secs(3);
         let policy = Standard::new(conf).new_request_policy();
         let (policy, dur) = policy
@@ -440,4 +818,246 @@ mod test {
         assert!(no_retry.is_none());
         assert_eq!(policy.retry_quota(), 480);
     }
+
+    #[tokio::test]
+    async fn adaptive_mode() {
+        let mut policy = Standard::new(test_config().with_mode(RetryMode::Adaptive));
+        assert!(policy.shared_state.rate_limiter.is_some());
+        // requests aren't delayed until a request has been throttled
+        tokio::time::timeout(Duration::from_millis(1), policy.acquire_send_token())
+            .await
+            .expect("no delay");
+
+        policy.with_config(test_config());
+        assert!(policy.shared_state.rate_limiter.is_none());
+    }
+
+    #[test]
+    fn config_setters() {
+        let mut policy = Standard::new(test_config());
+        policy.with_config(
+            test_config()
+                .with_max_attempts(2)
+                .with_initial_retry_tokens(10)
+                .with_retry_cost(10),
+        );
+        let policy = policy.new_request_policy();
+        assert_eq!(policy.retry_quota(), 10);
+        let (policy, _) = policy
+            .attempt_retry(Err(ErrorKind::ServerError))
+            .expect("should retry");
+        assert_eq!(policy.retry_quota(), 0);
+        assert!(policy.attempt_retry(Err(ErrorKind::ServerError)).is_none());
+    }
+
+    #[test]
+    fn retries_disabled() {
+        let policy = Standard::new(test_config().disable_retries()).new_request_policy();
+        assert!(policy.attempt_retry(Err(ErrorKind::ServerError)).is_none());
+        assert_eq!(policy.retry_quota(), 500);
+    }
+
+    #[derive(Clone)]
+    struct Classify(fn() -> RetryKind);
+
+    impl ClassifyResponse<SdkSuccess<()>, SdkError<TestOperationError>> for Classify {
+        fn classify(&self, _: Result<&SdkSuccess<()>, &SdkError<TestOperationError>>) -> RetryKind {
+            (self.0)()
+        }
+    }
+
+    fn operation(retry_kind: fn() -> RetryKind) -> Operation<(), Classify> {
+        let request = operation::Request::new(http::Request::new(SdkBody::from("")));
+        Operation::new(request, ()).with_retry_policy(Classify(retry_kind))
+    }
+
+    /// The number of attempts of `operation` with `policy` if every attempt fails
+    async fn attempts(policy: RetryHandler, operation: &Operation<(), Classify>) -> u32 {
+        let err = SdkError::ConstructionFailure("failed".into());
+        let mut policy = policy;
+        let mut attempts = 1;
+        while let Some(next) = policy.retry(operation, Err(&err)) {
+            policy = next.await;
+            attempts += 1;
+        }
+        attempts
+    }
+
+    #[tokio::test]
+    async fn operation_overrides() {
+        tokio::time::pause();
+        let policy = Standard::new(test_config());
+        let error = || RetryKind::Error(ErrorKind::ServerError);
+        assert_eq!(
+            attempts(policy.new_request_policy(), &operation(error)).await,
+            3
+        );
+
+        let mut reads = operation(error);
+        reads
+            .config_mut()
+            .insert(OperationRetryConfig::new().with_max_attempts(10));
+        assert_eq!(attempts(policy.new_request_policy(), &reads).await, 10);
+
+        let mut writes = operation(error);
+        writes
+            .config_mut()
+            .insert(OperationRetryConfig::new().disable_retries());
+        assert_eq!(attempts(policy.new_request_policy(), &writes).await, 1);
+
+        // explicit retries count towards the maximum attempts, but don't consume retry quota
+        let explicit = || RetryKind::Explicit(Duration::from_secs(1));
+        let quota = policy.retry_quota();
+        assert_eq!(
+            attempts(policy.new_request_policy(), &operation(explicit)).await,
+            3
+        );
+        assert_eq!(policy.retry_quota(), quota);
+
+        let mut explicit_writes = operation(explicit);
+        explicit_writes
+            .config_mut()
+            .insert(OperationRetryConfig::new().disable_retries());
+        assert_eq!(
+            attempts(policy.new_request_policy(), &explicit_writes).await,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn explicit_retries_disabled() {
+        tokio::time::pause();
+        let policy = Standard::new(test_config().disable_retries());
+        let explicit = operation(|| RetryKind::Explicit(Duration::from_secs(1)));
+        assert_eq!(attempts(policy.new_request_policy(), &explicit).await, 1);
+    }
+
+    #[tokio::test]
+    async fn record_attempts() {
+        tokio::time::pause();
+        let policy = Standard::new(test_config());
+        let mut operation = operation(|| RetryKind::Error(ErrorKind::ServerError));
+        let recorder = AttemptRecorder::new();
+        operation.config_mut().insert(recorder.clone());
+        assert_eq!(attempts(policy.new_request_policy(), &operation).await, 3);
+
+        let recorded = recorder.attempts();
+        assert_eq!(recorded.attempts(), 3);
+        assert_eq!(
+            recorded.backoffs(),
+            &[Duration::from_secs(1), Duration::from_secs(2)]
+        );
+        assert_eq!(
+            recorded.last_retry_kind(),
+            Some(&RetryKind::Error(ErrorKind::ServerError))
+        );
+        // the two retries consumed quota that wasn't given back
+        assert_eq!(policy.retry_quota(), 490);
+    }
+
+    #[tokio::test]
+    async fn timeouts_are_retried() {
+        tokio::time::pause();
+        let policy = Standard::new(test_config());
+        // the classifier of the operation doesn't see timed out attempts
+        let operation = operation(|| RetryKind::NotRetryable);
+        let timed_out = |kind| {
+            let err = timeout_failure::<TestOperationError>(kind, Duration::from_secs(1));
+            policy
+                .new_request_policy()
+                .retry(&operation, Err(&err))
+                .is_some()
+        };
+        assert!(timed_out(TimeoutKind::Connect));
+        assert!(timed_out(TimeoutKind::Attempt));
+        assert!(!timed_out(TimeoutKind::Total));
+    }
+
+    #[test]
+    fn retry_state() {
+        let client = Builder::new()
+            .middleware(Identity::new())
+            .connector_fn(|_| async { unreachable!() })
+            .build()
+            .with_retry_config(test_config().with_max_attempts(5));
+        let state = client.retry_state();
+        assert_eq!(state.quota_available(), 500);
+        assert_eq!(state.quota_capacity(), 500);
+        assert_eq!(state.max_attempts(), 5);
+        assert_eq!(client.retry_policy().retry_state(), state);
+    }
+
+    #[tokio::test]
+    async fn attempts_of_failed_dispatch() {
+        tokio::time::pause();
+        let operation = |recorder: &AttemptRecorder| {
+            let request = operation::Request::new(http::Request::new(SdkBody::from("")));
+            let mut operation = Operation::new(request, TestOperation)
+                .with_retry_policy(Classify(|| RetryKind::Error(ErrorKind::TransientError)));
+            operation.config_mut().insert(recorder.clone());
+            operation
+        };
+
+        let client = Builder::new()
+            .middleware(Identity::new())
+            .connector_fn(|_| async { Err(ConnectorError::transient("connection reset").into()) })
+            .build()
+            .with_retry_config(test_config());
+        let recorder = AttemptRecorder::new();
+        let err = client
+            .call(operation(&recorder))
+            .await
+            .expect_err("all attempts fail");
+        // there is no response to attach the attempts to
+        assert!(RequestAttempts::find(&err).is_none());
+        let attempts = recorder.attempts();
+        assert_eq!(attempts.attempts(), 3);
+        assert_eq!(
+            attempts.backoffs(),
+            &[Duration::from_secs(1), Duration::from_secs(2)]
+        );
+        // the error of the connector is left as is
+        match &err {
+            SdkError::DispatchFailure(err) => {
+                let err = err
+                    .downcast_ref::<ConnectorError>()
+                    .expect("connector error");
+                assert_eq!(
+                    err.source().map(|err| err.to_string()),
+                    Some("connection reset".to_string())
+                );
+            }
+            other => panic!("expected a dispatch failure, got {:?}", other),
+        }
+
+        // the total timeout elapses during the backoff before the third attempt
+        let client = client
+            .with_timeout_config(timeout::Config::new().with_total_timeout(Duration::from_secs(2)));
+        let recorder = AttemptRecorder::new();
+        let err = client
+            .call(operation(&recorder))
+            .await
+            .expect_err("times out");
+        assert!(TimeoutError::find(&err).is_some());
+        assert_eq!(recorder.attempts().attempts(), 2);
+
+        // the total timeout elapses during the first attempt
+        let client = Builder::new()
+            .middleware(Identity::new())
+            .connector_fn(|_| async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok(http::Response::new(SdkBody::from("")))
+            })
+            .build()
+            .with_timeout_config(timeout::Config::new().with_total_timeout(Duration::from_secs(2)));
+        let recorder = AttemptRecorder::new();
+        let err = client
+            .call(operation(&recorder))
+            .await
+            .expect_err("times out");
+        assert!(TimeoutError::find(&err).is_some());
+        let attempts = recorder.attempts();
+        assert_eq!(attempts.attempts(), 1);
+        assert!(attempts.last_retry_kind().is_none());
+    }
 }
@@ -0,0 +1,294 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Client side rate limiting for the adaptive retry mode
+//!
+//! The sending rate of a client is adjusted with CUBIC (the congestion control algorithm used by
+//! TCP): it is cut down when a request is throttled, and grows back along a cubic curve centered on
+//! the rate at which throttling last happened. Requests are sent through a token bucket filled at the
+//! calculated rate, so that a client that is being throttled slows down before it sends requests
+//! rather than after they fail.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The rate can't go below half a request per second
+const MIN_FILL_RATE: f64 = 0.5;
+const MIN_CAPACITY: f64 = 1.0;
+/// Weight of the latest measurement in the measured sending rate
+const SMOOTH: f64 = 0.8;
+/// Multiplicative decrease of the rate when a request is throttled
+const BETA: f64 = 0.7;
+/// Scale of the cubic growth of the rate
+const SCALE_CONSTANT: f64 = 0.4;
+
+/// Rate limiter shared by the requests of a client
+#[derive(Clone, Debug)]
+pub(crate) struct ClientRateLimiter {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    /// The time rates are measured from
+    start: Instant,
+    fill_rate: f64,
+    max_capacity: f64,
+    current_capacity: f64,
+    last_timestamp: Option<f64>,
+    /// The token bucket is only enabled once a request has been throttled
+    enabled: bool,
+    measured_tx_rate: f64,
+    last_tx_rate_bucket: f64,
+    request_count: u64,
+    last_max_rate: f64,
+    last_throttle_time: f64,
+    time_window: f64,
+}
+
+impl ClientRateLimiter {
+    pub(crate) fn new() -> Self {
+        let start = Instant::now();
+        ClientRateLimiter {
+            inner: Arc::new(Mutex::new(Inner::new(start))),
+        }
+    }
+
+    /// Take a token from the bucket, waiting for it if the bucket is empty
+    pub(crate) async fn acquire(&self) {
+        let delay = {
+            let mut inner = self.inner.lock().unwrap();
+            let now = inner.seconds_since_start(Instant::now());
+            inner.acquire(1.0, now)
+        };
+        if let Some(delay) = delay {
+            tracing::debug!(delay = ?delay, "waiting for the client side rate limiter");
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Update the sending rate with the response to a request
+    pub(crate) fn update(&self, throttled: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        let now = inner.seconds_since_start(Instant::now());
+        inner.update_sending_rate(throttled, now);
+    }
+}
+
+impl Inner {
+    fn new(start: Instant) -> Self {
+        Inner {
+            start,
+            fill_rate: 0.0,
+            max_capacity: 0.0,
+            current_capacity: 0.0,
+            last_timestamp: None,
+            enabled: false,
+            measured_tx_rate: 0.0,
+            last_tx_rate_bucket: 0.0,
+            request_count: 0,
+            last_max_rate: 0.0,
+            last_throttle_time: 0.0,
+            time_window: 0.0,
+        }
+    }
+
+    fn seconds_since_start(&self, now: Instant) -> f64 {
+        now.duration_since(self.start).as_secs_f64()
+    }
+
+    /// Reserve `amount` tokens, returning how long to wait for them to be available
+    ///
+    /// Tokens are reserved even when the bucket is empty: the capacity goes negative so that
+    /// requests that come later wait for the requests that came before them.
+    fn acquire(&mut self, amount: f64, now: f64) -> Option<Duration> {
+        if !self.enabled {
+            return None;
+        }
+        self.refill(now);
+        let delay = if amount > self.current_capacity {
+            Some(Duration::from_secs_f64(
+                (amount - self.current_capacity) / self.fill_rate,
+            ))
+        } else {
+            None
+        };
+        self.current_capacity -= amount;
+        delay
+    }
+
+    fn refill(&mut self, now: f64) {
+        if let Some(last_timestamp) = self.last_timestamp {
+            let fill_amount = (now - last_timestamp) * self.fill_rate;
+            self.current_capacity = self.max_capacity.min(self.current_capacity + fill_amount);
+        }
+        self.last_timestamp = Some(now);
+    }
+
+    fn update_sending_rate(&mut self, throttled: bool, now: f64) {
+        self.update_measured_rate(now);
+        let calculated_rate = if throttled {
+            let rate_to_use = if self.enabled {
+                self.measured_tx_rate.min(self.fill_rate)
+            } else {
+                self.measured_tx_rate
+            };
+            self.last_max_rate = rate_to_use;
+            self.calculate_time_window();
+            self.last_throttle_time = now;
+            self.enabled = true;
+            cubic_throttle(rate_to_use)
+        } else {
+            self.calculate_time_window();
+            self.cubic_success(now)
+        };
+        let new_rate = calculated_rate.min(2.0 * self.measured_tx_rate);
+        self.update_fill_rate(new_rate, now);
+    }
+
+    fn update_fill_rate(&mut self, new_rate: f64, now: f64) {
+        self.refill(now);
+        self.fill_rate = new_rate.max(MIN_FILL_RATE);
+        self.max_capacity = new_rate.max(MIN_CAPACITY);
+        self.current_capacity = self.current_capacity.min(self.max_capacity);
+    }
+
+    /// Measure the sending rate of the client over buckets of half a second
+    fn update_measured_rate(&mut self, now: f64) {
+        let time_bucket = (now * 2.0).floor() / 2.0;
+        self.request_count += 1;
+        if time_bucket > self.last_tx_rate_bucket {
+            let current_rate = self.request_count as f64 / (time_bucket - self.last_tx_rate_bucket);
+            self.measured_tx_rate = current_rate * SMOOTH + self.measured_tx_rate * (1.0 - SMOOTH);
+            self.request_count = 0;
+            self.last_tx_rate_bucket = time_bucket;
+        }
+    }
+
+    /// The time it takes for the rate to grow back to the rate at which throttling last happened
+    fn calculate_time_window(&mut self) {
+        self.time_window = (self.last_max_rate * (1.0 - BETA) / SCALE_CONSTANT).cbrt();
+    }
+
+    fn cubic_success(&self, now: f64) -> f64 {
+        let dt = now - self.last_throttle_time;
+        SCALE_CONSTANT * (dt - self.time_window).powi(3) + self.last_max_rate
+    }
+}
+
+fn cubic_throttle(rate_to_use: f64) -> f64 {
+    rate_to_use * BETA
+}
+
+#[cfg(test)]
+mod test {
+    use crate::retry::adaptive::{cubic_throttle, ClientRateLimiter, Inner};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    /// The timer rounds deadlines up to the next millisecond
+    fn assert_elapsed(start: Instant, expected: Duration) {
+        let elapsed = Instant::now() - start;
+        assert!(
+            elapsed >= expected && elapsed < expected + Duration::from_millis(10),
+            "{:?}",
+            elapsed
+        );
+    }
+
+    #[test]
+    fn cubic_success() {
+        let mut inner = Inner::new(Instant::now());
+        inner.last_max_rate = 10.0;
+        inner.calculate_time_window();
+        let expected = [
+            7.0,
+            9.64893600966,
+            10.000030849917364,
+            10.453284520772092,
+            13.408697022224185,
+            21.26626835427364,
+            36.425998516920465,
+            61.28788751016465,
+        ];
+        for (time, rate) in expected.iter().enumerate() {
+            assert_close(inner.cubic_success(time as f64), *rate);
+        }
+    }
+
+    #[test]
+    fn cubic_throttle_and_recovery() {
+        let mut inner = Inner::new(Instant::now());
+        inner.last_max_rate = 10.0;
+        inner.calculate_time_window();
+        // the rate drops on throttling, then grows back to the rate at the last throttle
+        assert_close(cubic_throttle(10.0), 7.0);
+        inner.last_throttle_time = 5.0;
+        assert_close(inner.cubic_success(5.0), 7.0);
+        assert!(inner.cubic_success(6.0) < 10.0);
+        assert!(inner.cubic_success(7.5) > 10.0);
+    }
+
+    #[test]
+    fn token_bucket_enabled_by_throttling() {
+        let mut inner = Inner::new(Instant::now());
+        for i in 0..10 {
+            assert_eq!(inner.acquire(1.0, i as f64 * 0.1), None);
+            inner.update_sending_rate(false, i as f64 * 0.1);
+        }
+        assert!(!inner.enabled);
+
+        inner.update_sending_rate(true, 1.0);
+        assert!(inner.enabled);
+        assert!(inner.fill_rate < inner.measured_tx_rate);
+        // a burst of requests exhausts the bucket and has to wait
+        let delays = (0..10)
+            .map(|_| inner.acquire(1.0, 1.0).unwrap_or_default())
+            .collect::<Vec<_>>();
+        assert!(delays[9] > delays[0]);
+        assert!(delays[9] > Duration::from_secs(1));
+    }
+
+    #[test]
+    fn rate_never_drops_below_minimum() {
+        let mut inner = Inner::new(Instant::now());
+        for i in 0..20 {
+            inner.update_sending_rate(true, i as f64 * 10.0);
+        }
+        assert_close(inner.fill_rate, 0.5);
+        // with an empty bucket, a token takes two seconds to fill
+        inner.current_capacity = 0.0;
+        inner.last_timestamp = Some(200.0);
+        assert_eq!(inner.acquire(1.0, 200.0), Some(Duration::from_secs(2)));
+        // the next request waits for the previous one
+        assert_eq!(inner.acquire(1.0, 200.0), Some(Duration::from_secs(4)));
+    }
+
+    #[tokio::test]
+    async fn requests_wait_for_tokens() {
+        tokio::time::pause();
+        let limiter = ClientRateLimiter::new();
+        {
+            let mut inner = limiter.inner.lock().unwrap();
+            inner.enabled = true;
+            inner.update_fill_rate(1.0, 0.0);
+        }
+        let start = Instant::now();
+        limiter.acquire().await;
+        assert_elapsed(start, Duration::from_secs(1));
+        limiter.acquire().await;
+        assert_elapsed(start, Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,677 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Mock connector that responds to requests according to rules
+//!
+//! Unlike [`TestConnection`](crate::test_connection::TestConnection), which serves a fixed series
+//! of responses, a [`MockConnection`] serves each request with the first [`Rule`] that matches it.
+//! Rules match on the method, path, query, headers and body of requests, and can respond with a
+//! fixed response, a response computed from the request, or a connector error (which surfaces as
+//! `SdkError::DispatchFailure`):
+//! ```rust
+//! use smithy_client::test_connection::mock::{MockConnection, Rule};
+//! use std::time::Duration;
+//!
+//! let conn = MockConnection::new()
+//!     .with_rule(
+//!         Rule::new()
+//!             .method("POST")
+//!             .header("x-amz-target", "TrentService.GenerateRandom")
+//!             .respond_with(http::Response::builder().status(200).body("{}").unwrap())
+//!             .times(2),
+//!     )
+//!     .with_rule(
+//!         Rule::new()
+//!             .path("/slow")
+//!             .delay(Duration::from_secs(5))
+//!             .fail_with(|| "connection reset".into()),
+//!     );
+//! let client = smithy_client::Client::from(conn.clone());
+//! // ...send requests through `client`...
+//! # conn.requests();
+//! ```
+//! Requests that no rule matches fail with an error that explains why each rule didn't match.
+//! They are also remembered, so that [`MockConnection::assert_done`] reports them even if the
+//! error was handled by the code under test.
+
+use bytes::Bytes;
+use smithy_http::body::SdkBody;
+use smithy_http::result::ConnectorError;
+use std::fmt::Write;
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::BoxError;
+
+type Matcher = Box<dyn Fn(&http::Request<SdkBody>) -> Result<(), String> + Send + Sync>;
+type Respond =
+    Arc<dyn Fn(&http::Request<SdkBody>) -> Result<http::Response<SdkBody>, BoxError> + Send + Sync>;
+
+/// How many times a rule responds
+#[derive(Clone, Copy, Debug)]
+enum Times {
+    Exactly(usize),
+    Always,
+}
+
+/// Expectation of a [`MockConnection`]: the requests it matches and how it responds to them
+///
+/// A rule matches every request until it is narrowed down with matchers such as
+/// [`Rule::method`], and responds once (see [`Rule::times`]) with an empty `200` response until a
+/// response is set with [`Rule::respond_with`], [`Rule::respond_with_fn`] or [`Rule::fail_with`].
+pub struct Rule {
+    description: Option<String>,
+    matchers: Vec<Matcher>,
+    respond: Respond,
+    times: Times,
+    served: usize,
+    delay: Option<Duration>,
+}
+
+impl std::fmt::Debug for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Rule")
+            .field("description", &self.description)
+            .field("times", &self.times)
+            .field("served", &self.served)
+            .field("delay", &self.delay)
+            .finish()
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rule {
+    pub fn new() -> Self {
+        Rule {
+            description: None,
+            matchers: Vec::new(),
+            respond: Arc::new(|_| Ok(http::Response::new(SdkBody::empty()))),
+            times: Times::Exactly(1),
+            served: 0,
+            delay: None,
+        }
+    }
+
+    /// Name the rule in the errors of requests that don't match
+    pub fn described_as(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Match requests with the given method
+    pub fn method(self, method: &'static str) -> Self {
+        self.matching(move |request| expect_eq("method", method, request.method().as_str()))
+    }
+
+    /// Match requests with the given path
+    pub fn path(self, path: &'static str) -> Self {
+        self.matching(move |request| expect_eq("path", path, request.uri().path()))
+    }
+
+    /// Match requests with the query parameter `name` set to `value`
+    ///
+    /// Query parameters are compared as they appear in the URI, ie. percent-encoded.
+    pub fn query_param(self, name: &'static str, value: &'static str) -> Self {
+        self.matching(move |request| {
+            let actual = request
+                .uri()
+                .query()
+                .unwrap_or_default()
+                .split('&')
+                .find_map(|param| {
+                    let mut parts = param.splitn(2, '=');
+                    match (parts.next(), parts.next()) {
+                        (Some(key), value) if key == name => Some(value.unwrap_or_default()),
+                        _ => None,
+                    }
+                });
+            match actual {
+                Some(actual) => expect_eq(&format!("query param `{}`", name), value, actual),
+                None => Err(format!("query param `{}`: missing", name)),
+            }
+        })
+    }
+
+    /// Match requests with the header `name` set to `value`
+    pub fn header(self, name: &'static str, value: &'static str) -> Self {
+        self.matching(move |request| match request.headers().get(name) {
+            Some(actual) => expect_eq(
+                &format!("header `{}`", name),
+                value,
+                &String::from_utf8_lossy(actual.as_bytes()),
+            ),
+            None => Err(format!("header `{}`: missing", name)),
+        })
+    }
+
+    /// Match requests whose body satisfies `predicate`
+    ///
+    /// Streaming bodies never match since they can't be read without consuming them.
+    pub fn body(self, predicate: impl Fn(&[u8]) -> bool + Send + Sync + 'static) -> Self {
+        self.matching(move |request| match request.body().bytes() {
+            Some(body) if predicate(body) => Ok(()),
+            Some(body) => Err(format!(
+                "body: predicate failed for {:?}",
+                String::from_utf8_lossy(body)
+            )),
+            None => Err("body: streaming bodies can't be matched".to_string()),
+        })
+    }
+
+    /// Match requests that satisfy `predicate`, explained by `description` when they don't
+    pub fn when(
+        self,
+        description: &'static str,
+        predicate: impl Fn(&http::Request<SdkBody>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.matching(move |request| {
+            if predicate(request) {
+                Ok(())
+            } else {
+                Err(description.to_string())
+            }
+        })
+    }
+
+    fn matching(
+        mut self,
+        matcher: impl Fn(&http::Request<SdkBody>) -> Result<(), String> + Send + Sync + 'static,
+    ) -> Self {
+        self.matchers.push(Box::new(matcher));
+        self
+    }
+
+    /// Respond with `response`
+    pub fn respond_with<B>(self, response: http::Response<B>) -> Self
+    where
+        Bytes: From<B>,
+    {
+        let (parts, body) = response.into_parts();
+        let (status, headers, body) = (parts.status, parts.headers, Bytes::from(body));
+        self.respond_with_fn(move |_| {
+            let mut response = http::Response::new(SdkBody::from(body.clone()));
+            *response.status_mut() = status;
+            *response.headers_mut() = headers.clone();
+            response
+        })
+    }
+
+    /// Respond with the response computed by `respond` from the request
+    ///
+    /// `respond` is called without holding the state of the connection, so it may use the
+    /// connection, eg. to look at [`MockConnection::requests`].
+    pub fn respond_with_fn(
+        mut self,
+        respond: impl Fn(&http::Request<SdkBody>) -> http::Response<SdkBody> + Send + Sync + 'static,
+    ) -> Self {
+        self.respond = Arc::new(move |request| Ok(respond(request)));
+        self
+    }
+
+    /// Fail with the connector error made by `error`
+    ///
+    /// Errors are returned as transient [`ConnectorError`]s (eg. a connection reset), which are
+    /// retried. Return a [`ConnectorError`] from `error` to classify the failure otherwise.
+    pub fn fail_with(mut self, error: impl Fn() -> BoxError + Send + Sync + 'static) -> Self {
+        self.respond = Arc::new(move |_| {
+            let err = error();
+            if err.is::<ConnectorError>() {
+                Err(err)
+            } else {
+                Err(ConnectorError::transient(err).into())
+            }
+        });
+        self
+    }
+
+    /// Respond `times` times, after which the rule no longer matches
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Times::Exactly(times);
+        self
+    }
+
+    /// Respond to every request that matches, however many there are
+    pub fn always(mut self) -> Self {
+        self.times = Times::Always;
+        self
+    }
+
+    /// Wait for `delay` before responding
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    fn exhausted(&self) -> bool {
+        match self.times {
+            Times::Exactly(times) => self.served >= times,
+            Times::Always => false,
+        }
+    }
+
+    fn pending(&self) -> Option<usize> {
+        match self.times {
+            Times::Exactly(times) if self.served < times => Some(times - self.served),
+            _ => None,
+        }
+    }
+
+    fn mismatches(&self, request: &http::Request<SdkBody>) -> Vec<String> {
+        self.matchers
+            .iter()
+            .filter_map(|matcher| matcher(request).err())
+            .collect()
+    }
+
+    fn name(&self, index: usize) -> String {
+        match &self.description {
+            Some(description) => format!("rule {} ({})", index, description),
+            None => format!("rule {}", index),
+        }
+    }
+}
+
+fn expect_eq(what: &str, expected: &str, actual: &str) -> Result<(), String> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(format!(
+            "{}: expected {:?}, got {:?}",
+            what, expected, actual
+        ))
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    rules: Vec<Rule>,
+    in_order: bool,
+    requests: Vec<http::Request<SdkBody>>,
+    unmatched: Vec<String>,
+}
+
+impl State {
+    /// Find the rule for `request`, or explain why no rule matches it
+    fn find_rule(&mut self, request: &http::Request<SdkBody>) -> Result<&mut Rule, String> {
+        let mut explanation = format!("no rule matches {} {}\n", request.method(), request.uri());
+        let mut found = None;
+        for (index, rule) in self.rules.iter().enumerate() {
+            if rule.exhausted() {
+                continue;
+            }
+            let mismatches = rule.mismatches(request);
+            if mismatches.is_empty() {
+                found = Some(index);
+                break;
+            }
+            let _ = writeln!(explanation, "  {}:", rule.name(index));
+            for mismatch in mismatches {
+                let _ = writeln!(explanation, "    {}", mismatch);
+            }
+            // the next rule is only considered once this one is exhausted
+            if self.in_order {
+                break;
+            }
+        }
+        if let Some(index) = found {
+            return Ok(&mut self.rules[index]);
+        }
+        if self.rules.iter().all(Rule::exhausted) {
+            explanation.push_str("  every rule has already responded\n");
+        }
+        Err(explanation)
+    }
+}
+
+/// Connector that responds to requests according to [rules](Rule)
+///
+/// See the [module documentation](crate::test_connection::mock).
+#[derive(Clone, Debug, Default)]
+pub struct MockConnection {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockConnection {
+    /// A connection without rules, with which every request fails
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule, which takes effect after the rules added before it
+    pub fn with_rule(self, rule: Rule) -> Self {
+        self.state.lock().unwrap().rules.push(rule);
+        self
+    }
+
+    /// Require the rules to respond in the order they were added: a request only matches the first
+    /// rule that hasn't responded as many times as it should
+    pub fn in_order(self) -> Self {
+        self.state.lock().unwrap().in_order = true;
+        self
+    }
+
+    /// The requests that were sent, whether a rule matched them or not
+    pub fn requests(&self) -> impl Deref<Target = Vec<http::Request<SdkBody>>> + '_ {
+        struct Requests<'a>(MutexGuard<'a, State>);
+        impl Deref for Requests<'_> {
+            type Target = Vec<http::Request<SdkBody>>;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0.requests
+            }
+        }
+        Requests(self.state.lock().unwrap())
+    }
+
+    /// Assert that every request matched a rule, and that every rule responded as many times as it
+    /// should have
+    pub fn assert_done(&self) {
+        let state = self.state.lock().unwrap();
+        let mut problems = state.unmatched.clone();
+        for (index, rule) in state.rules.iter().enumerate() {
+            if let Some(pending) = rule.pending() {
+                problems.push(format!(
+                    "{} expected {} more request(s)",
+                    rule.name(index),
+                    pending
+                ));
+            }
+        }
+        assert!(problems.is_empty(), "{}", problems.join("\n"));
+    }
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+impl tower::Service<http::Request<SdkBody>> for MockConnection {
+    type Response = http::Response<SdkBody>;
+    type Error = BoxError;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<SdkBody>) -> Self::Future {
+        let rule = {
+            let mut state = self.state.lock().unwrap();
+            match state.find_rule(&request) {
+                Ok(rule) => {
+                    rule.served += 1;
+                    Ok((rule.respond.clone(), rule.delay))
+                }
+                Err(explanation) => {
+                    state.unmatched.push(explanation.clone());
+                    Err(explanation)
+                }
+            }
+        };
+        // the state isn't locked while responding, so that `respond` can use the connection
+        let (result, delay) = match rule {
+            Ok((respond, delay)) => (respond(&request), delay),
+            Err(explanation) => (Err(explanation.into()), None),
+        };
+        self.state.lock().unwrap().requests.push(request);
+        Box::pin(async move {
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+            }
+            result
+        })
+    }
+}
+
+impl From<MockConnection> for crate::Client<MockConnection, tower::layer::util::Identity> {
+    fn from(conn: MockConnection) -> Self {
+        crate::Builder::new()
+            .middleware(tower::layer::util::Identity::new())
+            .connector(conn)
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_connection::mock::{MockConnection, Rule};
+    use smithy_http::body::SdkBody;
+    use smithy_http::result::ConnectorError;
+    use smithy_types::retry::ErrorKind;
+    use std::error::Error;
+    use std::time::Duration;
+    use tokio::time::Instant;
+    use tower::{BoxError, Service, ServiceExt};
+
+    fn request(method: &str, uri: &str, body: &'static str) -> http::Request<SdkBody> {
+        http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("x-amz-target", "Service.Operation")
+            .body(SdkBody::from(body))
+            .unwrap()
+    }
+
+    async fn send(
+        conn: &mut MockConnection,
+        request: http::Request<SdkBody>,
+    ) -> Result<http::Response<SdkBody>, BoxError> {
+        conn.ready().await.unwrap().call(request).await
+    }
+
+    fn status(response: Result<http::Response<SdkBody>, BoxError>) -> u16 {
+        response.expect("a rule should match").status().as_u16()
+    }
+
+    fn ok(status: u16) -> http::Response<&'static str> {
+        http::Response::builder().status(status).body("").unwrap()
+    }
+
+    #[tokio::test]
+    async fn match_requests() {
+        let mut conn = MockConnection::new()
+            .with_rule(
+                Rule::new()
+                    .method("PUT")
+                    .path("/bucket/key")
+                    .query_param("x-id", "PutObject")
+                    .respond_with(ok(201)),
+            )
+            .with_rule(
+                Rule::new()
+                    .header("x-amz-target", "Service.Operation")
+                    .body(|body| body.starts_with(b"{"))
+                    .respond_with(ok(202)),
+            );
+        assert_eq!(
+            status(send(&mut conn, request("POST", "https://example.com/", "{}")).await),
+            202
+        );
+        assert_eq!(
+            status(
+                send(
+                    &mut conn,
+                    request("PUT", "https://example.com/bucket/key?x-id=PutObject", "")
+                )
+                .await
+            ),
+            201
+        );
+        assert_eq!(conn.requests().len(), 2);
+        conn.assert_done();
+    }
+
+    #[tokio::test]
+    async fn respond_n_times() {
+        let mut conn = MockConnection::new()
+            .with_rule(Rule::new().respond_with(ok(500)).times(2))
+            .with_rule(Rule::new().respond_with(ok(200)));
+        for expected in &[500, 500, 200] {
+            assert_eq!(
+                status(send(&mut conn, request("GET", "https://example.com/", "")).await),
+                *expected
+            );
+        }
+        let err = send(&mut conn, request("GET", "https://example.com/", ""))
+            .await
+            .expect_err("every rule has responded");
+        assert!(err.to_string().contains("every rule has already responded"));
+    }
+
+    #[tokio::test]
+    async fn in_order() {
+        let mut conn = MockConnection::new()
+            .with_rule(Rule::new().path("/first").respond_with(ok(200)))
+            .with_rule(Rule::new().path("/second").respond_with(ok(200)))
+            .in_order();
+        let err = send(&mut conn, request("GET", "https://example.com/second", ""))
+            .await
+            .expect_err("the first rule must respond first");
+        assert_eq!(
+            err.to_string(),
+            "no rule matches GET https://example.com/second\n  rule 0:\n    path: expected \"/first\", got \"/second\"\n"
+        );
+        status(send(&mut conn, request("GET", "https://example.com/first", "")).await);
+        status(send(&mut conn, request("GET", "https://example.com/second", "")).await);
+    }
+
+    #[tokio::test]
+    async fn explain_mismatches() {
+        let mut conn = MockConnection::new()
+            .with_rule(
+                Rule::new()
+                    .described_as("GetItem")
+                    .method("POST")
+                    .header("x-amz-target", "DynamoDB_20120810.GetItem"),
+            )
+            .with_rule(
+                Rule::new()
+                    .query_param("list-type", "2")
+                    .when("is a request to S3", |req| {
+                        req.uri().host() == Some("s3.amazonaws.com")
+                    }),
+            );
+        let err = send(&mut conn, request("GET", "https://example.com/", ""))
+            .await
+            .expect_err("no rule should match");
+        assert_eq!(
+            err.to_string(),
+            "no rule matches GET https://example.com/
+  rule 0 (GetItem):
+    method: expected \"POST\", got \"GET\"
+    header `x-amz-target`: expected \"DynamoDB_20120810.GetItem\", got \"Service.Operation\"
+  rule 1:
+    query param `list-type`: missing
+    is a request to S3
+"
+        );
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "no rule matches GET https://example.com/")]
+    async fn assert_done_reports_unmatched_requests() {
+        let mut conn = MockConnection::new();
+        let _ = send(&mut conn, request("GET", "https://example.com/", "")).await;
+        conn.assert_done();
+    }
+
+    #[test]
+    #[should_panic(expected = "rule 0 expected 2 more request(s)")]
+    fn assert_done_reports_pending_rules() {
+        MockConnection::new()
+            .with_rule(Rule::new().times(2))
+            .assert_done();
+    }
+
+    #[tokio::test]
+    async fn custom_responses_and_failures() {
+        let mut conn = MockConnection::new()
+            .with_rule(Rule::new().path("/echo").respond_with_fn(|req| {
+                http::Response::new(SdkBody::from(req.body().bytes().unwrap().to_vec()))
+            }))
+            .with_rule(
+                Rule::new()
+                    .path("/reset")
+                    .fail_with(|| "connection reset".into())
+                    .always(),
+            );
+        let echo = send(
+            &mut conn,
+            request("POST", "https://example.com/echo", "hello"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(echo.body().bytes(), Some(&b"hello"[..]));
+        for _ in 0..2 {
+            let err = send(&mut conn, request("GET", "https://example.com/reset", ""))
+                .await
+                .expect_err("connection errors are injected");
+            let err = err
+                .downcast_ref::<ConnectorError>()
+                .expect("failures are connector errors");
+            assert_eq!(err.retryable_error_kind(), Some(ErrorKind::TransientError));
+            assert_eq!(err.source().unwrap().to_string(), "connection reset");
+        }
+        conn.assert_done();
+    }
+
+    #[tokio::test]
+    async fn classified_failures() {
+        let mut conn = MockConnection::new().with_rule(
+            Rule::new().fail_with(|| ConnectorError::other("invalid certificate").into()),
+        );
+        let err = send(&mut conn, request("GET", "https://example.com/", ""))
+            .await
+            .expect_err("connection errors are injected");
+        let err = err
+            .downcast_ref::<ConnectorError>()
+            .expect("failures are connector errors");
+        assert_eq!(err.retryable_error_kind(), None);
+        assert_eq!(err.source().unwrap().to_string(), "invalid certificate");
+    }
+
+    #[tokio::test]
+    async fn respond_with_the_connection() {
+        let conn = MockConnection::new();
+        let inner = conn.clone();
+        let mut conn = conn.with_rule(
+            Rule::new()
+                .respond_with_fn(move |_| {
+                    let sent = inner.requests().len() as u16;
+                    http::Response::builder()
+                        .status(200 + sent)
+                        .body(SdkBody::empty())
+                        .unwrap()
+                })
+                .times(2),
+        );
+        for expected in &[200, 201] {
+            assert_eq!(
+                status(send(&mut conn, request("GET", "https://example.com/", "")).await),
+                *expected
+            );
+        }
+        conn.assert_done();
+    }
+
+    #[tokio::test]
+    async fn delay() {
+        tokio::time::pause();
+        let mut conn = MockConnection::new().with_rule(
+            Rule::new()
+                .respond_with(ok(200))
+                .delay(Duration::from_secs(5)),
+        );
+        let start = Instant::now();
+        status(send(&mut conn, request("GET", "https://example.com/", "")).await);
+        assert!(Instant::now() - start >= Duration::from_secs(5));
+    }
+}
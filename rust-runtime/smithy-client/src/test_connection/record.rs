@@ -0,0 +1,745 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Recording real traffic and replaying it in tests
+//!
+//! [`RecordingConnection`] wraps a real connector and records every request it sends along with
+//! the response it got back. The recorded [`Traffic`] can be saved as JSON, checked in next to an
+//! integration test, and served by a [`ReplayingConnection`] so that the test runs offline:
+//! ```no_run
+//! use smithy_client::test_connection::record::{RecordingConnection, ReplayingConnection, Traffic};
+//!
+//! # async fn docs() -> std::io::Result<()> {
+//! // record the traffic once, against the real service...
+//! # let real_connector = smithy_client::test_connection::TestConnection::<String>::new(vec![]);
+//! let conn = RecordingConnection::new(real_connector);
+//! // ...send requests through `conn`...
+//! conn.traffic().write_to_file("tests/generate-random.json")?;
+//!
+//! // ...and replay it from then on
+//! let conn = ReplayingConnection::new(Traffic::read_from_file("tests/generate-random.json")?);
+//! // ...send the same requests through `conn`...
+//! conn.assert_requests_match(&[]);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Only bodies that are in memory are recorded for requests: streaming request bodies are recorded
+//! as empty. Response bodies are read in full by the recording connection.
+//!
+//! Credentials are never recorded: the values of the `Authorization`, `x-amz-security-token` and
+//! `x-amz-date` headers, and of the `X-Amz-Credential`, `X-Amz-Date`, `X-Amz-Security-Token` and
+//! `X-Amz-Signature` query parameters of presigned URIs, are replaced with [`REDACTED`]. Redacted
+//! values match any value when replayed requests are validated. The `SecretAccessKey`,
+//! `SessionToken` and `Token` fields of response bodies, in XML (eg. STS) or in JSON (eg. the
+//! instance metadata service), are redacted as well.
+
+use crate::test_connection::ValidateRequest;
+use bytes::{Bytes, BytesMut};
+use http::header::HeaderName;
+use http_body::Body as _;
+use serde::{Deserialize, Serialize};
+use smithy_http::body::SdkBody;
+use std::collections::{BTreeMap, VecDeque};
+use std::future::Future;
+use std::ops::Deref;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tower::BoxError;
+
+/// The value that replaces credentials in recorded requests
+pub const REDACTED: &str = "REDACTED";
+
+const REDACTED_HEADERS: &[&str] = &["authorization", "x-amz-security-token", "x-amz-date"];
+
+const REDACTED_QUERY_PARAMS: &[&str] = &[
+    "X-Amz-Credential",
+    "X-Amz-Date",
+    "X-Amz-Security-Token",
+    "X-Amz-Signature",
+];
+
+/// Fields of the credentials returned in response bodies
+const REDACTED_BODY_FIELDS: &[&str] = &["SecretAccessKey", "SessionToken", "Token"];
+
+/// Marks the requests replayed from recorded traffic, whose redacted values match any value
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Replayed;
+
+/// Requests and responses recorded by a [`RecordingConnection`]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Traffic {
+    events: Vec<Event>,
+}
+
+impl Traffic {
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("traffic can always be serialized")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn write_to_file(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_json())
+    }
+
+    pub fn read_from_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        Self::from_json(&json)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// A request and the response it got
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    request: Request,
+    response: Response,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Request {
+    method: String,
+    uri: String,
+    headers: BTreeMap<String, Vec<String>>,
+    body: Body,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Response {
+    status: u16,
+    headers: BTreeMap<String, Vec<String>>,
+    body: Body,
+}
+
+/// Bodies are recorded as text when they are valid UTF-8, and in base64 otherwise
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Body {
+    Utf8(String),
+    Base64(String),
+}
+
+impl Body {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Body::Utf8(text.to_string()),
+            Err(_) => Body::Base64(smithy_types::base64::encode(bytes)),
+        }
+    }
+
+    /// Replace the values of the credential fields of a text body
+    fn redact_credentials(self) -> Self {
+        match self {
+            Body::Utf8(mut text) => {
+                for field in REDACTED_BODY_FIELDS {
+                    text = redact_xml_element(&text, field);
+                    text = redact_json_field(&text, field);
+                }
+                Body::Utf8(text)
+            }
+            body => body,
+        }
+    }
+
+    fn to_bytes(&self) -> Bytes {
+        match self {
+            Body::Utf8(text) => Bytes::from(text.clone()),
+            Body::Base64(data) => smithy_types::base64::decode(data)
+                .expect("recorded body must be valid base64")
+                .into(),
+        }
+    }
+}
+
+/// Replace the content of the `<name>` elements of `body`
+fn redact_xml_element(body: &str, name: &str) -> String {
+    let (open, close) = (format!("<{}>", name), format!("</{}>", name));
+    let mut redacted = String::new();
+    let mut rest = body;
+    while let Some(start) = rest.find(&open) {
+        let value = start + open.len();
+        let end = match rest[value..].find(&close) {
+            Some(end) => value + end,
+            None => break,
+        };
+        redacted.push_str(&rest[..value]);
+        redacted.push_str(REDACTED);
+        rest = &rest[end..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+/// Replace the string values of the `"name"` fields of `body`
+fn redact_json_field(body: &str, name: &str) -> String {
+    let key = format!("\"{}\"", name);
+    let mut redacted = String::new();
+    let mut rest = body;
+    while let Some(start) = rest.find(&key) {
+        let (before, after) = rest.split_at(start + key.len());
+        redacted.push_str(before);
+        rest = after;
+        let value = match after.trim_start().strip_prefix(':') {
+            Some(value) => value.trim_start(),
+            None => continue,
+        };
+        let value = match value.strip_prefix('"') {
+            Some(value) => value,
+            None => continue,
+        };
+        // the value ends at the first quote that isn't escaped
+        let mut escaped = false;
+        let end = value.char_indices().find(|&(_, c)| {
+            let end = !escaped && c == '"';
+            escaped = !escaped && c == '\\';
+            end
+        });
+        let end = match end {
+            Some((end, _)) => end,
+            None => break,
+        };
+        let value_start = after.len() - value.len();
+        redacted.push_str(&after[..value_start]);
+        redacted.push_str(REDACTED);
+        rest = &after[value_start + end..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+fn record_headers(headers: &http::HeaderMap) -> BTreeMap<String, Vec<String>> {
+    let mut recorded = BTreeMap::<_, Vec<_>>::new();
+    for (name, value) in headers {
+        recorded
+            .entry(name.to_string())
+            .or_default()
+            .push(String::from_utf8_lossy(value.as_bytes()).into_owned());
+    }
+    recorded
+}
+
+fn record_request_headers(headers: &http::HeaderMap) -> BTreeMap<String, Vec<String>> {
+    let mut recorded = record_headers(headers);
+    for (name, values) in recorded.iter_mut() {
+        if REDACTED_HEADERS.contains(&name.as_str()) {
+            values
+                .iter_mut()
+                .for_each(|value| *value = REDACTED.to_string());
+        }
+    }
+    recorded
+}
+
+/// Replace the value of the query parameters of `uri` for which `redact` returns true
+pub(crate) fn redact_query(uri: &str, redact: impl Fn(&str) -> bool) -> String {
+    smithy_http::query::redact_query(uri, REDACTED, redact)
+}
+
+fn record_uri(uri: &http::Uri) -> String {
+    redact_query(&uri.to_string(), |name| {
+        REDACTED_QUERY_PARAMS
+            .iter()
+            .any(|redacted| redacted.eq_ignore_ascii_case(name))
+    })
+}
+
+fn replay_headers(recorded: &BTreeMap<String, Vec<String>>, headers: &mut http::HeaderMap) {
+    for (name, values) in recorded {
+        let name = HeaderName::from_bytes(name.as_bytes()).expect("recorded header name");
+        for value in values {
+            headers.append(&name, value.parse().expect("recorded header value"));
+        }
+    }
+}
+
+impl Request {
+    fn record(request: &http::Request<SdkBody>) -> Self {
+        Request {
+            method: request.method().to_string(),
+            uri: record_uri(request.uri()),
+            headers: record_request_headers(request.headers()),
+            body: Body::from_bytes(request.body().bytes().unwrap_or_default()),
+        }
+    }
+
+    fn replay(&self) -> http::Request<SdkBody> {
+        let mut request = http::Request::builder()
+            .method(self.method.as_str())
+            .uri(self.uri.as_str())
+            .body(SdkBody::from(self.body.to_bytes()))
+            .expect("recorded request must be valid");
+        replay_headers(&self.headers, request.headers_mut());
+        request.extensions_mut().insert(Replayed);
+        request
+    }
+}
+
+impl Response {
+    fn replay(&self) -> http::Response<SdkBody> {
+        let mut response = http::Response::builder()
+            .status(self.status)
+            .body(SdkBody::from(self.body.to_bytes()))
+            .expect("recorded response must be valid");
+        replay_headers(&self.headers, response.headers_mut());
+        response
+    }
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// Connector that records the traffic of the connector it wraps
+///
+/// Requests that fail to be dispatched are not recorded.
+#[derive(Clone, Debug)]
+pub struct RecordingConnection<S> {
+    inner: S,
+    events: Arc<Mutex<Vec<Event>>>,
+}
+
+impl<S> RecordingConnection<S> {
+    pub fn new(inner: S) -> Self {
+        RecordingConnection {
+            inner,
+            events: Default::default(),
+        }
+    }
+
+    /// The traffic recorded so far
+    pub fn traffic(&self) -> Traffic {
+        Traffic {
+            events: self.events.lock().unwrap().clone(),
+        }
+    }
+}
+
+impl<S> tower::Service<http::Request<SdkBody>> for RecordingConnection<S>
+where
+    S: tower::Service<http::Request<SdkBody>, Response = http::Response<SdkBody>>,
+    S::Error: Into<BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<SdkBody>;
+    type Error = BoxError;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<SdkBody>) -> Self::Future {
+        let request = Request::record(&req);
+        let response = self.inner.call(req);
+        let events = self.events.clone();
+        Box::pin(async move {
+            let (parts, body) = response.await.map_err(Into::into)?.into_parts();
+            let body = read_body(body).await?;
+            events.lock().unwrap().push(Event {
+                request,
+                response: Response {
+                    status: parts.status.as_u16(),
+                    headers: record_headers(&parts.headers),
+                    body: Body::from_bytes(&body).redact_credentials(),
+                },
+            });
+            Ok(http::Response::from_parts(parts, SdkBody::from(body)))
+        })
+    }
+}
+
+async fn read_body(body: SdkBody) -> Result<Bytes, BoxError> {
+    let mut body = Box::pin(body);
+    let mut data = BytesMut::new();
+    while let Some(chunk) = body.data().await {
+        data.extend_from_slice(&chunk?);
+    }
+    Ok(data.freeze())
+}
+
+/// Connector that serves the responses of recorded [`Traffic`]
+///
+/// Responses are served in the order they were recorded, or, with
+/// [`ReplayingConnection::match_requests`], by finding the first recorded request with the same
+/// method and URI, ignoring redacted query parameters. Either way, the requests that were sent are
+/// kept along with the recorded request they were matched with, for
+/// [`ValidateRequest::assert_matches`].
+#[derive(Clone, Debug)]
+pub struct ReplayingConnection {
+    events: Arc<Mutex<VecDeque<Event>>>,
+    requests: Arc<Mutex<Vec<ValidateRequest>>>,
+    match_requests: bool,
+}
+
+impl ReplayingConnection {
+    pub fn new(traffic: Traffic) -> Self {
+        ReplayingConnection {
+            events: Arc::new(Mutex::new(traffic.events.into())),
+            requests: Default::default(),
+            match_requests: false,
+        }
+    }
+
+    /// Serve the response of the first recorded request with the same method and URI instead of
+    /// serving the responses in order
+    pub fn match_requests(mut self) -> Self {
+        self.match_requests = true;
+        self
+    }
+
+    pub fn requests(&self) -> impl Deref<Target = Vec<ValidateRequest>> + '_ {
+        self.requests.lock().unwrap()
+    }
+
+    /// Assert that every recorded response was served, and that every request matches the request
+    /// that was recorded for it
+    pub fn assert_requests_match(&self, ignore_headers: &[HeaderName]) {
+        let remaining = self.events.lock().unwrap().len();
+        assert_eq!(
+            remaining, 0,
+            "{} recorded requests were never sent",
+            remaining
+        );
+        for request in self.requests().iter() {
+            request.assert_matches(ignore_headers.to_vec());
+        }
+    }
+
+    fn next_event(&self, request: &http::Request<SdkBody>) -> Option<Event> {
+        let mut events = self.events.lock().unwrap();
+        if !self.match_requests {
+            return events.pop_front();
+        }
+        let method = request.method().as_str();
+        let uri = record_uri(request.uri());
+        let position = events
+            .iter()
+            .position(|event| event.request.method == method && event.request.uri == uri)?;
+        events.remove(position)
+    }
+}
+
+impl tower::Service<http::Request<SdkBody>> for ReplayingConnection {
+    type Response = http::Response<SdkBody>;
+    type Error = BoxError;
+    type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, actual: http::Request<SdkBody>) -> Self::Future {
+        let result = match self.next_event(&actual) {
+            Some(event) => {
+                self.requests.lock().unwrap().push(ValidateRequest {
+                    expected: event.request.replay(),
+                    actual,
+                });
+                Ok(event.response.replay())
+            }
+            None => Err(format!(
+                "no recorded response for {} {}",
+                actual.method(),
+                actual.uri()
+            )
+            .into()),
+        };
+        std::future::ready(result)
+    }
+}
+
+impl From<ReplayingConnection>
+    for crate::Client<ReplayingConnection, tower::layer::util::Identity>
+{
+    fn from(conn: ReplayingConnection) -> Self {
+        crate::Builder::new()
+            .middleware(tower::layer::util::Identity::new())
+            .connector(conn)
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_connection::record::{
+        Body, RecordingConnection, ReplayingConnection, Traffic, REDACTED,
+    };
+    use crate::test_connection::TestConnection;
+    use bytes::Bytes;
+    use http::header::CONTENT_TYPE;
+    use smithy_http::body::SdkBody;
+    use tower::{Service, ServiceExt};
+
+    fn request(uri: &str, body: &'static str) -> http::Request<SdkBody> {
+        http::Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json")
+            .header("authorization", "secret")
+            .body(SdkBody::from(body))
+            .unwrap()
+    }
+
+    fn response(body: &'static [u8]) -> http::Response<Bytes> {
+        http::Response::builder()
+            .status(200)
+            .header("x-amz-request-id", "1234")
+            .body(Bytes::from_static(body))
+            .unwrap()
+    }
+
+    async fn send<S>(conn: &mut S, request: http::Request<SdkBody>) -> http::Response<SdkBody>
+    where
+        S: Service<http::Request<SdkBody>, Response = http::Response<SdkBody>>,
+        S::Error: std::fmt::Debug,
+    {
+        conn.ready().await.unwrap().call(request).await.unwrap()
+    }
+
+    async fn record() -> Traffic {
+        let mut conn = RecordingConnection::new(TestConnection::new(vec![
+            (
+                request("https://example.com/a", ""),
+                response(br#"{"first": true}"#),
+            ),
+            (
+                request("https://example.com/b", ""),
+                response(&[0xff, 0xfe]),
+            ),
+        ]));
+        let first = send(&mut conn, request("https://example.com/a", r#"{"a": 1}"#)).await;
+        assert_eq!(first.body().bytes(), Some(&br#"{"first": true}"#[..]));
+        send(&mut conn, request("https://example.com/b", r#"{"b": 2}"#)).await;
+        conn.traffic()
+    }
+
+    #[tokio::test]
+    async fn record_traffic() {
+        let traffic = record().await;
+        assert_eq!(traffic.events().len(), 2);
+        assert_eq!(Traffic::from_json(&traffic.to_json()).unwrap(), traffic);
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        traffic.write_to_file(file.path()).unwrap();
+        assert_eq!(Traffic::read_from_file(file.path()).unwrap(), traffic);
+    }
+
+    fn signed_request(date: &str, signature: &str) -> http::Request<SdkBody> {
+        http::Request::builder()
+            .uri(format!(
+                "https://example.com/presigned?X-Amz-Credential=AKID%2F{}&X-Amz-Date={}&X-Amz-Signature={}&x-id=GetObject",
+                date, date, signature
+            ))
+            .header("authorization", signature)
+            .header("x-amz-security-token", "session-token")
+            .header("x-amz-date", date)
+            .body(SdkBody::empty())
+            .unwrap()
+    }
+
+    async fn record_signed() -> Traffic {
+        let mut conn = RecordingConnection::new(TestConnection::new(vec![(
+            signed_request("20210618T170000Z", "recorded-signature"),
+            response(b""),
+        )]));
+        send(
+            &mut conn,
+            signed_request("20210618T170000Z", "recorded-signature"),
+        )
+        .await;
+        conn.traffic()
+    }
+
+    #[tokio::test]
+    async fn record_redacts_credentials() {
+        let traffic = record_signed().await;
+        let json = traffic.to_json();
+        assert!(!json.contains("recorded-signature"), "{}", json);
+        assert!(!json.contains("session-token"), "{}", json);
+        assert!(!json.contains("AKID"), "{}", json);
+        assert!(!json.contains("20210618T170000Z"), "{}", json);
+        assert!(json.contains(&format!(
+            "https://example.com/presigned?X-Amz-Credential={r}&X-Amz-Date={r}&X-Amz-Signature={r}&x-id=GetObject",
+            r = REDACTED
+        )), "{}", json);
+
+        // requests signed with other credentials, at another time, match the recorded request
+        let mut conn = ReplayingConnection::new(traffic).match_requests();
+        send(
+            &mut conn,
+            signed_request("20210619T120000Z", "other-signature"),
+        )
+        .await;
+        conn.assert_requests_match(&[]);
+    }
+
+    #[tokio::test]
+    async fn record_redacts_credentials_from_responses() {
+        let sts = br#"<AssumeRoleResponse>
+  <AssumeRoleResult>
+    <Credentials>
+      <AccessKeyId>ASIARTESTID</AccessKeyId>
+      <SecretAccessKey>sts-secret</SecretAccessKey>
+      <SessionToken>sts-session-token</SessionToken>
+      <Expiration>2021-06-18T18:00:00Z</Expiration>
+    </Credentials>
+  </AssumeRoleResult>
+</AssumeRoleResponse>"#;
+        let imds = br#"{
+  "Code" : "Success",
+  "AccessKeyId" : "ASIARTESTID",
+  "SecretAccessKey" : "imds-secret",
+  "Token" : "imds-token-with-\"quotes\"",
+  "Expiration" : "2021-06-18T18:00:00Z"
+}"#;
+        let mut conn = RecordingConnection::new(TestConnection::new(vec![
+            (request("https://sts.amazonaws.com/", ""), response(sts)),
+            (request("http://169.254.169.254/", ""), response(imds)),
+        ]));
+        // the response is only redacted in the recording
+        let live = send(&mut conn, request("https://sts.amazonaws.com/", "")).await;
+        assert_eq!(live.body().bytes(), Some(&sts[..]));
+        send(&mut conn, request("http://169.254.169.254/", "")).await;
+
+        let json = conn.traffic().to_json();
+        for secret in &[
+            "sts-secret",
+            "sts-session-token",
+            "imds-secret",
+            "imds-token",
+        ] {
+            assert!(!json.contains(secret), "{}", json);
+        }
+        let replayed = ReplayingConnection::new(conn.traffic())
+            .ready()
+            .await
+            .unwrap()
+            .call(request("https://sts.amazonaws.com/", ""))
+            .await
+            .unwrap();
+        let body = std::str::from_utf8(replayed.body().bytes().unwrap()).unwrap();
+        assert!(
+            body.contains("<AccessKeyId>ASIARTESTID</AccessKeyId>"),
+            "{}",
+            body
+        );
+        assert!(
+            body.contains(&format!(
+                "<SecretAccessKey>{r}</SecretAccessKey>\n      <SessionToken>{r}</SessionToken>",
+                r = REDACTED
+            )),
+            "{}",
+            body
+        );
+
+        let traffic = conn.traffic();
+        match &traffic.events()[1].response.body {
+            Body::Utf8(body) => {
+                assert!(
+                    body.contains(r#""AccessKeyId" : "ASIARTESTID""#),
+                    "{}",
+                    body
+                );
+                assert!(
+                    body.contains(&format!(r#""SecretAccessKey" : "{}""#, REDACTED)),
+                    "{}",
+                    body
+                );
+                assert!(
+                    body.contains(&format!(r#""Token" : "{}","#, REDACTED)),
+                    "{}",
+                    body
+                );
+            }
+            other => panic!("expected a text body, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "URI mismatch")]
+    async fn replay_validates_query_params() {
+        let mut conn = ReplayingConnection::new(record_signed().await);
+        // the query parameters that were not redacted must still match
+        let mut request = signed_request("20210618T170000Z", "recorded-signature");
+        *request.uri_mut() = request
+            .uri()
+            .to_string()
+            .replace("x-id=GetObject", "x-id=PutObject")
+            .parse()
+            .unwrap();
+        send(&mut conn, request).await;
+        conn.assert_requests_match(&[]);
+    }
+
+    #[tokio::test]
+    async fn replay_in_order() {
+        let mut conn = ReplayingConnection::new(record().await);
+        let first = send(&mut conn, request("https://example.com/a", r#"{"a":1}"#)).await;
+        assert_eq!(first.status(), 200);
+        assert_eq!(first.headers()["x-amz-request-id"], "1234");
+        assert_eq!(first.body().bytes(), Some(&br#"{"first": true}"#[..]));
+        // bodies that aren't UTF-8 are replayed as they were recorded
+        let second = send(&mut conn, request("https://example.com/b", r#"{"b":2}"#)).await;
+        assert_eq!(second.body().bytes(), Some(&[0xff, 0xfe][..]));
+        conn.assert_requests_match(&[]);
+
+        assert!(conn
+            .ready()
+            .await
+            .unwrap()
+            .call(request("https://example.com/a", ""))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn replay_matching_requests() {
+        let mut conn = ReplayingConnection::new(record().await).match_requests();
+        let second = send(&mut conn, request("https://example.com/b", r#"{"b": 2}"#)).await;
+        assert_eq!(second.body().bytes(), Some(&[0xff, 0xfe][..]));
+        let first = send(&mut conn, request("https://example.com/a", r#"{"a": 1}"#)).await;
+        assert_eq!(first.body().bytes(), Some(&br#"{"first": true}"#[..]));
+        assert!(conn
+            .ready()
+            .await
+            .unwrap()
+            .call(request("https://example.com/c", ""))
+            .await
+            .is_err());
+        conn.assert_requests_match(&[]);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "Header mismatch")]
+    async fn replay_validates_requests() {
+        let mut conn = ReplayingConnection::new(record().await);
+        let mut changed = request("https://example.com/a", r#"{"a": 1}"#);
+        changed
+            .headers_mut()
+            .insert(CONTENT_TYPE, "application/xml".parse().unwrap());
+        send(&mut conn, changed).await;
+        send(&mut conn, request("https://example.com/b", r#"{"b": 2}"#)).await;
+        conn.assert_requests_match(&[]);
+    }
+
+    #[tokio::test]
+    async fn replay_ignores_headers() {
+        let mut conn = ReplayingConnection::new(record().await);
+        let mut changed = request("https://example.com/a", r#"{"a": 1}"#);
+        changed
+            .headers_mut()
+            .insert(CONTENT_TYPE, "application/xml".parse().unwrap());
+        send(&mut conn, changed).await;
+        send(&mut conn, request("https://example.com/b", r#"{"b": 2}"#)).await;
+        conn.assert_requests_match(&[CONTENT_TYPE]);
+    }
+}
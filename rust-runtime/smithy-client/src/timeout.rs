@@ -0,0 +1,505 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Timeouts for requests sent by a [`Client`](crate::Client)
+//!
+//! Three timeouts can be configured with [`Config`]:
+//! - The **connect** timeout limits the time to establish a connection. It is applied by the
+//!   connectors created by the [`Builder`](crate::Builder) (eg. `Builder::rustls`), which read it
+//!   for every connection: it can be set before or after the connector is chosen, or on the
+//!   [`Client`](crate::Client) itself.
+//! - The **attempt** timeout limits each attempt of a request, from dispatch to the parsed response.
+//! - The **total** timeout limits the whole call, across all retries.
+//!
+//! When a timeout elapses, the call fails with a [`SdkError::DispatchFailure`] caused by a
+//! [`TimeoutError`]. Connect and attempt timeouts are wrapped in a transient [`ConnectorError`], so
+//! that they are retried like other connection failures.
+
+use crate::{SdkError, SdkSuccess};
+use pin_project::pin_project;
+use smithy_http::operation::Operation;
+use smithy_http::result::ConnectorError;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::Sleep;
+use tower::{Layer, Service};
+
+/// Timeout configuration
+///
+/// No timeout is set by default:
+/// ```rust
+/// use smithy_client::timeout::Config;
+/// use std::time::Duration;
+/// let conf = Config::new()
+///     .with_connect_timeout(Duration::from_secs(3))
+///     .with_attempt_timeout(Duration::from_secs(10))
+///     .with_total_timeout(Duration::from_secs(30));
+/// ```
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Config {
+    connect: Option<Duration>,
+    attempt: Option<Duration>,
+    total: Option<Duration>,
+}
+
+impl Config {
+    /// Create a configuration without any timeout
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum time to establish a connection
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect = Some(timeout);
+        self
+    }
+
+    /// Set the maximum time of each attempt of a request
+    pub fn with_attempt_timeout(mut self, timeout: Duration) -> Self {
+        self.attempt = Some(timeout);
+        self
+    }
+
+    /// Set the maximum time of a call, including all of its retries
+    pub fn with_total_timeout(mut self, timeout: Duration) -> Self {
+        self.total = Some(timeout);
+        self
+    }
+
+    /// The maximum time to establish a connection
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        self.connect
+    }
+
+    /// The maximum time of each attempt of a request
+    pub fn attempt_timeout(&self) -> Option<Duration> {
+        self.attempt
+    }
+
+    /// The maximum time of a call, including all of its retries
+    pub fn total_timeout(&self) -> Option<Duration> {
+        self.total
+    }
+}
+
+/// The connect timeout of a client, shared with the connectors created by its builder
+///
+/// The connectors read it for every connection, so that setting the timeout configuration of a
+/// client after its connector was created still applies the connect timeout.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SharedConnectTimeout(Arc<Mutex<Option<Duration>>>);
+
+impl SharedConnectTimeout {
+    #[cfg(feature = "hyper")]
+    pub(crate) fn get(&self) -> Option<Duration> {
+        *self.0.lock().unwrap()
+    }
+
+    pub(crate) fn set(&self, timeout: Option<Duration>) {
+        *self.0.lock().unwrap() = timeout;
+    }
+}
+
+/// The timeout that elapsed
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeoutKind {
+    /// A connection could not be established in time
+    Connect,
+    /// An attempt of the request did not complete in time
+    Attempt,
+    /// The call, including its retries, did not complete in time
+    Total,
+}
+
+/// A timeout elapsed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimeoutError {
+    kind: TimeoutKind,
+    duration: Duration,
+}
+
+impl TimeoutError {
+    fn new(kind: TimeoutKind, duration: Duration) -> Self {
+        TimeoutError { kind, duration }
+    }
+
+    /// Which timeout elapsed
+    pub fn kind(&self) -> TimeoutKind {
+        self.kind
+    }
+
+    /// The duration of the timeout
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Find the timeout that caused `err`, if any
+    ///
+    /// The timeout error may have been wrapped by the connector, so the whole chain of sources of
+    /// the error is searched.
+    pub fn find<E>(err: &SdkError<E>) -> Option<&TimeoutError> {
+        let mut source: Option<&(dyn Error + 'static)> = match err {
+            SdkError::DispatchFailure(err) => Some(err.as_ref()),
+            _ => None,
+        };
+        while let Some(err) = source {
+            if let Some(timeout) = err.downcast_ref::<TimeoutError>() {
+                return Some(timeout);
+            }
+            source = err.source();
+        }
+        None
+    }
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let timeout = match self.kind {
+            TimeoutKind::Connect => "connect",
+            TimeoutKind::Attempt => "attempt",
+            TimeoutKind::Total => "total",
+        };
+        write!(f, "{} timeout of {:?} elapsed", timeout, self.duration)
+    }
+}
+
+impl Error for TimeoutError {}
+
+/// Fail `future` with a timeout error of `kind` if it doesn't complete within `timeout`
+pub(crate) async fn with_timeout<F, T, E>(
+    kind: TimeoutKind,
+    timeout: Option<Duration>,
+    future: F,
+) -> Result<T, SdkError<E>>
+where
+    F: Future<Output = Result<T, SdkError<E>>>,
+{
+    match timeout {
+        Some(duration) => match tokio::time::timeout(duration, future).await {
+            Ok(result) => result,
+            Err(_) => Err(timeout_failure(kind, duration)),
+        },
+        None => future.await,
+    }
+}
+
+pub(crate) fn timeout_failure<E>(kind: TimeoutKind, duration: Duration) -> SdkError<E> {
+    let err = TimeoutError::new(kind, duration);
+    match kind {
+        // the total timeout already covers the retries, so it is not transient
+        TimeoutKind::Total => SdkError::DispatchFailure(Box::new(err)),
+        TimeoutKind::Connect | TimeoutKind::Attempt => {
+            SdkError::DispatchFailure(Box::new(ConnectorError::transient(err)))
+        }
+    }
+}
+
+/// Layer that applies the attempt timeout
+///
+/// It is added inside of the retry layer, so that every attempt is limited separately. It also
+/// classifies the errors of hyper connectors for the retry policy (see [`ConnectorError`]).
+#[derive(Clone, Debug)]
+pub(crate) struct AttemptTimeoutLayer {
+    timeout: Option<Duration>,
+}
+
+impl AttemptTimeoutLayer {
+    pub(crate) fn new(timeout: Option<Duration>) -> Self {
+        AttemptTimeoutLayer { timeout }
+    }
+}
+
+impl<S> Layer<S> for AttemptTimeoutLayer {
+    type Service = AttemptTimeoutService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AttemptTimeoutService {
+            inner,
+            timeout: self.timeout,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct AttemptTimeoutService<S> {
+    inner: S,
+    timeout: Option<Duration>,
+}
+
+impl<S, O, R, T, E> Service<Operation<O, R>> for AttemptTimeoutService<S>
+where
+    S: Service<Operation<O, R>, Response = SdkSuccess<T>, Error = SdkError<E>>,
+{
+    type Response = SdkSuccess<T>;
+    type Error = SdkError<E>;
+    type Future = AttemptTimeoutFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Operation<O, R>) -> Self::Future {
+        AttemptTimeoutFuture {
+            inner: self.inner.call(req),
+            sleep: self.timeout.map(tokio::time::sleep),
+            timeout: self.timeout.unwrap_or_default(),
+        }
+    }
+}
+
+// The future is not boxed so that the bounds of `Client::call_raw` don't need to require `Send`
+#[pin_project]
+pub(crate) struct AttemptTimeoutFuture<F> {
+    #[pin]
+    inner: F,
+    #[pin]
+    sleep: Option<Sleep>,
+    timeout: Duration,
+}
+
+impl<F, T, E> Future for AttemptTimeoutFuture<F>
+where
+    F: Future<Output = Result<T, SdkError<E>>>,
+{
+    type Output = Result<T, SdkError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        if let Poll::Ready(result) = this.inner.poll(cx) {
+            #[cfg(feature = "hyper")]
+            let result = result.map_err(crate::hyper_impls::classify_dispatch_failure);
+            return Poll::Ready(result);
+        }
+        match this.sleep.as_pin_mut().map(|sleep| sleep.poll(cx)) {
+            Some(Poll::Ready(())) => {
+                tracing::debug!(timeout = ?this.timeout, "attempt timed out");
+                Poll::Ready(Err(timeout_failure(TimeoutKind::Attempt, *this.timeout)))
+            }
+            _ => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(feature = "hyper")]
+pub use connect::ConnectTimeout;
+
+#[cfg(feature = "hyper")]
+mod connect {
+    use crate::timeout::{SharedConnectTimeout, TimeoutError, TimeoutKind};
+    use crate::BoxError;
+    use http::Uri;
+    use smithy_http::result::ConnectorError;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+    use tower::Service;
+
+    /// Connector that applies the connect timeout
+    ///
+    /// It wraps the connector of a [`hyper::Client`], eg. an `HttpsConnector`.
+    #[derive(Clone, Debug)]
+    pub struct ConnectTimeout<C> {
+        inner: C,
+        timeout: SharedConnectTimeout,
+    }
+
+    impl<C> ConnectTimeout<C> {
+        /// Limit the time `inner` takes to connect to `timeout`
+        pub fn new(inner: C, timeout: Option<Duration>) -> Self {
+            let shared = SharedConnectTimeout::default();
+            shared.set(timeout);
+            Self::shared(inner, shared)
+        }
+
+        /// Limit the time `inner` takes to connect to the current value of `timeout`
+        pub(crate) fn shared(inner: C, timeout: SharedConnectTimeout) -> Self {
+            ConnectTimeout { inner, timeout }
+        }
+    }
+
+    impl<C> Service<Uri> for ConnectTimeout<C>
+    where
+        C: Service<Uri>,
+        C::Error: Into<BoxError>,
+        C::Future: Send + 'static,
+    {
+        type Response = C::Response;
+        type Error = BoxError;
+        #[allow(clippy::type_complexity)]
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            self.inner.poll_ready(cx).map_err(Into::into)
+        }
+
+        fn call(&mut self, uri: Uri) -> Self::Future {
+            let connect = self.inner.call(uri);
+            let timeout = self.timeout.get();
+            Box::pin(async move {
+                match timeout {
+                    Some(duration) => match tokio::time::timeout(duration, connect).await {
+                        Ok(connection) => connection.map_err(Into::into),
+                        Err(_) => Err(ConnectorError::transient(TimeoutError::new(
+                            TimeoutKind::Connect,
+                            duration,
+                        ))
+                        .into()),
+                    },
+                    None => connect.await.map_err(Into::into),
+                }
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::timeout::{
+        timeout_failure, with_timeout, AttemptTimeoutLayer, TimeoutError, TimeoutKind,
+    };
+    use crate::{SdkError, SdkSuccess};
+    use smithy_http::body::SdkBody;
+    use smithy_http::operation::{self, Operation};
+    use smithy_http::result::ConnectorError;
+    use smithy_types::retry::ErrorKind;
+    use std::time::Duration;
+    use tower::{service_fn, Layer, Service, ServiceExt};
+
+    type TestResult = Result<SdkSuccess<()>, SdkError<()>>;
+
+    fn operation() -> Operation<(), ()> {
+        Operation::new(
+            operation::Request::new(http::Request::new(SdkBody::from(""))),
+            (),
+        )
+    }
+
+    fn success() -> SdkSuccess<()> {
+        SdkSuccess {
+            raw: http::Response::new(SdkBody::from("")),
+            parsed: (),
+        }
+    }
+
+    fn timeout_kind(result: &TestResult) -> Option<TimeoutKind> {
+        match result {
+            Err(err) => TimeoutError::find(err).map(TimeoutError::kind),
+            Ok(_) => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn attempt_timeout() {
+        tokio::time::pause();
+        let mut svc = AttemptTimeoutLayer::new(Some(Duration::from_secs(5))).layer(service_fn(
+            |_: Operation<(), ()>| async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok(success())
+            },
+        ));
+        let result = svc.ready().await.unwrap().call(operation()).await;
+        assert_eq!(timeout_kind(&result), Some(TimeoutKind::Attempt));
+        assert_eq!(
+            TimeoutError::find(&result.unwrap_err())
+                .unwrap()
+                .to_string(),
+            "attempt timeout of 5s elapsed"
+        );
+
+        let mut svc = AttemptTimeoutLayer::new(Some(Duration::from_secs(15))).layer(service_fn(
+            |_: Operation<(), ()>| async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok(success())
+            },
+        ));
+        let result: TestResult = svc.ready().await.unwrap().call(operation()).await;
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn timeouts_are_transient() {
+        let error_kind = |kind| match timeout_failure::<()>(kind, Duration::from_secs(1)) {
+            SdkError::DispatchFailure(err) => {
+                ConnectorError::find(err.as_ref()).and_then(ConnectorError::retryable_error_kind)
+            }
+            other => panic!("expected a dispatch failure, got {:?}", other),
+        };
+        assert_eq!(
+            error_kind(TimeoutKind::Connect),
+            Some(ErrorKind::TransientError)
+        );
+        assert_eq!(
+            error_kind(TimeoutKind::Attempt),
+            Some(ErrorKind::TransientError)
+        );
+        assert_eq!(error_kind(TimeoutKind::Total), None);
+    }
+
+    #[tokio::test]
+    async fn total_timeout() {
+        tokio::time::pause();
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(success())
+        };
+        let result = with_timeout(TimeoutKind::Total, Some(Duration::from_secs(1)), slow).await;
+        assert_eq!(timeout_kind(&result), Some(TimeoutKind::Total));
+        let result: TestResult =
+            with_timeout(TimeoutKind::Total, None, async { Ok(success()) }).await;
+        assert!(result.is_ok());
+    }
+
+    #[cfg(feature = "hyper")]
+    #[tokio::test]
+    async fn connect_timeout() {
+        use crate::timeout::ConnectTimeout;
+        use std::convert::Infallible;
+
+        tokio::time::pause();
+        let mut connector = ConnectTimeout::new(
+            service_fn(|_: http::Uri| async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok::<_, Infallible>(())
+            }),
+            Some(Duration::from_secs(1)),
+        );
+        let err = connector
+            .ready()
+            .await
+            .unwrap()
+            .call(http::Uri::from_static("https://example.com"))
+            .await
+            .expect_err("connecting times out");
+        // connector errors are wrapped by hyper, then by the client
+        let result: TestResult = Err(SdkError::DispatchFailure(Box::new(WrappedError(err))));
+        assert_eq!(timeout_kind(&result), Some(TimeoutKind::Connect));
+    }
+
+    #[cfg(feature = "hyper")]
+    #[derive(Debug)]
+    struct WrappedError(crate::BoxError);
+
+    #[cfg(feature = "hyper")]
+    impl std::fmt::Display for WrappedError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "connection failed")
+        }
+    }
+
+    #[cfg(feature = "hyper")]
+    impl std::error::Error for WrappedError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(self.0.as_ref())
+        }
+    }
+}
@@ -0,0 +1,65 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+use std::error::Error as StdError;
+use std::fmt;
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    HeadersTooLong,
+    PayloadTooLong,
+    MessageTooLong,
+    InvalidHeaderNameLength,
+    InvalidHeaderValue,
+    InvalidHeaderValueType(u8),
+    InvalidHeadersLength,
+    InvalidMessageLength,
+    InvalidUtf8String,
+    MessageChecksumMismatch(u32, u32),
+    PreludeChecksumMismatch(u32, u32),
+    /// The stream ended in the middle of a message
+    UnexpectedEndOfStream,
+    /// The body of the stream could not be read
+    ReadBody(smithy_http::body::Error),
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::ReadBody(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Error::*;
+        match self {
+            HeadersTooLong => write!(f, "headers too long to fit in event stream frame"),
+            PayloadTooLong => write!(f, "payload too long to fit in event stream frame"),
+            MessageTooLong => write!(f, "message too long to fit in event stream frame"),
+            InvalidHeaderNameLength => write!(f, "invalid header name length"),
+            InvalidHeaderValue => write!(f, "invalid header value"),
+            InvalidHeaderValueType(val) => write!(f, "invalid header value type: {}", val),
+            InvalidHeadersLength => write!(f, "invalid headers length"),
+            InvalidMessageLength => write!(f, "invalid message length"),
+            InvalidUtf8String => write!(f, "encountered invalid UTF-8 string"),
+            MessageChecksumMismatch(expected, actual) => write!(
+                f,
+                "message checksum 0x{:X} didn't match expected checksum 0x{:X}",
+                actual, expected
+            ),
+            PreludeChecksumMismatch(expected, actual) => write!(
+                f,
+                "prelude checksum 0x{:X} didn't match expected checksum 0x{:X}",
+                actual, expected
+            ),
+            UnexpectedEndOfStream => write!(f, "the stream ended in the middle of a message"),
+            ReadBody(err) => write!(f, "failed to read the event stream body: {}", err),
+        }
+    }
+}
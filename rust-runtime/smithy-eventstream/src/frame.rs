@@ -0,0 +1,683 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Event stream message framing
+//!
+//! A message is laid out as follows (all integers are big endian):
+//! ```text
+//! +--------------------+--------------------+--------------------+
+//! | total length (u32) | headers length(u32)| prelude CRC (u32)  |
+//! +--------------------+--------------------+--------------------+
+//! | headers (headers length bytes)                               |
+//! +--------------------------------------------------------------+
+//! | payload (total length - headers length - 16 bytes)           |
+//! +--------------------------------------------------------------+
+//! | message CRC (u32): CRC32 of all of the preceding bytes       |
+//! +--------------------------------------------------------------+
+//! ```
+
+use crate::error::Error;
+use crate::str_bytes::StrBytes;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use smithy_types::Instant;
+use std::convert::TryFrom;
+use std::error::Error as StdError;
+use std::fmt;
+use std::mem::size_of;
+use std::sync::{mpsc, Mutex};
+
+const PRELUDE_LENGTH_BYTES: u32 = 3 * size_of::<u32>() as u32;
+const PRELUDE_LENGTH_BYTES_USIZE: usize = PRELUDE_LENGTH_BYTES as usize;
+const MESSAGE_CRC_LENGTH_BYTES: u32 = size_of::<u32>() as u32;
+const MIN_MESSAGE_LENGTH: u32 = PRELUDE_LENGTH_BYTES + MESSAGE_CRC_LENGTH_BYTES;
+const MAX_HEADER_NAME_LEN: usize = 255;
+const MAX_HEADERS_LENGTH: u32 = 128 * 1024;
+const MAX_MESSAGE_LENGTH: u32 = 16 * 1024 * 1024;
+
+mod value_type {
+    pub(super) const TYPE_TRUE: u8 = 0;
+    pub(super) const TYPE_FALSE: u8 = 1;
+    pub(super) const TYPE_BYTE: u8 = 2;
+    pub(super) const TYPE_INT16: u8 = 3;
+    pub(super) const TYPE_INT32: u8 = 4;
+    pub(super) const TYPE_INT64: u8 = 5;
+    pub(super) const TYPE_BYTE_ARRAY: u8 = 6;
+    pub(super) const TYPE_STRING: u8 = 7;
+    pub(super) const TYPE_TIMESTAMP: u8 = 8;
+    pub(super) const TYPE_UUID: u8 = 9;
+}
+
+/// Event stream header value
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+pub enum HeaderValue {
+    Bool(bool),
+    Byte(i8),
+    Int16(i16),
+    Int32(i32),
+    Int64(i64),
+    ByteArray(Bytes),
+    String(StrBytes),
+    Timestamp(Instant),
+    Uuid(u128),
+}
+
+impl HeaderValue {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            HeaderValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_byte_array(&self) -> Option<&Bytes> {
+        match self {
+            HeaderValue::ByteArray(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_string(&self) -> Option<&StrBytes> {
+        match self {
+            HeaderValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_timestamp(&self) -> Option<Instant> {
+        match self {
+            HeaderValue::Timestamp(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn read_from(buffer: &mut Bytes) -> Result<HeaderValue, Error> {
+        use value_type::*;
+        let value_type = read_u8(buffer)?;
+        match value_type {
+            TYPE_TRUE => Ok(HeaderValue::Bool(true)),
+            TYPE_FALSE => Ok(HeaderValue::Bool(false)),
+            TYPE_BYTE => Ok(HeaderValue::Byte(read_u8(buffer)? as i8)),
+            TYPE_INT16 => {
+                check_remaining(buffer, size_of::<i16>())?;
+                Ok(HeaderValue::Int16(buffer.get_i16()))
+            }
+            TYPE_INT32 => {
+                check_remaining(buffer, size_of::<i32>())?;
+                Ok(HeaderValue::Int32(buffer.get_i32()))
+            }
+            TYPE_INT64 => {
+                check_remaining(buffer, size_of::<i64>())?;
+                Ok(HeaderValue::Int64(buffer.get_i64()))
+            }
+            TYPE_BYTE_ARRAY | TYPE_STRING => {
+                check_remaining(buffer, size_of::<u16>())?;
+                let len = buffer.get_u16() as usize;
+                check_remaining(buffer, len)?;
+                let bytes = buffer.split_to(len);
+                if value_type == TYPE_STRING {
+                    Ok(HeaderValue::String(
+                        StrBytes::try_from(bytes).map_err(|_| Error::InvalidUtf8String)?,
+                    ))
+                } else {
+                    Ok(HeaderValue::ByteArray(bytes))
+                }
+            }
+            TYPE_TIMESTAMP => {
+                check_remaining(buffer, size_of::<i64>())?;
+                Ok(HeaderValue::Timestamp(Instant::from_epoch_millis(
+                    buffer.get_i64(),
+                )))
+            }
+            TYPE_UUID => {
+                check_remaining(buffer, size_of::<u128>())?;
+                Ok(HeaderValue::Uuid(buffer.get_u128()))
+            }
+            _ => Err(Error::InvalidHeaderValueType(value_type)),
+        }
+    }
+
+    fn write_to<B: BufMut>(&self, buffer: &mut B) -> Result<(), Error> {
+        use value_type::*;
+        match self {
+            HeaderValue::Bool(value) => buffer.put_u8(if *value { TYPE_TRUE } else { TYPE_FALSE }),
+            HeaderValue::Byte(value) => {
+                buffer.put_u8(TYPE_BYTE);
+                buffer.put_i8(*value);
+            }
+            HeaderValue::Int16(value) => {
+                buffer.put_u8(TYPE_INT16);
+                buffer.put_i16(*value);
+            }
+            HeaderValue::Int32(value) => {
+                buffer.put_u8(TYPE_INT32);
+                buffer.put_i32(*value);
+            }
+            HeaderValue::Int64(value) => {
+                buffer.put_u8(TYPE_INT64);
+                buffer.put_i64(*value);
+            }
+            HeaderValue::ByteArray(value) => {
+                buffer.put_u8(TYPE_BYTE_ARRAY);
+                buffer.put_u16(checked_u16_len(value.len())?);
+                buffer.put_slice(value);
+            }
+            HeaderValue::String(value) => {
+                buffer.put_u8(TYPE_STRING);
+                buffer.put_u16(checked_u16_len(value.as_bytes().len())?);
+                buffer.put_slice(value.as_bytes());
+            }
+            HeaderValue::Timestamp(value) => {
+                buffer.put_u8(TYPE_TIMESTAMP);
+                buffer.put_i64(value.epoch_millis());
+            }
+            HeaderValue::Uuid(value) => {
+                buffer.put_u8(TYPE_UUID);
+                buffer.put_u128(*value);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Event stream header
+#[derive(Clone, Debug, PartialEq)]
+pub struct Header {
+    name: StrBytes,
+    value: HeaderValue,
+}
+
+impl Header {
+    pub fn new(name: impl Into<StrBytes>, value: HeaderValue) -> Header {
+        Header {
+            name: name.into(),
+            value,
+        }
+    }
+
+    pub fn name(&self) -> &StrBytes {
+        &self.name
+    }
+
+    pub fn value(&self) -> &HeaderValue {
+        &self.value
+    }
+
+    fn read_from(buffer: &mut Bytes) -> Result<Header, Error> {
+        let name_len = read_u8(buffer)? as usize;
+        if name_len == 0 {
+            return Err(Error::InvalidHeaderNameLength);
+        }
+        check_remaining(buffer, name_len)?;
+        let name =
+            StrBytes::try_from(buffer.split_to(name_len)).map_err(|_| Error::InvalidUtf8String)?;
+        let value = HeaderValue::read_from(buffer)?;
+        Ok(Header { name, value })
+    }
+
+    /// Write the header in its wire format to `buffer`
+    pub fn write_to(&self, mut buffer: &mut dyn BufMut) -> Result<(), Error> {
+        let name = self.name.as_bytes();
+        if name.is_empty() || name.len() > MAX_HEADER_NAME_LEN {
+            return Err(Error::InvalidHeaderNameLength);
+        }
+        buffer.put_u8(name.len() as u8);
+        buffer.put_slice(name);
+        self.value.write_to(&mut buffer)
+    }
+}
+
+/// Event stream message
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Message {
+    headers: Vec<Header>,
+    payload: Bytes,
+}
+
+impl Message {
+    /// Create a message with the given payload and no headers
+    pub fn new(payload: impl Into<Bytes>) -> Message {
+        Message {
+            headers: Vec::new(),
+            payload: payload.into(),
+        }
+    }
+
+    /// Add a header to the message
+    pub fn add_header(mut self, header: Header) -> Self {
+        self.headers.push(header);
+        self
+    }
+
+    pub fn headers(&self) -> &[Header] {
+        &self.headers
+    }
+
+    /// The value of the first header named `name`
+    pub fn header(&self, name: &str) -> Option<&HeaderValue> {
+        self.headers
+            .iter()
+            .find(|header| header.name == name)
+            .map(|header| &header.value)
+    }
+
+    pub fn payload(&self) -> &Bytes {
+        &self.payload
+    }
+
+    /// Read one message from the front of `buffer`
+    ///
+    /// The buffer must contain the whole message. The headers and payload of the message share the
+    /// memory of the buffer rather than copying it.
+    pub fn read_from(buffer: &mut Bytes) -> Result<Message, Error> {
+        if buffer.remaining() < PRELUDE_LENGTH_BYTES_USIZE {
+            return Err(Error::InvalidMessageLength);
+        }
+        let (total_len, header_len) = read_prelude(buffer)?;
+        if buffer.remaining() < total_len as usize {
+            return Err(Error::InvalidMessageLength);
+        }
+        let mut message = buffer.split_to(total_len as usize);
+        let crc_offset = (total_len - MESSAGE_CRC_LENGTH_BYTES) as usize;
+        let expected_crc = (&message[crc_offset..]).get_u32();
+        let message_crc = crc32fast::hash(&message[..crc_offset]);
+        if expected_crc != message_crc {
+            return Err(Error::MessageChecksumMismatch(expected_crc, message_crc));
+        }
+        message.truncate(crc_offset);
+        message.advance(PRELUDE_LENGTH_BYTES_USIZE);
+
+        let mut header_bytes = message.split_to(header_len as usize);
+        let mut headers = Vec::new();
+        while header_bytes.has_remaining() {
+            headers.push(Header::read_from(&mut header_bytes)?);
+        }
+        Ok(Message {
+            headers,
+            payload: message,
+        })
+    }
+
+    /// Write the message to `buffer`
+    pub fn write_to(&self, buffer: &mut dyn BufMut) -> Result<(), Error> {
+        let mut headers = Vec::new();
+        for header in &self.headers {
+            header.write_to(&mut headers)?;
+        }
+        if headers.len() > MAX_HEADERS_LENGTH as usize {
+            return Err(Error::HeadersTooLong);
+        }
+        let headers_len = headers.len() as u32;
+        let payload_len = u32::try_from(self.payload.len()).map_err(|_| Error::PayloadTooLong)?;
+        let total_len = MIN_MESSAGE_LENGTH
+            .checked_add(headers_len)
+            .and_then(|len| len.checked_add(payload_len))
+            .filter(|len| *len <= MAX_MESSAGE_LENGTH)
+            .ok_or(Error::MessageTooLong)?;
+
+        let mut message = Vec::with_capacity(total_len as usize);
+        message.put_u32(total_len);
+        message.put_u32(headers_len);
+        message.put_u32(crc32fast::hash(&message));
+        message.put_slice(&headers);
+        message.put_slice(&self.payload);
+        message.put_u32(crc32fast::hash(&message));
+        buffer.put_slice(&message);
+        Ok(())
+    }
+}
+
+/// Incrementally decodes messages from chunks of data
+///
+/// Chunks of a body rarely line up with messages: data is buffered until a whole message has been
+/// received.
+#[derive(Debug, Default)]
+pub struct MessageFrameDecoder {
+    buffer: BytesMut,
+}
+
+impl MessageFrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buffer a chunk of data
+    pub fn push(&mut self, data: impl Buf) {
+        self.buffer.put(data);
+    }
+
+    /// Decode the next message, or return `None` if it hasn't been fully received yet
+    pub fn next_message(&mut self) -> Result<Option<Message>, Error> {
+        if self.buffer.len() < PRELUDE_LENGTH_BYTES_USIZE {
+            return Ok(None);
+        }
+        // Validate the prelude before waiting for the rest of the message so that a corrupted
+        // length doesn't leave the decoder waiting for data that never comes
+        let (total_len, _) = read_prelude(&self.buffer[..])?;
+        if self.buffer.len() < total_len as usize {
+            return Ok(None);
+        }
+        let mut message = self.buffer.split_to(total_len as usize).freeze();
+        Message::read_from(&mut message).map(Some)
+    }
+
+    /// Whether a partial message is buffered
+    pub fn has_partial_message(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+}
+
+/// Converts the events of an event stream into messages
+pub trait MarshallMessage: fmt::Debug {
+    /// The type of event that is marshalled
+    type Input;
+
+    fn marshall(&self, input: Self::Input) -> Result<Message, Error>;
+}
+
+pub type SignMessageError = Box<dyn StdError + Send + Sync + 'static>;
+
+/// Signs the messages of an event stream
+///
+/// Signatures are usually chained: the signature of a message depends on the signature of the
+/// previous message, which is why signing requires a mutable reference.
+pub trait SignMessage: fmt::Debug {
+    /// Wrap `message` in a signed message
+    fn sign(&mut self, message: Message) -> Result<Message, SignMessageError>;
+
+    /// Sign the empty message that marks the end of the stream
+    fn sign_empty(&mut self) -> Result<Message, SignMessageError>;
+}
+
+/// A [`SignMessage`] that is provided after it is needed
+///
+/// The messages of a request body are signed with a signature seeded from the signature of the
+/// request, which is only known once the request has been signed. The body is created with a
+/// `DeferredSigner` and the request signer provides the actual signer through the
+/// [`DeferredSignerSender`], which is stored in the property bag of the request.
+#[derive(Debug)]
+pub struct DeferredSigner {
+    receiver: Option<Mutex<mpsc::Receiver<Box<dyn SignMessage + Send + Sync>>>>,
+    signer: Option<Box<dyn SignMessage + Send + Sync>>,
+}
+
+impl DeferredSigner {
+    pub fn new() -> (Self, DeferredSignerSender) {
+        let (sender, receiver) = mpsc::channel();
+        (
+            DeferredSigner {
+                receiver: Some(Mutex::new(receiver)),
+                signer: None,
+            },
+            DeferredSignerSender(Mutex::new(sender)),
+        )
+    }
+
+    fn signer(&mut self) -> Result<&mut (dyn SignMessage + Send + Sync), SignMessageError> {
+        if self.signer.is_none() {
+            let receiver = self
+                .receiver
+                .take()
+                .ok_or("the message signer was never provided")?;
+            let signer = receiver
+                .into_inner()
+                .map_err(|_| "the message signer was never provided")?
+                .try_recv()
+                .map_err(|_| "no message signer was provided before the first message was sent")?;
+            self.signer = Some(signer);
+        }
+        Ok(self.signer.as_deref_mut().expect("set above"))
+    }
+}
+
+impl SignMessage for DeferredSigner {
+    fn sign(&mut self, message: Message) -> Result<Message, SignMessageError> {
+        self.signer()?.sign(message)
+    }
+
+    fn sign_empty(&mut self) -> Result<Message, SignMessageError> {
+        self.signer()?.sign_empty()
+    }
+}
+
+/// Provides the signer of a [`DeferredSigner`]
+#[derive(Debug)]
+pub struct DeferredSignerSender(Mutex<mpsc::Sender<Box<dyn SignMessage + Send + Sync>>>);
+
+impl DeferredSignerSender {
+    /// Provide the signer of the [`DeferredSigner`]
+    ///
+    /// If the body was dropped, the signer is dropped as well.
+    pub fn send(&self, signer: Box<dyn SignMessage + Send + Sync>) {
+        // The receiver is only gone if the body was dropped, in which case there's nothing to sign
+        let _ = self.0.lock().unwrap().send(signer);
+    }
+}
+
+/// Validate the prelude at the front of `buffer`, returning the total and headers lengths
+fn read_prelude(buffer: &[u8]) -> Result<(u32, u32), Error> {
+    let mut prelude = &buffer[..PRELUDE_LENGTH_BYTES_USIZE];
+    let total_len = prelude.get_u32();
+    let header_len = prelude.get_u32();
+    let expected_crc = prelude.get_u32();
+    let prelude_crc = crc32fast::hash(&buffer[..PRELUDE_LENGTH_BYTES_USIZE - 4]);
+    if expected_crc != prelude_crc {
+        return Err(Error::PreludeChecksumMismatch(expected_crc, prelude_crc));
+    }
+    if header_len > MAX_HEADERS_LENGTH {
+        return Err(Error::InvalidHeadersLength);
+    }
+    if !(MIN_MESSAGE_LENGTH..=MAX_MESSAGE_LENGTH).contains(&total_len)
+        || header_len > total_len - MIN_MESSAGE_LENGTH
+    {
+        return Err(Error::InvalidMessageLength);
+    }
+    Ok((total_len, header_len))
+}
+
+fn check_remaining(buffer: &Bytes, len: usize) -> Result<(), Error> {
+    if buffer.remaining() < len {
+        Err(Error::InvalidHeadersLength)
+    } else {
+        Ok(())
+    }
+}
+
+fn read_u8(buffer: &mut Bytes) -> Result<u8, Error> {
+    check_remaining(buffer, 1)?;
+    Ok(buffer.get_u8())
+}
+
+fn checked_u16_len(len: usize) -> Result<u16, Error> {
+    u16::try_from(len).map_err(|_| Error::InvalidHeaderValue)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::Error;
+    use crate::frame::{Header, HeaderValue, Message, MessageFrameDecoder};
+    use bytes::Bytes;
+    use smithy_types::Instant;
+
+    fn decode(bytes: &'static [u8]) -> Result<Message, Error> {
+        Message::read_from(&mut Bytes::from_static(bytes))
+    }
+
+    fn encode(message: &Message) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        message.write_to(&mut buffer).expect("valid message");
+        buffer
+    }
+
+    // Test vectors from the encoded/positive and encoded/negative test suites of the AWS CRT event
+    // stream library
+
+    const EMPTY_MESSAGE: &[u8] = &[
+        0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x05, 0xc2, 0x48, 0xeb, 0x7d, 0x98, 0xc8,
+        0xff,
+    ];
+
+    const PAYLOAD_NO_HEADERS: &[u8] = &[
+        0x00, 0x00, 0x00, 0x1d, 0x00, 0x00, 0x00, 0x00, 0xfd, 0x52, 0x8c, 0x5a, 0x7b, 0x27, 0x66,
+        0x6f, 0x6f, 0x27, 0x3a, 0x27, 0x62, 0x61, 0x72, 0x27, 0x7d, 0xc3, 0x65, 0x39, 0x36,
+    ];
+
+    const PAYLOAD_ONE_STR_HEADER: &[u8] = &[
+        0x00, 0x00, 0x00, 0x3d, 0x00, 0x00, 0x00, 0x20, 0x07, 0xfd, 0x83, 0x96, 0x0c, b'c', b'o',
+        b'n', b't', b'e', b'n', b't', b'-', b't', b'y', b'p', b'e', 0x07, 0x00, 0x10, b'a', b'p',
+        b'p', b'l', b'i', b'c', b'a', b't', b'i', b'o', b'n', b'/', b'j', b's', b'o', b'n', 0x7b,
+        0x27, 0x66, 0x6f, 0x6f, 0x27, 0x3a, 0x27, 0x62, 0x61, 0x72, 0x27, 0x7d, 0x8d, 0x9c, 0x08,
+        0xb1,
+    ];
+
+    const INT32_HEADER: &[u8] = &[
+        0x00, 0x00, 0x00, 0x2d, 0x00, 0x00, 0x00, 0x10, 0x41, 0xc4, 0x24, 0xb8, 0x0a, b'e', b'v',
+        b'e', b'n', b't', b'-', b't', b'y', b'p', b'e', 0x04, 0x00, 0x00, 0xa0, 0x0c, 0x7b, 0x27,
+        0x66, 0x6f, 0x6f, 0x27, 0x3a, 0x27, 0x62, 0x61, 0x72, 0x27, 0x7d, 0x36, 0xf4, 0x80, 0xa0,
+    ];
+
+    #[test]
+    fn empty_message() {
+        let message = decode(EMPTY_MESSAGE).unwrap();
+        assert!(message.headers().is_empty());
+        assert!(message.payload().is_empty());
+        assert_eq!(encode(&message), EMPTY_MESSAGE);
+    }
+
+    #[test]
+    fn payload_no_headers() {
+        let message = decode(PAYLOAD_NO_HEADERS).unwrap();
+        assert!(message.headers().is_empty());
+        assert_eq!(message.payload(), &b"{'foo':'bar'}"[..]);
+        assert_eq!(encode(&message), PAYLOAD_NO_HEADERS);
+    }
+
+    #[test]
+    fn payload_one_str_header() {
+        let message = decode(PAYLOAD_ONE_STR_HEADER).unwrap();
+        assert_eq!(
+            message.headers(),
+            &[Header::new(
+                "content-type",
+                HeaderValue::String("application/json".into())
+            )]
+        );
+        assert_eq!(message.payload(), &b"{'foo':'bar'}"[..]);
+        assert_eq!(encode(&message), PAYLOAD_ONE_STR_HEADER);
+    }
+
+    #[test]
+    fn int32_header() {
+        let message = decode(INT32_HEADER).unwrap();
+        assert_eq!(
+            message.header("event-type"),
+            Some(&HeaderValue::Int32(0x0000a00c))
+        );
+        assert_eq!(encode(&message), INT32_HEADER);
+    }
+
+    #[test]
+    fn corrupted_length() {
+        let mut corrupted = PAYLOAD_ONE_STR_HEADER.to_vec();
+        corrupted[3] = 0x3e;
+        assert!(matches!(
+            Message::read_from(&mut Bytes::from(corrupted)),
+            Err(Error::PreludeChecksumMismatch(0x07fd8396, _))
+        ));
+    }
+
+    #[test]
+    fn corrupted_headers() {
+        let mut corrupted = PAYLOAD_ONE_STR_HEADER.to_vec();
+        corrupted[13] = b'C';
+        assert!(matches!(
+            Message::read_from(&mut Bytes::from(corrupted)),
+            Err(Error::MessageChecksumMismatch(0x8d9c08b1, _))
+        ));
+    }
+
+    #[test]
+    fn corrupted_payload() {
+        let mut corrupted = PAYLOAD_NO_HEADERS.to_vec();
+        corrupted[13] = b'F';
+        assert!(matches!(
+            Message::read_from(&mut Bytes::from(corrupted)),
+            Err(Error::MessageChecksumMismatch(_, _))
+        ));
+    }
+
+    #[test]
+    fn all_header_types_round_trip() {
+        let message = Message::new(&b"some payload"[..])
+            .add_header(Header::new("true", HeaderValue::Bool(true)))
+            .add_header(Header::new("false", HeaderValue::Bool(false)))
+            .add_header(Header::new("byte", HeaderValue::Byte(-50)))
+            .add_header(Header::new("short", HeaderValue::Int16(20_000)))
+            .add_header(Header::new("int", HeaderValue::Int32(500_000)))
+            .add_header(Header::new("long", HeaderValue::Int64(50_000_000_000)))
+            .add_header(Header::new(
+                "bytes",
+                HeaderValue::ByteArray(Bytes::from_static(&[0, 1, 2, 255])),
+            ))
+            .add_header(Header::new("str", HeaderValue::String("héllo".into())))
+            .add_header(Header::new(
+                "time",
+                HeaderValue::Timestamp(Instant::from_epoch_millis(1_605_500_000_123)),
+            ))
+            .add_header(Header::new(
+                "uuid",
+                HeaderValue::Uuid(0xb79bc914_de21_4e13_b8b2_bc47e85b7f0b),
+            ));
+        let encoded = Bytes::from(encode(&message));
+        let decoded = Message::read_from(&mut encoded.clone()).unwrap();
+        assert_eq!(decoded, message);
+        // the payload of the decoded message is a slice of the encoded message
+        let payload_offset = encoded.len() - 4 - decoded.payload().len();
+        assert_eq!(
+            decoded.payload().as_ptr(),
+            encoded[payload_offset..].as_ptr()
+        );
+    }
+
+    #[test]
+    fn invalid_header_value_type() {
+        let message = Message::new(Bytes::new()).add_header(Header::new("a", HeaderValue::Byte(1)));
+        let mut encoded = encode(&message);
+        // header value type is right after the prelude, the name length and the name
+        encoded[14] = 42;
+        // fix up the message CRC
+        let crc_offset = encoded.len() - 4;
+        let crc = crc32fast::hash(&encoded[..crc_offset]);
+        encoded[crc_offset..].copy_from_slice(&crc.to_be_bytes());
+        assert!(matches!(
+            Message::read_from(&mut Bytes::from(encoded)),
+            Err(Error::InvalidHeaderValueType(42))
+        ));
+    }
+
+    #[test]
+    fn decode_messages_split_across_chunks() {
+        let mut data = PAYLOAD_ONE_STR_HEADER.to_vec();
+        data.extend_from_slice(EMPTY_MESSAGE);
+        data.extend_from_slice(INT32_HEADER);
+        let mut decoder = MessageFrameDecoder::new();
+        let mut messages = vec![];
+        for chunk in data.chunks(7) {
+            decoder.push(chunk);
+            while let Some(message) = decoder.next_message().unwrap() {
+                messages.push(message);
+            }
+        }
+        assert!(!decoder.has_partial_message());
+        assert_eq!(
+            messages,
+            vec![
+                decode(PAYLOAD_ONE_STR_HEADER).unwrap(),
+                decode(EMPTY_MESSAGE).unwrap(),
+                decode(INT32_HEADER).unwrap()
+            ]
+        );
+    }
+}
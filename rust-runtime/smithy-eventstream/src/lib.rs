@@ -0,0 +1,19 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Event stream framing (`application/vnd.amazon.eventstream`)
+//!
+//! Event streams are used by streaming operations such as Kinesis `SubscribeToShard`, Transcribe
+//! streaming and S3 `SelectObjectContent`. A stream is a sequence of binary [messages](frame::Message)
+//! made of headers and a payload, each protected by CRC32 checksums.
+//!
+//! - [`frame`]: encoding and decoding of messages
+//! - [`stream`]: a [`Stream`](futures_core::Stream) of messages over a response body, and a request
+//!   body from a stream of messages or of signed events
+
+pub mod error;
+pub mod frame;
+pub mod str_bytes;
+pub mod stream;
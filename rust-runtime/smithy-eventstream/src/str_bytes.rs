@@ -0,0 +1,99 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! UTF-8 string byte buffer representation with validation amortization.
+
+use bytes::Bytes;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::Utf8Error;
+
+/// UTF-8 string stored in [`Bytes`]
+///
+/// Strings decoded from a message share the buffer of the message instead of being copied. The
+/// bytes are validated as UTF-8 when the `StrBytes` is created, so that [`as_str`](StrBytes::as_str)
+/// is free.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct StrBytes {
+    bytes: Bytes,
+}
+
+impl StrBytes {
+    fn new(bytes: Bytes) -> Self {
+        StrBytes { bytes }
+    }
+
+    /// The string as bytes
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.bytes
+    }
+
+    /// The string
+    pub fn as_str(&self) -> &str {
+        // Safety: the bytes were validated as UTF-8 when the `StrBytes` was created
+        unsafe { std::str::from_utf8_unchecked(&self.bytes[..]) }
+    }
+}
+
+impl fmt::Debug for StrBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for StrBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<String> for StrBytes {
+    fn from(value: String) -> Self {
+        StrBytes::new(Bytes::from(value))
+    }
+}
+
+impl From<&'static str> for StrBytes {
+    fn from(value: &'static str) -> Self {
+        StrBytes::new(Bytes::from_static(value.as_bytes()))
+    }
+}
+
+impl TryFrom<Bytes> for StrBytes {
+    type Error = Utf8Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        std::str::from_utf8(&bytes)?;
+        Ok(StrBytes::new(bytes))
+    }
+}
+
+impl PartialEq<str> for StrBytes {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for StrBytes {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::str_bytes::StrBytes;
+    use bytes::Bytes;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn invalid_utf8() {
+        assert!(StrBytes::try_from(Bytes::from_static(&[0xC3, 0x28])).is_err());
+        let valid = StrBytes::try_from(Bytes::from_static("héllo".as_bytes())).unwrap();
+        assert_eq!(valid, "héllo");
+        assert_eq!(valid, StrBytes::from("héllo"));
+        assert_eq!(valid, StrBytes::from(String::from("héllo")));
+    }
+}
@@ -0,0 +1,414 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Event streams over HTTP bodies
+//!
+//! - [`MessageStream`] decodes the messages of a response body
+//! - [`MessageStreamBody`] encodes a stream of messages into a request body
+//! - [`SignedEventStreamBody`] marshalls and signs a stream of events into a request body
+
+use crate::error::Error;
+use crate::frame::{MarshallMessage, Message, MessageFrameDecoder, SignMessage};
+use bytes::Bytes;
+use futures_core::Stream;
+use http::HeaderMap;
+use http_body::{Body, SizeHint};
+use pin_project::pin_project;
+use smithy_http::body::SdkBody;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// [`Stream`] of the messages of a response body
+#[pin_project]
+#[derive(Debug)]
+pub struct MessageStream {
+    #[pin]
+    body: SdkBody,
+    decoder: MessageFrameDecoder,
+    done: bool,
+}
+
+impl MessageStream {
+    pub fn new(body: SdkBody) -> Self {
+        MessageStream {
+            body,
+            decoder: MessageFrameDecoder::new(),
+            done: false,
+        }
+    }
+}
+
+impl Stream for MessageStream {
+    type Item = Result<Message, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            if *this.done {
+                return Poll::Ready(None);
+            }
+            match this.decoder.next_message() {
+                Ok(Some(message)) => return Poll::Ready(Some(Ok(message))),
+                Ok(None) => {}
+                Err(err) => {
+                    // The framing can't be recovered after an invalid message
+                    *this.done = true;
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+            match this.body.as_mut().poll_data(cx) {
+                Poll::Ready(Some(Ok(data))) => this.decoder.push(data),
+                Poll::Ready(Some(Err(err))) => {
+                    *this.done = true;
+                    return Poll::Ready(Some(Err(Error::ReadBody(err))));
+                }
+                Poll::Ready(None) => {
+                    *this.done = true;
+                    if this.decoder.has_partial_message() {
+                        return Poll::Ready(Some(Err(Error::UnexpectedEndOfStream)));
+                    }
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Request body that encodes a [`Stream`] of messages
+///
+/// Each message is sent as a data frame as soon as the stream produces it. If the stream fails,
+/// the body fails with the same error.
+#[pin_project]
+pub struct MessageStreamBody<S> {
+    #[pin]
+    messages: S,
+}
+
+impl<S, E> MessageStreamBody<S>
+where
+    S: Stream<Item = Result<Message, E>>,
+    E: Into<smithy_http::body::Error>,
+{
+    pub fn new(messages: S) -> Self {
+        MessageStreamBody { messages }
+    }
+}
+
+impl<S, E> MessageStreamBody<S>
+where
+    S: Stream<Item = Result<Message, E>> + Send + Sync + 'static,
+    E: Into<smithy_http::body::Error>,
+{
+    /// Convert the body into an [`SdkBody`] to send it with a request
+    ///
+    /// A streaming body can't be replayed, so the request can't be retried.
+    pub fn into_sdk_body(self) -> SdkBody {
+        SdkBody::from_dyn(http_body::combinators::BoxBody::new(self))
+    }
+}
+
+impl<S, E> Body for MessageStreamBody<S>
+where
+    S: Stream<Item = Result<Message, E>>,
+    E: Into<smithy_http::body::Error>,
+{
+    type Data = Bytes;
+    type Error = smithy_http::body::Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        match this.messages.poll_next(cx) {
+            Poll::Ready(Some(Ok(message))) => {
+                let mut buffer = Vec::new();
+                Poll::Ready(Some(
+                    message
+                        .write_to(&mut buffer)
+                        .map(|_| Bytes::from(buffer))
+                        .map_err(|err| err.into()),
+                ))
+            }
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(err.into()))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::default()
+    }
+}
+
+/// Request body that marshalls, signs and encodes a [`Stream`] of events
+///
+/// Each event is converted into a message by the marshaller and wrapped in a signed message by
+/// the signer. When the stream ends, a signed empty message is sent to mark the end of the
+/// events. The signer is usually a [`DeferredSigner`](crate::frame::DeferredSigner) since the
+/// signatures are seeded from the signature of the request.
+#[pin_project]
+pub struct SignedEventStreamBody<S, M> {
+    #[pin]
+    events: S,
+    marshaller: M,
+    signer: Box<dyn SignMessage + Send + Sync>,
+    end_sent: bool,
+}
+
+impl<S, M, T, E> SignedEventStreamBody<S, M>
+where
+    S: Stream<Item = Result<T, E>>,
+    M: MarshallMessage<Input = T>,
+    E: Into<smithy_http::body::Error>,
+{
+    pub fn new(events: S, marshaller: M, signer: impl SignMessage + Send + Sync + 'static) -> Self {
+        SignedEventStreamBody {
+            events,
+            marshaller,
+            signer: Box::new(signer),
+            end_sent: false,
+        }
+    }
+}
+
+impl<S, M, T, E> SignedEventStreamBody<S, M>
+where
+    S: Stream<Item = Result<T, E>> + Send + Sync + 'static,
+    M: MarshallMessage<Input = T> + Send + Sync + 'static,
+    E: Into<smithy_http::body::Error>,
+{
+    /// Convert the body into an [`SdkBody`] to send it with a request
+    ///
+    /// A streaming body can't be replayed, so the request can't be retried.
+    pub fn into_sdk_body(self) -> SdkBody {
+        SdkBody::from_dyn(http_body::combinators::BoxBody::new(self))
+    }
+}
+
+fn encode(message: Message) -> Result<Bytes, smithy_http::body::Error> {
+    let mut buffer = Vec::new();
+    message.write_to(&mut buffer)?;
+    Ok(Bytes::from(buffer))
+}
+
+impl<S, M, T, E> Body for SignedEventStreamBody<S, M>
+where
+    S: Stream<Item = Result<T, E>>,
+    M: MarshallMessage<Input = T>,
+    E: Into<smithy_http::body::Error>,
+{
+    type Data = Bytes;
+    type Error = smithy_http::body::Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        if *this.end_sent {
+            return Poll::Ready(None);
+        }
+        match this.events.poll_next(cx) {
+            Poll::Ready(Some(Ok(event))) => {
+                let signer = this.signer;
+                Poll::Ready(Some(
+                    this.marshaller
+                        .marshall(event)
+                        .map_err(|err| err.into())
+                        .and_then(|message| signer.sign(message))
+                        .and_then(encode),
+                ))
+            }
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(err.into()))),
+            Poll::Ready(None) => {
+                *this.end_sent = true;
+                Poll::Ready(Some(this.signer.sign_empty().and_then(encode)))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::Error;
+    use crate::frame::{
+        DeferredSigner, Header, HeaderValue, MarshallMessage, Message, SignMessage,
+        SignMessageError,
+    };
+    use crate::stream::{MessageStream, MessageStreamBody, SignedEventStreamBody};
+    use bytes::Bytes;
+    use futures_util::stream::{self, StreamExt};
+    use http_body::Body;
+    use smithy_http::body::SdkBody;
+    use std::convert::Infallible;
+
+    fn messages() -> Vec<Message> {
+        (0..3)
+            .map(|i| {
+                Message::new(format!("payload {}", i)).add_header(Header::new(
+                    ":event-type",
+                    HeaderValue::String("Record".into()),
+                ))
+            })
+            .collect()
+    }
+
+    fn encode(messages: &[Message]) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        for message in messages {
+            message.write_to(&mut buffer).unwrap();
+        }
+        buffer
+    }
+
+    /// A streaming body that returns `data` in chunks of `chunk_size` bytes
+    fn chunked_body(data: Vec<u8>, chunk_size: usize) -> SdkBody {
+        let chunks = data
+            .chunks(chunk_size)
+            .map(|chunk| Ok::<_, Infallible>(Bytes::copy_from_slice(chunk)))
+            .collect::<Vec<_>>();
+        SdkBody::from(hyper::Body::wrap_stream(stream::iter(chunks)))
+    }
+
+    #[tokio::test]
+    async fn read_message_stream() {
+        let body = chunked_body(encode(&messages()), 10);
+        let decoded = MessageStream::new(body)
+            .map(|message| message.expect("valid message"))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(decoded, messages());
+    }
+
+    #[tokio::test]
+    async fn truncated_message_stream() {
+        let mut data = encode(&messages());
+        data.truncate(data.len() - 1);
+        let decoded = MessageStream::new(chunked_body(data, 10))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(decoded.len(), 3);
+        assert!(decoded[1].is_ok());
+        assert!(matches!(decoded[2], Err(Error::UnexpectedEndOfStream)));
+    }
+
+    #[tokio::test]
+    async fn write_message_stream() {
+        let body = MessageStreamBody::new(stream::iter(
+            messages().into_iter().map(Ok::<_, Infallible>),
+        ))
+        .into_sdk_body();
+        let mut body = Box::pin(body);
+        let mut written = Vec::new();
+        while let Some(data) = body.data().await {
+            written.push(data.expect("valid data"));
+        }
+        // one data frame per message
+        assert_eq!(written.len(), 3);
+        assert_eq!(written.concat(), encode(&messages()));
+    }
+
+    #[derive(Debug)]
+    struct TestMarshaller;
+
+    impl MarshallMessage for TestMarshaller {
+        type Input = String;
+
+        fn marshall(&self, input: String) -> Result<Message, Error> {
+            Ok(Message::new(input))
+        }
+    }
+
+    /// Wraps messages in a message with a sequence number
+    #[derive(Debug, Default)]
+    struct TestSigner {
+        sequence: i32,
+    }
+
+    impl SignMessage for TestSigner {
+        fn sign(&mut self, message: Message) -> Result<Message, SignMessageError> {
+            let mut payload = Vec::new();
+            message.write_to(&mut payload)?;
+            self.sequence += 1;
+            Ok(Message::new(payload)
+                .add_header(Header::new(":sequence", HeaderValue::Int32(self.sequence))))
+        }
+
+        fn sign_empty(&mut self) -> Result<Message, SignMessageError> {
+            self.sign(Message::new(&b""[..]))
+        }
+    }
+
+    async fn read_signed_body(body: SdkBody) -> Result<Vec<Message>, smithy_http::body::Error> {
+        let mut body = Box::pin(body);
+        let mut messages = Vec::new();
+        while let Some(data) = body.data().await {
+            let mut data = data?;
+            messages.push(Message::read_from(&mut data).expect("valid message"));
+            assert!(data.is_empty(), "one data frame per message");
+        }
+        Ok(messages)
+    }
+
+    #[tokio::test]
+    async fn write_signed_event_stream() {
+        let events = vec!["one".to_string(), "two".to_string()];
+        let body = SignedEventStreamBody::new(
+            stream::iter(events.into_iter().map(Ok::<_, Infallible>)),
+            TestMarshaller,
+            TestSigner::default(),
+        )
+        .into_sdk_body();
+        let messages = read_signed_body(body).await.unwrap();
+        assert_eq!(messages.len(), 3);
+        let unwrapped = messages
+            .iter()
+            .map(|message| Message::read_from(&mut message.payload().clone()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(unwrapped[0], Message::new("one"));
+        assert_eq!(unwrapped[1], Message::new("two"));
+        // the end of the stream is marked by a signed empty message
+        assert_eq!(unwrapped[2], Message::new(&b""[..]));
+        assert_eq!(
+            messages[2].header(":sequence").unwrap(),
+            &HeaderValue::Int32(3)
+        );
+    }
+
+    #[tokio::test]
+    async fn deferred_signer() {
+        let events = || stream::iter(vec![Ok::<_, Infallible>("one".to_string())]);
+
+        let (signer, sender) = DeferredSigner::new();
+        let body = SignedEventStreamBody::new(events(), TestMarshaller, signer).into_sdk_body();
+        sender.send(Box::new(TestSigner::default()));
+        assert_eq!(read_signed_body(body).await.unwrap().len(), 2);
+
+        // the body fails if the signer wasn't provided by the time the first event is sent
+        let (signer, _sender) = DeferredSigner::new();
+        let body = SignedEventStreamBody::new(events(), TestMarshaller, signer).into_sdk_body();
+        assert!(read_signed_body(body).await.is_err());
+    }
+}
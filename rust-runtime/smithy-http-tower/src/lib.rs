@@ -6,6 +6,7 @@
 pub mod dispatch;
 pub mod map_request;
 pub mod parse_response;
+pub mod wire_log;
 
 use smithy_http::result::SdkError;
 use tower::BoxError;
@@ -54,17 +55,20 @@ impl<E> From<SendOperationError> for SdkError<E> {
 #[cfg(test)]
 mod tests {
     use crate::dispatch::DispatchLayer;
-    use crate::map_request::MapRequestLayer;
+    use crate::map_request::{AsyncMapRequestLayer, MapRequestLayer};
     use crate::parse_response::ParseResponseLayer;
+    use crate::SendOperationError;
     use bytes::Bytes;
     use http::Response;
     use smithy_http::body::SdkBody;
-    use smithy_http::middleware::MapRequest;
+    use smithy_http::middleware::{AsyncMapRequest, MapRequest};
     use smithy_http::operation;
     use smithy_http::operation::{Operation, Request};
     use smithy_http::response::ParseStrictResponse;
     use std::convert::{Infallible, TryInto};
-    use tower::{service_fn, Service, ServiceBuilder};
+    use std::future::{Future, Ready};
+    use std::pin::Pin;
+    use tower::{service_fn, Service, ServiceBuilder, ServiceExt};
 
     /// Creates a stubbed service stack and runs it to validate that all the types line up &
     /// everything is properly wired
@@ -111,4 +115,61 @@ mod tests {
         let resp = svc.call(req).await.expect("Response should succeed");
         assert_eq!(resp.parsed, "OK".to_string())
     }
+
+    #[tokio::test]
+    async fn async_map_request() {
+        #[derive(Clone)]
+        struct AddHeaderLater;
+        impl AsyncMapRequest for AddHeaderLater {
+            type Error = Infallible;
+            type Future = Pin<Box<dyn Future<Output = Result<Request, Infallible>> + Send>>;
+            fn apply(&self, request: Request) -> Self::Future {
+                Box::pin(async move {
+                    tokio::task::yield_now().await;
+                    request.augment(|mut req, _| {
+                        req.headers_mut()
+                            .insert("X-Test", "Value".try_into().unwrap());
+                        Ok(req)
+                    })
+                })
+            }
+        }
+
+        #[derive(Clone)]
+        struct Fail;
+        impl AsyncMapRequest for Fail {
+            type Error = &'static str;
+            type Future = Ready<Result<Request, &'static str>>;
+            fn apply(&self, _request: Request) -> Self::Future {
+                std::future::ready(Err("failed"))
+            }
+        }
+
+        let http_layer = service_fn(|request: http::Request<SdkBody>| async move {
+            assert_eq!(request.headers().get("X-Test").unwrap(), "Value");
+            Result::<_, Infallible>::Ok(http::Response::new(SdkBody::from("ok")))
+        });
+        // the layer can be reused to build several stacks
+        let layer = AsyncMapRequestLayer::for_mapper(AddHeaderLater);
+        for _ in 0..2 {
+            let mut svc = ServiceBuilder::new()
+                .layer(layer.clone())
+                .layer(DispatchLayer)
+                .service(http_layer);
+            let req = Request::new(http::Request::new(SdkBody::from("hello")));
+            let resp = svc.ready().await.unwrap().call(req).await;
+            assert!(resp.is_ok());
+        }
+
+        let mut svc = ServiceBuilder::new()
+            .layer(AsyncMapRequestLayer::for_mapper(Fail))
+            .layer(DispatchLayer)
+            .service(http_layer);
+        let req = Request::new(http::Request::new(SdkBody::from("hello")));
+        let err = svc.ready().await.unwrap().call(req).await.unwrap_err();
+        assert!(matches!(
+            err,
+            SendOperationError::RequestConstructionError(_)
+        ));
+    }
 }
@@ -5,13 +5,75 @@
 
 use crate::SendOperationError;
 use pin_project::pin_project;
-use smithy_http::middleware::MapRequest;
+use smithy_http::middleware::{AsyncMapRequest, MapRequest};
 use smithy_http::operation;
 use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use tower::{Layer, Service};
 
+#[derive(Clone)]
+/// Tower service for [`AsyncMapRequest`](smithy_http::middleware::AsyncMapRequest)
+pub struct AsyncMapRequestService<S, M> {
+    inner: S,
+    mapper: M,
+}
+
+#[derive(Clone, Debug)]
+pub struct AsyncMapRequestLayer<M> {
+    mapper: M,
+}
+
+impl<M: AsyncMapRequest + Clone> AsyncMapRequestLayer<M> {
+    pub fn for_mapper(mapper: M) -> Self {
+        AsyncMapRequestLayer { mapper }
+    }
+}
+
+impl<S, M> Layer<S> for AsyncMapRequestLayer<M>
+where
+    M: Clone,
+{
+    type Service = AsyncMapRequestService<S, M>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AsyncMapRequestService {
+            inner,
+            mapper: self.mapper.clone(),
+        }
+    }
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+impl<S, M> Service<operation::Request> for AsyncMapRequestService<S, M>
+where
+    S: Service<operation::Request, Error = SendOperationError> + Clone + Send + 'static,
+    S::Future: Send,
+    M: AsyncMapRequest,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: operation::Request) -> Self::Future {
+        // `self.inner` has been driven to readiness: take it and leave a fresh clone behind.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let future = self.mapper.apply(req);
+        Box::pin(async move {
+            let mapped_request = future
+                .await
+                .map_err(|e| SendOperationError::RequestConstructionError(e.into()))?;
+            inner.call(mapped_request).await
+        })
+    }
+}
+
 #[derive(Clone)]
 /// Tower service for [`MapRequest`](smithy_http::middleware::MapRequest)
 pub struct MapRequestService<S, M> {
@@ -19,7 +81,7 @@ pub struct MapRequestService<S, M> {
     mapper: M,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct MapRequestLayer<M> {
     mapper: M,
 }
@@ -0,0 +1,424 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0.
+ */
+
+//! Logging of the requests and responses sent over the wire
+//!
+//! [`WireLogLayer`] logs the method, URI and headers of requests, and the status and headers of
+//! responses, through `tracing`. Bodies can be logged as well, but only when they are in memory:
+//! streaming bodies are never read by the logger and are logged as `<streaming body>`. This is the
+//! case of every response body received by a hyper-based connector, so with those connectors only
+//! request bodies are actually logged.
+//!
+//! Credentials are redacted from the logs: the `Authorization` and `x-amz-security-token` headers,
+//! and the `X-Amz-Credential`, `X-Amz-Security-Token` and `X-Amz-Signature` query parameters of
+//! presigned URIs, are always redacted, and so are the parts of a request or response that are
+//! bound to `@sensitive` members (see [`SensitiveData`]).
+//!
+//! The layer sees the request exactly as it will be dispatched when it is the innermost layer of the
+//! middleware, ie. after signing:
+//! ```rust
+//! use smithy_http_tower::wire_log::WireLogLayer;
+//! use tower::layer::util::{Identity, Stack};
+//!
+//! # let middleware = Identity::new();
+//! let middleware = Stack::new(WireLogLayer::new().with_bodies(true), middleware);
+//! ```
+
+use crate::SendOperationError;
+use http::header::{HeaderName, AUTHORIZATION};
+use http::HeaderMap;
+use pin_project::pin_project;
+use smithy_http::body::SdkBody;
+use smithy_http::operation;
+use smithy_http::operation::SensitiveData;
+use smithy_http::query::redact_query;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+use tracing::Level;
+
+const REDACTED: &str = "*** Sensitive Data Redacted ***";
+
+const REDACTED_QUERY_PARAMS: &[&str] = &[
+    "X-Amz-Credential",
+    "X-Amz-Security-Token",
+    "X-Amz-Signature",
+];
+
+/// Log an event at a level only known at runtime
+macro_rules! log_at {
+    ($level:expr, $($args:tt)+) => {
+        match $level {
+            Level::ERROR => tracing::error!($($args)+),
+            Level::WARN => tracing::warn!($($args)+),
+            Level::INFO => tracing::info!($($args)+),
+            Level::DEBUG => tracing::debug!($($args)+),
+            Level::TRACE => tracing::trace!($($args)+),
+        }
+    };
+}
+
+#[derive(Clone, Debug)]
+struct Config {
+    level: Level,
+    bodies: bool,
+    redacted_headers: Vec<HeaderName>,
+}
+
+/// Layer that logs requests and responses (see the [module documentation](crate::wire_log))
+#[derive(Clone, Debug)]
+pub struct WireLogLayer {
+    config: Config,
+}
+
+impl Default for WireLogLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WireLogLayer {
+    /// Log requests and responses at the `DEBUG` level, without their bodies
+    pub fn new() -> Self {
+        WireLogLayer {
+            config: Config {
+                level: Level::DEBUG,
+                bodies: false,
+                redacted_headers: vec![
+                    AUTHORIZATION,
+                    HeaderName::from_static("x-amz-security-token"),
+                ],
+            },
+        }
+    }
+
+    /// Log at `level` instead of `DEBUG`
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.config.level = level;
+        self
+    }
+
+    /// Log the bodies of requests and responses that are in memory
+    ///
+    /// Streaming bodies, such as the bodies of the responses received by a hyper-based connector,
+    /// are logged as `<streaming body>`.
+    pub fn with_bodies(mut self, bodies: bool) -> Self {
+        self.config.bodies = bodies;
+        self
+    }
+
+    /// Redact the header `name` in addition to `Authorization` and `x-amz-security-token`
+    pub fn redact_header(mut self, name: HeaderName) -> Self {
+        self.config.redacted_headers.push(name);
+        self
+    }
+}
+
+impl<S> Layer<S> for WireLogLayer {
+    type Service = WireLogService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        WireLogService {
+            inner,
+            config: Arc::new(self.config.clone()),
+        }
+    }
+}
+
+/// Tower service that logs requests and responses (see [`WireLogLayer`])
+#[derive(Clone, Debug)]
+pub struct WireLogService<S> {
+    inner: S,
+    config: Arc<Config>,
+}
+
+impl<S> Service<operation::Request> for WireLogService<S>
+where
+    S: Service<operation::Request, Response = http::Response<SdkBody>, Error = SendOperationError>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = WireLogFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: operation::Request) -> Self::Future {
+        let sensitive = req
+            .config()
+            .get::<SensitiveData>()
+            .cloned()
+            .unwrap_or_default();
+        log_request(&self.config, &sensitive, req.request());
+        WireLogFuture {
+            inner: self.inner.call(req),
+            config: self.config.clone(),
+            sensitive,
+        }
+    }
+}
+
+#[pin_project]
+pub struct WireLogFuture<F> {
+    #[pin]
+    inner: F,
+    config: Arc<Config>,
+    sensitive: SensitiveData,
+}
+
+impl<F> Future for WireLogFuture<F>
+where
+    F: Future<Output = Result<http::Response<SdkBody>, SendOperationError>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = match this.inner.poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+        match &result {
+            Ok(response) => log_response(this.config, this.sensitive, response),
+            Err(err) => log_at!(this.config.level, error = ?err, "request failed"),
+        }
+        Poll::Ready(result)
+    }
+}
+
+fn log_request(config: &Config, sensitive: &SensitiveData, request: &http::Request<SdkBody>) {
+    let uri = Uri {
+        uri: request.uri(),
+        redacted: sensitive.uri(),
+    };
+    let headers = Headers {
+        headers: request.headers(),
+        config,
+        sensitive,
+    };
+    if config.bodies {
+        let body = Body {
+            body: request.body(),
+            redacted: sensitive.request_body(),
+        };
+        log_at!(config.level, method = %request.method(), uri = %uri, headers = ?headers, body = %body, "sending request");
+    } else {
+        log_at!(config.level, method = %request.method(), uri = %uri, headers = ?headers, "sending request");
+    }
+}
+
+fn log_response(config: &Config, sensitive: &SensitiveData, response: &http::Response<SdkBody>) {
+    let headers = Headers {
+        headers: response.headers(),
+        config,
+        sensitive,
+    };
+    if config.bodies {
+        let body = Body {
+            body: response.body(),
+            redacted: sensitive.response_body(),
+        };
+        log_at!(config.level, status = %response.status(), headers = ?headers, body = %body, "received response");
+    } else {
+        log_at!(config.level, status = %response.status(), headers = ?headers, "received response");
+    }
+}
+
+struct Uri<'a> {
+    uri: &'a http::Uri,
+    redacted: bool,
+}
+
+impl Display for Uri<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if !self.redacted {
+            let uri = redact_query(&self.uri.to_string(), REDACTED, |name| {
+                REDACTED_QUERY_PARAMS
+                    .iter()
+                    .any(|redacted| redacted.eq_ignore_ascii_case(name))
+            });
+            return f.write_str(&uri);
+        }
+        // the path and query may contain the value of sensitive members, but not the host
+        if let (Some(scheme), Some(authority)) = (self.uri.scheme(), self.uri.authority()) {
+            write!(f, "{}://{}", scheme, authority)?;
+        }
+        write!(f, "/{}", REDACTED)
+    }
+}
+
+struct Headers<'a> {
+    headers: &'a HeaderMap,
+    config: &'a Config,
+    sensitive: &'a SensitiveData,
+}
+
+impl Debug for Headers<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (name, value) in self.headers {
+            if self.config.redacted_headers.contains(name) || self.sensitive.header(name.as_str()) {
+                map.entry(name, &REDACTED);
+            } else {
+                map.entry(name, value);
+            }
+        }
+        map.finish()
+    }
+}
+
+struct Body<'a> {
+    body: &'a SdkBody,
+    redacted: bool,
+}
+
+impl Display for Body<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.redacted {
+            return f.write_str(REDACTED);
+        }
+        // only bodies that are in memory can be logged without consuming them
+        match self.body.bytes() {
+            Some(bytes) => f.write_str(&String::from_utf8_lossy(bytes)),
+            None => f.write_str("<streaming body>"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::wire_log::{Body, Headers, Uri, WireLogLayer};
+    use crate::SendOperationError;
+    use http::header::CONTENT_TYPE;
+    use smithy_http::body::SdkBody;
+    use smithy_http::operation;
+    use smithy_http::operation::SensitiveData;
+    use tower::{service_fn, Layer, Service, ServiceExt};
+
+    fn request(body: SdkBody) -> http::Request<SdkBody> {
+        http::Request::builder()
+            .uri("https://example.com/bucket/key?secret=abc")
+            .header("authorization", "AWS4-HMAC-SHA256 Signature=abcd")
+            .header("x-amz-security-token", "token")
+            .header("x-amz-secret", "hunter2")
+            .header("content-type", "application/json")
+            .body(body)
+            .unwrap()
+    }
+
+    #[test]
+    fn redact_credentials() {
+        let layer = WireLogLayer::new();
+        let request = request(SdkBody::empty());
+        let headers = Headers {
+            headers: request.headers(),
+            config: &layer.config,
+            sensitive: &SensitiveData::new(),
+        };
+        assert_eq!(
+            format!("{:?}", headers),
+            r#"{"authorization": "*** Sensitive Data Redacted ***", "x-amz-security-token": "*** Sensitive Data Redacted ***", "x-amz-secret": "hunter2", "content-type": "application/json"}"#
+        );
+    }
+
+    #[test]
+    fn redact_sensitive_data() {
+        let layer = WireLogLayer::new().redact_header(CONTENT_TYPE);
+        let request = request(SdkBody::from("body"));
+        let sensitive = SensitiveData::new()
+            .with_header("X-Amz-Secret")
+            .with_uri()
+            .with_request_body();
+        let headers = Headers {
+            headers: request.headers(),
+            config: &layer.config,
+            sensitive: &sensitive,
+        };
+        assert_eq!(
+            format!("{:?}", headers),
+            r#"{"authorization": "*** Sensitive Data Redacted ***", "x-amz-security-token": "*** Sensitive Data Redacted ***", "x-amz-secret": "*** Sensitive Data Redacted ***", "content-type": "*** Sensitive Data Redacted ***"}"#
+        );
+        let uri = Uri {
+            uri: request.uri(),
+            redacted: sensitive.uri(),
+        };
+        assert_eq!(
+            uri.to_string(),
+            "https://example.com/*** Sensitive Data Redacted ***"
+        );
+        let body = Body {
+            body: request.body(),
+            redacted: sensitive.request_body(),
+        };
+        assert_eq!(body.to_string(), "*** Sensitive Data Redacted ***");
+    }
+
+    #[test]
+    fn redact_presigned_uri() {
+        let uri = "https://bucket.s3.amazonaws.com/key?X-Amz-Algorithm=AWS4-HMAC-SHA256\
+            &X-Amz-Credential=AKIDEXAMPLE%2F20210618%2Fus-east-1%2Fs3%2Faws4_request\
+            &X-Amz-Date=20210618T170728Z&X-Amz-Expires=900&X-Amz-Security-Token=token\
+            &X-Amz-SignedHeaders=host&X-Amz-Signature=abcd"
+            .parse()
+            .unwrap();
+        let uri = Uri {
+            uri: &uri,
+            redacted: false,
+        };
+        assert_eq!(
+            uri.to_string(),
+            "https://bucket.s3.amazonaws.com/key?X-Amz-Algorithm=AWS4-HMAC-SHA256\
+            &X-Amz-Credential=*** Sensitive Data Redacted ***\
+            &X-Amz-Date=20210618T170728Z&X-Amz-Expires=900\
+            &X-Amz-Security-Token=*** Sensitive Data Redacted ***\
+            &X-Amz-SignedHeaders=host&X-Amz-Signature=*** Sensitive Data Redacted ***"
+        );
+
+        // other query parameters are logged as is
+        let request = request(SdkBody::empty());
+        let uri = Uri {
+            uri: request.uri(),
+            redacted: false,
+        };
+        assert_eq!(uri.to_string(), "https://example.com/bucket/key?secret=abc");
+    }
+
+    #[test]
+    fn log_bodies_in_memory() {
+        let body = SdkBody::from("{\"key\": \"value\"}");
+        let body = Body {
+            body: &body,
+            redacted: false,
+        };
+        assert_eq!(body.to_string(), r#"{"key": "value"}"#);
+
+        let body = SdkBody::from(hyper::Body::from("streaming"));
+        let body = Body {
+            body: &body,
+            redacted: false,
+        };
+        assert_eq!(body.to_string(), "<streaming body>");
+    }
+
+    #[tokio::test]
+    async fn streaming_bodies_are_not_consumed() {
+        let mut svc = WireLogLayer::new().with_bodies(true).layer(service_fn(
+            |req: operation::Request| async move {
+                let (request, _) = req.into_parts();
+                let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                assert_eq!(body.as_ref(), b"streaming");
+                Ok::<_, SendOperationError>(http::Response::new(SdkBody::from("response")))
+            },
+        ));
+        let request =
+            operation::Request::new(request(SdkBody::from(hyper::Body::from("streaming"))));
+        let response = svc.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.body().bytes(), Some(&b"response"[..]));
+    }
+}
@@ -17,6 +17,7 @@ use bytes::{Buf, Bytes};
 use http::Response;
 use http_body::Body;
 use std::error::Error;
+use std::future::Future;
 use tracing::trace;
 
 type BoxError = Box<dyn Error + Send + Sync>;
@@ -60,6 +61,44 @@ pub trait MapRequest {
     fn apply(&self, request: operation::Request) -> Result<operation::Request, Self::Error>;
 }
 
+/// [`AsyncMapRequest`] defines an asynchronous middleware that transforms an [`operation::Request`].
+///
+/// Typically, these middleware will read configuration from the `PropertyBag` and use it to
+/// augment the request. Most middleware should be expressed as [`MapRequest`]. `AsyncMapRequest`
+/// is intended for middleware that must wait on I/O, eg. loading credentials from a remote
+/// credentials provider prior to signing.
+///
+/// ```rust
+/// # use smithy_http::middleware::AsyncMapRequest;
+/// # use smithy_http::operation;
+/// use std::convert::Infallible;
+/// use std::future::Future;
+/// use std::pin::Pin;
+/// struct Delay;
+/// impl AsyncMapRequest for Delay {
+///     type Error = Infallible;
+///     type Future = Pin<Box<dyn Future<Output = Result<operation::Request, Infallible>> + Send>>;
+///     fn apply(&self, request: operation::Request) -> Self::Future {
+///         Box::pin(async move {
+///             // eg. `tokio::time::sleep(...).await;`
+///             Ok(request)
+///         })
+///     }
+/// }
+/// ```
+pub trait AsyncMapRequest {
+    /// The Error type returned by this operation.
+    ///
+    /// If this middleware never fails use [std::convert::Infallible] or similar.
+    type Error: Into<BoxError> + 'static;
+
+    /// The future returned by [`apply`](AsyncMapRequest::apply)
+    type Future: Future<Output = Result<operation::Request, Self::Error>> + Send + 'static;
+
+    /// Apply this middleware to a request.
+    fn apply(&self, request: operation::Request) -> Self::Future;
+}
+
 /// Load a response using `handler` to parse the results.
 ///
 /// This function is intended to be used on the response side of a middleware chain.
@@ -37,6 +37,81 @@ impl Metadata {
     }
 }
 
+/// The parts of the requests and responses of an operation that carry `@sensitive` members
+///
+/// Generated code inserts `SensitiveData` into the property bag of operations with sensitive
+/// members so that middleware that logs requests and responses knows what to redact.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SensitiveData {
+    headers: Vec<Cow<'static, str>>,
+    header_prefixes: Vec<Cow<'static, str>>,
+    uri: bool,
+    request_body: bool,
+    response_body: bool,
+}
+
+impl SensitiveData {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The header `name` is bound to a sensitive member
+    pub fn with_header(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.headers.push(name.into());
+        self
+    }
+
+    /// The headers starting with `prefix` are bound to a sensitive member
+    pub fn with_header_prefix(mut self, prefix: impl Into<Cow<'static, str>>) -> Self {
+        self.header_prefixes.push(prefix.into());
+        self
+    }
+
+    /// A sensitive member is bound to the path or the query string of the request
+    pub fn with_uri(mut self) -> Self {
+        self.uri = true;
+        self
+    }
+
+    /// The body of the request contains a sensitive member
+    pub fn with_request_body(mut self) -> Self {
+        self.request_body = true;
+        self
+    }
+
+    /// The body of the response contains a sensitive member
+    pub fn with_response_body(mut self) -> Self {
+        self.response_body = true;
+        self
+    }
+
+    /// Is the header `name` bound to a sensitive member? Header names are case insensitive.
+    pub fn header(&self, name: &str) -> bool {
+        self.headers
+            .iter()
+            .any(|header| header.eq_ignore_ascii_case(name))
+            || self.header_prefixes.iter().any(|prefix| {
+                name.len() >= prefix.len()
+                    && name.as_bytes()[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+            })
+    }
+
+    /// Is a sensitive member bound to the path or the query string of the request?
+    pub fn uri(&self) -> bool {
+        self.uri
+    }
+
+    /// Does the body of the request contain a sensitive member?
+    pub fn request_body(&self) -> bool {
+        self.request_body
+    }
+
+    /// Does the body of the response contain a sensitive member?
+    pub fn response_body(&self) -> bool {
+        self.response_body
+    }
+}
+
 #[non_exhaustive]
 #[derive(Clone, Debug)]
 pub struct Parts<H, R> {
@@ -181,6 +256,10 @@ impl Request {
         self.configuration.lock().unwrap()
     }
 
+    pub fn request(&self) -> &http::Request<SdkBody> {
+        &self.inner
+    }
+
     pub fn request_mut(&mut self) -> &mut http::Request<SdkBody> {
         &mut self.inner
     }
@@ -211,7 +290,7 @@ impl Request {
 #[cfg(test)]
 mod test {
     use crate::body::SdkBody;
-    use crate::operation::Request;
+    use crate::operation::{Request, SensitiveData};
     use http::header::{AUTHORIZATION, CONTENT_LENGTH};
     use http::Uri;
 
@@ -241,4 +320,16 @@ mod test {
         assert_eq!(request.body().bytes().unwrap(), "hello world!".as_bytes());
         assert_eq!(config.lock().unwrap().get::<&str>(), Some(&"hello"));
     }
+
+    #[test]
+    fn sensitive_headers() {
+        let sensitive = SensitiveData::new()
+            .with_header("X-Amz-Secret")
+            .with_header_prefix("x-amz-meta-");
+        assert!(sensitive.header("x-amz-secret"));
+        assert!(sensitive.header("X-Amz-Meta-Password"));
+        assert!(!sensitive.header("x-amz-meta"));
+        assert!(!sensitive.header("content-length"));
+        assert!(!sensitive.request_body());
+    }
 }
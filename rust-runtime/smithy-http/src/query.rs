@@ -56,6 +56,38 @@ impl<'a> Writer<'a> {
     }
 }
 
+/// Replace the value of the query parameters of `uri` for which `redact` returns true with
+/// `replacement`
+///
+/// ```rust
+/// use smithy_http::query::redact_query;
+/// let uri = "https://example.com/key?X-Amz-Signature=abcd&versionId=1";
+/// assert_eq!(
+///     redact_query(uri, "REDACTED", |name| name == "X-Amz-Signature"),
+///     "https://example.com/key?X-Amz-Signature=REDACTED&versionId=1"
+/// );
+/// ```
+pub fn redact_query(uri: &str, replacement: &str, redact: impl Fn(&str) -> bool) -> String {
+    let mut parts = uri.splitn(2, '?');
+    let path = parts.next().unwrap_or_default();
+    let query = match parts.next() {
+        Some(query) => query,
+        None => return uri.to_string(),
+    };
+    let query = query
+        .split('&')
+        .map(|param| {
+            let name = param.split('=').next().unwrap_or_default();
+            if param.contains('=') && redact(name) {
+                format!("{}={}", name, replacement)
+            } else {
+                param.to_string()
+            }
+        })
+        .collect::<Vec<_>>();
+    format!("{}?{}", path, query.join("&"))
+}
+
 #[cfg(test)]
 mod test {
     use crate::query::fmt_string;
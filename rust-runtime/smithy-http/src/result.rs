@@ -4,6 +4,7 @@
  */
 
 use crate::body::SdkBody;
+use smithy_types::retry::ErrorKind;
 use std::error::Error;
 use std::fmt;
 use std::fmt::{Debug, Display, Formatter};
@@ -40,6 +41,16 @@ pub enum SdkError<E> {
     },
 }
 
+impl<E> SdkError<E> {
+    /// The raw response of the failed request, if a response was received
+    pub fn raw_response(&self) -> Option<&http::Response<SdkBody>> {
+        match self {
+            SdkError::ResponseError { raw, .. } | SdkError::ServiceError { raw, .. } => Some(raw),
+            SdkError::ConstructionFailure(_) | SdkError::DispatchFailure(_) => None,
+        }
+    }
+}
+
 impl<E> Display for SdkError<E>
 where
     E: Error,
@@ -67,3 +78,139 @@ where
         }
     }
 }
+
+/// Error from a connector, classified for retries
+///
+/// Connectors fail for many reasons, some of which go away when the request is sent again (eg. the
+/// connection was reset). Connectors return a `ConnectorError` to tell retry policies what kind
+/// of failure an error is, since it ends up boxed in [`SdkError::DispatchFailure`].
+///
+/// The `ConnectorError` only describes the class of the failure: the error of the connector is its
+/// [`source`](Error::source).
+#[derive(Debug)]
+pub struct ConnectorError {
+    err: BoxError,
+    kind: Option<ErrorKind>,
+}
+
+impl ConnectorError {
+    /// A failure that is likely to go away when the request is retried (eg. a connect error, a
+    /// timeout or a connection closed by the server)
+    pub fn transient(err: impl Into<BoxError>) -> Self {
+        ConnectorError {
+            err: err.into(),
+            kind: Some(ErrorKind::TransientError),
+        }
+    }
+
+    /// A failure that won't go away when the request is retried
+    pub fn other(err: impl Into<BoxError>) -> Self {
+        ConnectorError {
+            err: err.into(),
+            kind: None,
+        }
+    }
+
+    /// The kind of the failure, if it can be retried
+    pub fn retryable_error_kind(&self) -> Option<ErrorKind> {
+        self.kind
+    }
+
+    /// Find the `ConnectorError` in the chain of sources of `err`
+    ///
+    /// Connector errors may be wrapped by the HTTP client or by middleware before they reach the
+    /// [`SdkError`].
+    pub fn find<'a>(err: &'a (dyn Error + 'static)) -> Option<&'a ConnectorError> {
+        let mut next = Some(err);
+        while let Some(err) = next {
+            if let Some(err) = err.downcast_ref::<ConnectorError>() {
+                return Some(err);
+            }
+            next = err.source();
+        }
+        None
+    }
+}
+
+impl Display for ConnectorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            Some(ErrorKind::TransientError) => write!(f, "transient connector error"),
+            _ => write!(f, "connector error"),
+        }
+    }
+}
+
+impl Error for ConnectorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.err.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::body::SdkBody;
+    use crate::result::{ConnectorError, SdkError};
+    use smithy_types::retry::ErrorKind;
+    use std::error::Error;
+    use std::fmt::{self, Display, Formatter};
+
+    #[derive(Debug)]
+    struct Wrapper(ConnectorError);
+
+    impl Display for Wrapper {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            write!(f, "wrapped: {}", self.0)
+        }
+    }
+
+    impl Error for Wrapper {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[test]
+    fn find_connector_errors() {
+        let err: Box<dyn Error + Send + Sync> =
+            Box::new(Wrapper(ConnectorError::transient("connection reset")));
+        let found = ConnectorError::find(err.as_ref()).expect("wrapped connector error");
+        assert_eq!(
+            found.retryable_error_kind(),
+            Some(ErrorKind::TransientError)
+        );
+        assert_eq!(err.to_string(), "wrapped: transient connector error");
+        assert_eq!(
+            found.source().map(|err| err.to_string()),
+            Some("connection reset".to_string())
+        );
+
+        let err: Box<dyn Error + Send + Sync> = Box::new(ConnectorError::other("bad certificate"));
+        let found = ConnectorError::find(err.as_ref()).expect("connector error");
+        assert_eq!(found.retryable_error_kind(), None);
+        assert_eq!(err.to_string(), "connector error");
+
+        let err: Box<dyn Error + Send + Sync> = "not from a connector".into();
+        assert!(ConnectorError::find(err.as_ref()).is_none());
+    }
+
+    #[test]
+    fn raw_response() {
+        let err = SdkError::<ConnectorError>::ResponseError {
+            raw: http::Response::builder()
+                .status(500)
+                .body(SdkBody::from(""))
+                .unwrap(),
+            err: "unparseable".into(),
+        };
+        assert_eq!(
+            err.raw_response().map(|raw| raw.status()),
+            Some(http::StatusCode::INTERNAL_SERVER_ERROR)
+        );
+
+        let err = SdkError::<ConnectorError>::DispatchFailure(Box::new(ConnectorError::transient(
+            "connection reset",
+        )));
+        assert!(err.raw_response().is_none());
+    }
+}
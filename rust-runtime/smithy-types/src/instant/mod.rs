@@ -28,6 +28,13 @@ impl Instant {
         }
     }
 
+    pub fn from_epoch_millis(epoch_millis: i64) -> Self {
+        Instant {
+            seconds: epoch_millis.div_euclid(1000),
+            subsecond_nanos: epoch_millis.rem_euclid(1000) as u32 * 1_000_000,
+        }
+    }
+
     pub fn from_fractional_seconds(epoch_seconds: i64, fraction: f64) -> Self {
         let subsecond_nanos = (fraction * 1_000_000_000_f64) as u32;
         Instant::from_secs_and_nanos(epoch_seconds, subsecond_nanos)
@@ -132,6 +139,11 @@ impl Instant {
         self.seconds
     }
 
+    /// Milliseconds since the Unix epoch. Sub-millisecond precision is truncated.
+    pub fn epoch_millis(&self) -> i64 {
+        self.seconds * 1000 + (self.subsecond_nanos / 1_000_000) as i64
+    }
+
     pub fn fmt(&self, format: Format) -> String {
         match format {
             Format::DateTime => format::rfc3339::format(&self),
@@ -174,6 +186,16 @@ mod test {
     use crate::instant::Format;
     use crate::Instant;
 
+    #[test]
+    fn epoch_millis() {
+        let instant = Instant::from_epoch_millis(1576540098123);
+        assert_eq!(instant.epoch_seconds(), 1576540098);
+        assert_eq!(instant.epoch_millis(), 1576540098123);
+        let before_epoch = Instant::from_epoch_millis(-1500);
+        assert_eq!(before_epoch.epoch_seconds(), -2);
+        assert_eq!(before_epoch.epoch_millis(), -1500);
+    }
+
     #[test]
     fn test_instant_fmt() {
         let instant = Instant::from_epoch_seconds(1576540098);